@@ -6,6 +6,246 @@
 use std::fmt::Display;
 use std::fmt::Write;
 
+// intrinsics of one register type, used to generate its `exp_{name}` and `activate_{name}`
+// functions, that evaluate `nano_gemm_core::Activation` on each lane of a register
+struct ActivationOps {
+    ty: &'static str,
+    reg_ty: String,
+    name: String,
+    // emitted before each function
+    attrs: &'static str,
+    // emitted at the start of each function
+    imports: &'static str,
+
+    set1: String,
+    add: String,
+    sub: String,
+    mul: String,
+    div: String,
+    max: String,
+    min: String,
+    // `mul_add(a, b, c) = a * b + c`
+    mul_add: String,
+    // shifts the low bits of `{}` into the exponent, and reinterprets them as a float
+    pow2: String,
+}
+
+impl ActivationOps {
+    fn x86(ty: &'static str, bits: usize, attrs: &'static str) -> Self {
+        let prefix = if bits == 128 {
+            "_mm".to_string()
+        } else {
+            format!("_mm{bits}")
+        };
+        let (suffix, reg_suffix, int, shift) = if ty == "f32" {
+            ("ps", "", "epi32", 23)
+        } else {
+            ("pd", "d", "epi64", 52)
+        };
+        let op = |op: &str| format!("{prefix}_{op}_{suffix}");
+
+        Self {
+            ty,
+            reg_ty: format!("crate::x86::__m{bits}{reg_suffix}"),
+            name: bits.to_string(),
+            attrs,
+            imports: r#"
+                #[cfg(target_arch = "x86_64")]
+                use core::arch::x86_64::*;
+                #[cfg(target_arch = "x86")]
+                use core::arch::x86::*;
+            "#,
+            set1: op("set1"),
+            add: op("add"),
+            sub: op("sub"),
+            mul: op("mul"),
+            div: op("div"),
+            max: op("max"),
+            min: op("min"),
+            mul_add: op("fmadd"),
+            pow2: format!(
+                "{prefix}_castsi{bits}_{suffix}({prefix}_slli_{int}::<{shift}>({prefix}_cast{suffix}_si{bits}({{}})))"
+            ),
+        }
+    }
+
+    // the module is expected to define `mul_add`, like the aarch64 kernels
+    fn neon(ty: &'static str) -> Self {
+        let (reg_ty, int, shift) = if ty == "f32" {
+            ("float32x4_t", "s32", 23)
+        } else {
+            ("float64x2_t", "s64", 52)
+        };
+        let op = |op: &str| format!("v{op}q_{ty}");
+
+        Self {
+            ty,
+            reg_ty: reg_ty.to_string(),
+            name: "neon".to_string(),
+            attrs: "",
+            imports: "",
+            set1: format!("vdupq_n_{ty}"),
+            add: op("add"),
+            sub: op("sub"),
+            mul: op("mul"),
+            div: op("div"),
+            max: op("max"),
+            min: op("min"),
+            mul_add: "mul_add".to_string(),
+            pow2: format!(
+                "vreinterpretq_{ty}_{int}(vshlq_n_{int}::<{shift}>(vreinterpretq_{int}_{ty}({{}})))"
+            ),
+        }
+    }
+}
+
+impl Display for ActivationOps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            ty,
+            reg_ty,
+            name,
+            attrs,
+            imports,
+            set1,
+            add,
+            sub,
+            mul,
+            div,
+            max,
+            min,
+            mul_add,
+            pow2,
+        } = self;
+
+        // float literal, rounded to the precision of `ty`
+        let lit = |x: f64| {
+            if *ty == "f32" {
+                format!("{:?}", x as f32)
+            } else {
+                format!("{x:?}")
+            }
+        };
+
+        // exp(x) = 2^n * exp(r), where n = round(x / ln(2)) and |r| <= ln(2) / 2.
+        //
+        // `x` is clamped so that 2^n is a normal number. adding `magic` rounds `x / ln(2)` to an
+        // integer, and leaves the biased exponent of 2^n in the low bits of the sum. `ln(2)` is
+        // split in two parts, the first of which is exact when multiplied by `n`, and exp(r) is
+        // given by its taylor series.
+        let (lo, hi, magic, ln2_hi, ln2_lo, degree) = if *ty == "f32" {
+            (
+                "-87.0",
+                "88.0",
+                "8388735.0",
+                "0.693359375",
+                "-2.1219444e-4",
+                7,
+            )
+        } else {
+            (
+                "-708.0",
+                "709.0",
+                "4503599627371519.0",
+                "6.931471803691238e-1",
+                "1.9082149292705877e-10",
+                12,
+            )
+        };
+
+        write!(f, "{attrs} #[inline(always)]\n")?;
+        write!(
+            f,
+            "unsafe fn exp_{name}(x: {reg_ty}) -> {reg_ty} {{ {imports}\n"
+        )?;
+        // NaN goes through, since x86 returns the second operand of min and max if either is NaN
+        write!(f, "let x = {min}({set1}({hi}), {max}({set1}({lo}), x));\n")?;
+        write!(
+            f,
+            "let t = {mul_add}(x, {set1}(core::{ty}::consts::LOG2_E), {set1}({magic}));\n"
+        )?;
+        write!(f, "let n = {sub}(t, {set1}({magic}));\n")?;
+        write!(f, "let r = {sub}(x, {mul}(n, {set1}({ln2_hi})));\n")?;
+        write!(f, "let r = {sub}(r, {mul}(n, {set1}({ln2_lo})));\n")?;
+        let coeffs = (0..=degree)
+            .map(|k| lit(1.0 / (1..=k).map(|i| i as f64).product::<f64>()))
+            .collect::<Vec<_>>();
+        write!(f, "let p = {set1}({});\n", coeffs[degree])?;
+        for c in coeffs[..degree].iter().rev() {
+            write!(f, "let p = {mul_add}(p, r, {set1}({c}));\n")?;
+        }
+        write!(f, "{mul}(p, {})\n", pow2.replace("{}", "t"))?;
+        write!(f, "}}\n")?;
+
+        // gelu(x) = x * sigmoid(2 * sqrt(2 / pi) * (x + 0.044715 * x^3))
+        let gelu_1 = -2.0 * (2.0 / std::f64::consts::PI).sqrt();
+        let gelu_3 = lit(gelu_1 * 0.044715);
+        let gelu_1 = lit(gelu_1);
+
+        write!(f, "{attrs} #[inline(always)]\n")?;
+        write!(
+            f,
+            "unsafe fn activate_{name}(activation: nano_gemm_core::Activation<{ty}>, x: {reg_ty}) -> {reg_ty} {{ {imports}\n"
+        )?;
+        write!(f, "use nano_gemm_core::Activation;\n")?;
+        write!(f, "let zero = {set1}(0.0);\n")?;
+        write!(f, "let one = {set1}(1.0);\n")?;
+        write!(f, "match activation {{\n")?;
+        write!(f, "Activation::None => x,\n")?;
+        write!(f, "Activation::Relu => {max}(zero, x),\n")?;
+        write!(
+            f,
+            "Activation::LeakyRelu(slope) => {mul_add}({set1}(slope), {min}(zero, x), {max}(zero, x)),\n"
+        )?;
+        write!(
+            f,
+            "Activation::Gelu => {div}(x, {add}(one, exp_{name}({mul}(x, {mul_add}({mul}(x, x), {set1}({gelu_3}), {set1}({gelu_1})))))),\n"
+        )?;
+        write!(
+            f,
+            "Activation::Sigmoid => {div}(one, {add}(one, exp_{name}({sub}(zero, x)))),\n"
+        )?;
+        write!(
+            f,
+            "Activation::Tanh => {sub}(one, {div}({set1}(2.0), {add}(one, exp_{name}({add}(x, x))))),\n"
+        )?;
+        write!(f, "}}\n")?;
+        write!(f, "}}\n")
+    }
+}
+
+// emits the pointer and column stride of the matrix read in place of `dst`, which is `dst` itself
+// unless a separate `src` is given
+fn write_src(f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+        f,
+        "let (src, src_cs) = if src.is_null() {{ (dst as *const _, dst_cs) }} else {{ (src, src_cs) }};\n"
+    )
+}
+
+// emits the factor of `k_scale` applied to the row `depth` of `rhs`. the complex kernels apply
+// `conj_rhs` to the scaled elements, so the scale is conjugated beforehand to cancel it out
+fn write_rhs_scale(f: &mut std::fmt::Formatter<'_>, cplx: bool) -> std::fmt::Result {
+    if cplx {
+        write!(
+            f,
+            "let rhs_scale = if conj_rhs {{ (*k_scale.offset(depth)).conj() }} else {{ *k_scale.offset(depth) }};\n"
+        )
+    } else {
+        write!(f, "let rhs_scale = *k_scale.offset(depth);\n")
+    }
+}
+
+// the element of `rhs` at row `depth` and column `j`, multiplied by the factor emitted by
+// `write_rhs_scale` if `scaled`
+fn rhs_elem(j: usize, scaled: bool) -> String {
+    if scaled {
+        format!("*rhs.offset(depth * rhs_rs + {j} * rhs_cs) * rhs_scale")
+    } else {
+        format!("*rhs.offset(depth * rhs_rs + {j} * rhs_cs)")
+    }
+}
+
 mod generic {
     use super::*;
 
@@ -23,6 +263,8 @@ mod generic {
         pub store_unaligned: [&'static str; 3],
         pub set1: &'static str,
         pub mul_add: &'static str,
+        // evaluates `nano_gemm_core::Activation` on a register
+        pub activate: &'static str,
     }
 
     pub struct CplxKernel {
@@ -43,6 +285,106 @@ mod generic {
         pub conj: &'static str,
     }
 
+    // multiplies the product by the row and column scales. the arguments are the same as for
+    // `write_bias`
+    fn write_scale(
+        f: &mut std::fmt::Formatter<'_>,
+        (n, mr, nr): (&usize, &usize, &usize),
+        load_unaligned: &[&str; 3],
+        set1: &str,
+        mul_add: &str,
+    ) -> Result<(), std::fmt::Error> {
+        write!(f, "if !row_scale.is_null() {{")?;
+        let mut i = 0;
+        while i < *mr {
+            let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+            write!(
+                f,
+                "let scale = {}(row_scale.offset({i}));\n",
+                load_unaligned[ii]
+            )?;
+            for j in 0..*nr {
+                write!(
+                    f,
+                    "acc_{i}_{j} = {mul_add}(scale, acc_{i}_{j}, core::mem::zeroed());\n"
+                )?;
+            }
+            i += 1 << ii;
+        }
+        write!(f, "}}\n")?;
+        write!(f, "if !col_scale.is_null() {{")?;
+        for j in 0..*nr {
+            write!(f, "let scale = {set1}(*col_scale.offset({j}));\n")?;
+            let mut i = 0;
+            while i < *mr {
+                let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                write!(
+                    f,
+                    "acc_{i}_{j} = {mul_add}(scale, acc_{i}_{j}, core::mem::zeroed());\n"
+                )?;
+                i += 1 << ii;
+            }
+        }
+        write!(f, "}}")
+    }
+
+    // adds the bias vectors to the product scaled by `beta`, then resets `beta` to one so that
+    // the stores only combine the result with `dst`. the other arguments are the kernel's fields,
+    // with the tile dimensions given as `(n, mr, nr)`
+    fn write_bias(
+        f: &mut std::fmt::Formatter<'_>,
+        (n, mr, nr): (&usize, &usize, &usize),
+        load_unaligned: &[&str; 3],
+        set1: &str,
+        mul_add: &str,
+        one: &str,
+    ) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "let beta = if !row_bias.is_null() || !col_bias.is_null() {{"
+        )?;
+        write!(f, "let beta = {set1}(beta);\n")?;
+        write!(f, "let one = {set1}({one});\n")?;
+        for j in 0..*nr {
+            let mut i = 0;
+            while i < *mr {
+                let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                write!(
+                    f,
+                    "acc_{i}_{j} = {mul_add}(beta, acc_{i}_{j}, core::mem::zeroed());\n"
+                )?;
+                i += 1 << ii;
+            }
+        }
+        write!(f, "if !row_bias.is_null() {{")?;
+        let mut i = 0;
+        while i < *mr {
+            let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+            write!(
+                f,
+                "let bias = {}(row_bias.offset({i}));\n",
+                load_unaligned[ii]
+            )?;
+            for j in 0..*nr {
+                write!(f, "acc_{i}_{j} = {mul_add}(one, bias, acc_{i}_{j});\n")?;
+            }
+            i += 1 << ii;
+        }
+        write!(f, "}}\n")?;
+        write!(f, "if !col_bias.is_null() {{")?;
+        for j in 0..*nr {
+            write!(f, "let bias = {set1}(*col_bias.offset({j}));\n")?;
+            let mut i = 0;
+            while i < *mr {
+                let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                write!(f, "acc_{i}_{j} = {mul_add}(one, bias, acc_{i}_{j});\n")?;
+                i += 1 << ii;
+            }
+        }
+        write!(f, "}}")?;
+        write!(f, "{one} }} else {{ beta }};\n")
+    }
+
     impl Display for RealKernel {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             let Self {
@@ -63,7 +405,7 @@ mod generic {
             write!(
                 f,
                 r#"pub unsafe fn matmul_{mr}_{nr}_{}(
-                &nano_gemm_core::MicroKernelData {{ alpha, beta, k, dst_cs, lhs_cs, rhs_rs, rhs_cs, .. }}: &nano_gemm_core::MicroKernelData< {ty} >,
+                &nano_gemm_core::MicroKernelData {{ alpha, beta, read_dst, k, dst_cs, src, src_cs, lhs_cs, rhs_rs, rhs_cs, row_scale, col_scale, k_scale, row_bias, col_bias, activation, .. }}: &nano_gemm_core::MicroKernelData< {ty} >,
                 dst: *mut {ty},
                 lhs: *const {ty},
                 rhs: *const {ty},
@@ -73,6 +415,7 @@ mod generic {
             )?;
 
             write!(f, "_ = k;\n")?;
+            write_src(f)?;
             let mut i = 0;
             while i < *mr {
                 let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
@@ -88,16 +431,15 @@ mod generic {
                 i += 1 << ii;
             }
 
-            if let Some(k) = self.k {
-                for depth in 0..k {
-                    write!(f, "let depth = {depth};\n")?;
-                    self.inner_kernel(f)?;
-                }
-            } else {
-                write!(f, "for depth in 0..k as isize {{")?;
-                self.inner_kernel(f)?;
-                write!(f, "}}")?;
-            }
+            write!(f, "if k_scale.is_null() {{")?;
+            self.write_depth_loop(f, false)?;
+            write!(f, "}} else {{")?;
+            self.write_depth_loop(f, true)?;
+            write!(f, "}}")?;
+
+            write_scale(f, (n, mr, nr), load_unaligned, self.set1, mul_add)?;
+            write_bias(f, (n, mr, nr), load_unaligned, self.set1, mul_add, "1.0")?;
+            self.write_activation(f)?;
 
             write!(f, "if alpha == 1.0 {{")?;
             write!(f, "let beta = {}(beta);\n", self.set1)?;
@@ -107,9 +449,10 @@ mod generic {
                     let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
                     write!(f, "{{")?;
                     write!(f, "let dst = dst.offset({i} + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} + {j} * src_cs);")?;
                     write!(
                         f,
-                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {}(dst)));\n",
+                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {}(src)));\n",
                         store_unaligned[ii], load_unaligned[ii],
                     )?;
                     write!(f, "}}")?;
@@ -118,7 +461,7 @@ mod generic {
             }
             write!(f, "}}")?;
 
-            write!(f, "else if alpha == 0.0 {{")?;
+            write!(f, "else if alpha == 0.0 && !read_dst {{")?;
             write!(f, "let beta = {}(beta);\n", self.set1)?;
             for j in 0..self.nr {
                 let mut i = 0;
@@ -146,9 +489,10 @@ mod generic {
                     let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
                     write!(f, "{{")?;
                     write!(f, "let dst = dst.offset({i} + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} + {j} * src_cs);")?;
                     write!(
                         f,
-                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {mul_add}(alpha, {}(dst), core::mem::zeroed())));\n",
+                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {mul_add}(alpha, {}(src), core::mem::zeroed())));\n",
                         store_unaligned[ii], load_unaligned[ii],
                     )?;
                     write!(f, "}}")?;
@@ -162,7 +506,90 @@ mod generic {
     }
 
     impl RealKernel {
-        fn inner_kernel(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        // combines the accumulators with `dst` and applies the activation, then resets `alpha`,
+        // `beta` and `read_dst` so that the stores only write the accumulators
+        fn write_activation(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                n,
+                mr,
+                nr,
+                load_unaligned,
+                set1,
+                mul_add,
+                activate,
+                ..
+            } = self;
+
+            write!(
+                f,
+                "let (alpha, beta, read_dst) = if !matches!(activation, nano_gemm_core::Activation::None) {{"
+            )?;
+            write!(f, "let beta = {set1}(beta);\n")?;
+            for j in 0..*nr {
+                let mut i = 0;
+                while i < *mr {
+                    let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                    write!(
+                        f,
+                        "acc_{i}_{j} = {mul_add}(beta, acc_{i}_{j}, core::mem::zeroed());\n"
+                    )?;
+                    i += 1 << ii;
+                }
+            }
+            write!(f, "if alpha != 0.0 || read_dst {{")?;
+            write!(f, "let alpha = {set1}(alpha);\n")?;
+            for j in 0..*nr {
+                let mut i = 0;
+                while i < *mr {
+                    let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                    write!(
+                        f,
+                        "acc_{i}_{j} = {mul_add}(alpha, {}(src.offset({i} + {j} * src_cs)), acc_{i}_{j});\n",
+                        load_unaligned[ii],
+                    )?;
+                    i += 1 << ii;
+                }
+            }
+            write!(f, "}}")?;
+            for j in 0..*nr {
+                let mut i = 0;
+                while i < *mr {
+                    let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
+                    write!(f, "acc_{i}_{j} = {activate}(activation, acc_{i}_{j});\n")?;
+                    i += 1 << ii;
+                }
+            }
+            write!(
+                f,
+                "(0.0, 1.0, false) }} else {{ (alpha, beta, read_dst) }};\n"
+            )
+        }
+
+        // accumulates the products over the depth, with the rows of `rhs` scaled by `k_scale` if
+        // `scaled`
+        fn write_depth_loop(
+            &self,
+            f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
+        ) -> Result<(), std::fmt::Error> {
+            if let Some(k) = self.k {
+                for depth in 0..k {
+                    write!(f, "let depth = {depth};\n")?;
+                    self.inner_kernel(f, scaled)?;
+                }
+            } else {
+                write!(f, "for depth in 0..k as isize {{")?;
+                self.inner_kernel(f, scaled)?;
+                write!(f, "}}")?;
+            }
+            Ok(())
+        }
+
+        fn inner_kernel(
+            &self,
+            f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
+        ) -> Result<(), std::fmt::Error> {
             let Self {
                 mr,
                 set1,
@@ -172,6 +599,10 @@ mod generic {
                 ..
             } = self;
 
+            if scaled {
+                write_rhs_scale(f, false)?;
+            }
+
             let mut i = 0;
             while i < *mr {
                 let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
@@ -183,10 +614,7 @@ mod generic {
                 i += 1 << ii;
             }
             for j in 0..self.nr {
-                write!(
-                    f,
-                    "let tmp_rhs = {set1}(*rhs.offset(depth * rhs_rs + {j} * rhs_cs));\n",
-                )?;
+                write!(f, "let tmp_rhs = {set1}({});\n", rhs_elem(j, scaled))?;
 
                 let mut i = 0;
                 while i < *mr {
@@ -224,7 +652,7 @@ mod generic {
             write!(
                 f,
                 r#"pub unsafe fn matmul_{mr}_{nr}_{}(
-                &nano_gemm_core::MicroKernelData {{ alpha, beta, k, dst_cs, lhs_cs, rhs_rs, rhs_cs, conj_lhs, conj_rhs, .. }}: &nano_gemm_core::MicroKernelData< {ty} >,
+                &nano_gemm_core::MicroKernelData {{ alpha, beta, read_dst, k, dst_cs, src, src_cs, lhs_cs, rhs_rs, rhs_cs, conj_lhs, conj_rhs, row_scale, col_scale, k_scale, row_bias, col_bias, .. }}: &nano_gemm_core::MicroKernelData< {ty} >,
                 dst: *mut {ty},
                 lhs: *const {ty},
                 rhs: *const {ty},
@@ -234,6 +662,7 @@ mod generic {
             )?;
 
             write!(f, "_ = k;\n")?;
+            write_src(f)?;
             let mut i = 0;
             while i < *mr {
                 let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
@@ -249,28 +678,10 @@ mod generic {
                 i += 1 << ii;
             }
 
-            write!(f, "if conj_lhs == conj_rhs {{")?;
-            if let Some(k) = self.k {
-                for depth in 0..k {
-                    write!(f, "let depth = {depth};\n")?;
-                    self.inner_kernel_no_conj(f)?;
-                }
-            } else {
-                write!(f, "for depth in 0..k as isize {{")?;
-                self.inner_kernel_no_conj(f)?;
-                write!(f, "}}")?;
-            }
+            write!(f, "if k_scale.is_null() {{")?;
+            self.write_depth_loops(f, false)?;
             write!(f, "}} else {{")?;
-            if let Some(k) = self.k {
-                for depth in 0..k {
-                    write!(f, "let depth = {depth};\n")?;
-                    self.inner_kernel_conj(f)?;
-                }
-            } else {
-                write!(f, "for depth in 0..k as isize {{")?;
-                self.inner_kernel_conj(f)?;
-                write!(f, "}}")?;
-            }
+            self.write_depth_loops(f, true)?;
             write!(f, "}}")?;
 
             write!(f, "if conj_rhs {{")?;
@@ -284,6 +695,16 @@ mod generic {
             }
             write!(f, "}}")?;
 
+            write_scale(f, (n, mr, nr), load_unaligned, self.set1, mul_add)?;
+            write_bias(
+                f,
+                (n, mr, nr),
+                load_unaligned,
+                self.set1,
+                mul_add,
+                &format!("{ty} {{ re: 1.0, im: 0.0 }}"),
+            )?;
+
             write!(f, "if alpha == ({ty} {{ re: 1.0, im: 0.0 }}) {{")?;
             write!(f, "let beta = {}(beta);\n", self.set1)?;
             for j in 0..self.nr {
@@ -292,9 +713,10 @@ mod generic {
                     let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
                     write!(f, "{{")?;
                     write!(f, "let dst = dst.offset({i} + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} + {j} * src_cs);")?;
                     write!(
                         f,
-                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {}(dst)));\n",
+                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {}(src)));\n",
                         store_unaligned[ii], load_unaligned[ii],
                     )?;
                     write!(f, "}}")?;
@@ -303,7 +725,10 @@ mod generic {
             }
             write!(f, "}}")?;
 
-            write!(f, "else if alpha == ({ty} {{ re: 0.0, im: 0.0 }}) {{")?;
+            write!(
+                f,
+                "else if alpha == ({ty} {{ re: 0.0, im: 0.0 }}) && !read_dst {{"
+            )?;
             write!(f, "let beta = {}(beta);\n", self.set1)?;
             for j in 0..self.nr {
                 let mut i = 0;
@@ -331,9 +756,10 @@ mod generic {
                     let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
                     write!(f, "{{")?;
                     write!(f, "let dst = dst.offset({i} + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} + {j} * src_cs);")?;
                     write!(
                         f,
-                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {mul_add}(alpha, {}(dst), core::mem::zeroed())));\n",
+                        "{}(dst, {mul_add}(beta, acc_{i}_{j}, {mul_add}(alpha, {}(src), core::mem::zeroed())));\n",
                         store_unaligned[ii], load_unaligned[ii],
                     )?;
                     write!(f, "}}")?;
@@ -347,9 +773,41 @@ mod generic {
     }
 
     impl CplxKernel {
+        // same as `RealKernel::write_depth_loop`, for both cases of the conjugation
+        fn write_depth_loops(
+            &self,
+            f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
+        ) -> Result<(), std::fmt::Error> {
+            write!(f, "if conj_lhs == conj_rhs {{")?;
+            if let Some(k) = self.k {
+                for depth in 0..k {
+                    write!(f, "let depth = {depth};\n")?;
+                    self.inner_kernel_no_conj(f, scaled)?;
+                }
+            } else {
+                write!(f, "for depth in 0..k as isize {{")?;
+                self.inner_kernel_no_conj(f, scaled)?;
+                write!(f, "}}")?;
+            }
+            write!(f, "}} else {{")?;
+            if let Some(k) = self.k {
+                for depth in 0..k {
+                    write!(f, "let depth = {depth};\n")?;
+                    self.inner_kernel_conj(f, scaled)?;
+                }
+            } else {
+                write!(f, "for depth in 0..k as isize {{")?;
+                self.inner_kernel_conj(f, scaled)?;
+                write!(f, "}}")?;
+            }
+            write!(f, "}}")
+        }
+
         fn inner_kernel_no_conj(
             &self,
             f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
         ) -> Result<(), std::fmt::Error> {
             let Self {
                 mr,
@@ -360,6 +818,10 @@ mod generic {
                 ..
             } = self;
 
+            if scaled {
+                write_rhs_scale(f, true)?;
+            }
+
             let mut i = 0;
             while i < *mr {
                 let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
@@ -371,10 +833,7 @@ mod generic {
                 i += 1 << ii;
             }
             for j in 0..self.nr {
-                write!(
-                    f,
-                    "let tmp_rhs = {set1}(*rhs.offset(depth * rhs_rs + {j} * rhs_cs));\n",
-                )?;
+                write!(f, "let tmp_rhs = {set1}({});\n", rhs_elem(j, scaled))?;
 
                 let mut i = 0;
                 while i < *mr {
@@ -393,6 +852,7 @@ mod generic {
         fn inner_kernel_conj(
             &self,
             f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
         ) -> Result<(), std::fmt::Error> {
             let Self {
                 mr,
@@ -403,6 +863,10 @@ mod generic {
                 ..
             } = self;
 
+            if scaled {
+                write_rhs_scale(f, true)?;
+            }
+
             let mut i = 0;
             while i < *mr {
                 let ii = Ord::min((mr - i).ilog2() as usize, n.ilog2() as usize);
@@ -414,10 +878,7 @@ mod generic {
                 i += 1 << ii;
             }
             for j in 0..self.nr {
-                write!(
-                    f,
-                    "let tmp_rhs = {set1}(*rhs.offset(depth * rhs_rs + {j} * rhs_cs));\n",
-                )?;
+                write!(f, "let tmp_rhs = {set1}({});\n", rhs_elem(j, scaled))?;
 
                 let mut i = 0;
                 while i < *mr {
@@ -486,6 +947,7 @@ pub mod aarch64 {
             }}
             "###
         )?;
+        write!(code, "{}", ActivationOps::neon("f32"))?;
         for mr in 1..=8 {
             for nr in 1..=4 {
                 for k in (1..=16).into_iter().map(Some).chain([None]) {
@@ -501,6 +963,7 @@ pub mod aarch64 {
                         store_unaligned: ["store_1", "store_2", "store_4"],
                         set1: "set1",
                         mul_add: "mul_add",
+                        activate: "activate_neon",
                     };
                     write!(code, "{kernel}")?;
                 }
@@ -572,6 +1035,7 @@ pub mod aarch64 {
             }}
             "###
         )?;
+        write!(code, "{}", ActivationOps::neon("f64"))?;
         for mr in 1..=4 {
             for nr in 1..=4 {
                 for k in (1..=16).into_iter().map(Some).chain([None]) {
@@ -587,6 +1051,7 @@ pub mod aarch64 {
                         store_unaligned: ["store_1", "store_2", "store_4"],
                         set1: "set1",
                         mul_add: "mul_add",
+                        activate: "activate_neon",
                     };
                     write!(code, "{kernel}")?;
                 }
@@ -897,6 +1362,9 @@ pub mod x86 {
         mask_store_unaligned: &'static str,
         mul_add: &'static str,
         mul: &'static str,
+        add: &'static str,
+        // evaluates `nano_gemm_core::Activation` on a register
+        activate: &'static str,
         need_mask: bool,
     }
 
@@ -922,9 +1390,26 @@ pub mod x86 {
         mul_addsub: &'static str,
         mul_subadd: &'static str,
         xor: &'static str,
+        add: &'static str,
         need_mask: bool,
     }
 
+    struct GemvKernel {
+        ty: &'static str,
+        reg_ty: &'static str,
+        // register size
+        n: usize,
+
+        target_features: &'static str,
+        setzero: &'static str,
+        set1: &'static str,
+        load_unaligned: &'static str,
+        store_unaligned: &'static str,
+        add: &'static str,
+        mul: &'static str,
+        mul_add: &'static str,
+    }
+
     impl Display for RealKernel {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             // function that multiplies (mr_div_n * n, k) by (k, n_r)
@@ -941,7 +1426,7 @@ pub mod x86 {
             write!(
                 f,
                 r#"pub unsafe fn matmul_{0:}_{1:}_{2:}(
-                &nano_gemm_core::MicroKernelData {{ alpha, beta, k, dst_cs, lhs_cs, rhs_rs, rhs_cs, last_mask, .. }}: &nano_gemm_core::MicroKernelData< {3:} >,
+                &nano_gemm_core::MicroKernelData {{ alpha, beta, read_dst, k, dst_cs, src, src_cs, lhs_cs, rhs_rs, rhs_cs, last_mask, row_scale, col_scale, k_scale, row_bias, col_bias, activation, .. }}: &nano_gemm_core::MicroKernelData< {3:} >,
                 dst: *mut {3:},
                 lhs: *const {3:},
                 rhs: *const {3:},
@@ -963,6 +1448,7 @@ pub mod x86 {
             )?;
 
             write!(f, "_ = k;\n")?;
+            write_src(f)?;
             write!(f, "type Reg = {};\n", self.reg_ty)?;
             write!(f, "const N: isize = {};\n", self.n)?;
             write!(
@@ -986,70 +1472,28 @@ pub mod x86 {
                 write!(f, "_ = last_mask;\n")?;
             }
 
-            if let Some(k) = self.k {
-                for depth in 0..k {
-                    write!(f, "let depth = {depth};\n")?;
-                    for i in 0..self.mr_div_n {
-                        self.write_load_lhs(i, f)?;
-                    }
-                    for j in 0..self.nr {
+            write!(f, "if k_scale.is_null() {{")?;
+            self.write_depth_loop(f, false)?;
+            write!(f, "}} else {{")?;
+            self.write_depth_loop(f, true)?;
+            write!(f, "}}")?;
+
+            self.write_scale(f)?;
+            self.write_bias(f)?;
+            self.write_activation(f)?;
+
+            write!(f, "if alpha == 1.0 {{")?;
+            write!(f, "let beta = {}(beta);\n", self.set1)?;
+            for j in 0..self.nr {
+                for i in 0..self.mr_div_n {
+                    write!(f, "{{")?;
+                    write!(f, "let dst = dst.offset({i} * N + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} * N + {j} * src_cs);")?;
+                    if i + 1 < self.mr_div_n || !self.need_mask {
                         write!(
                             f,
-                            "let tmp_rhs = {}(*rhs.offset(depth * rhs_rs + {j} * rhs_cs));\n",
-                            self.set1
-                        )?;
-
-                        for i in 0..self.mr_div_n {
-                            if depth > 0 {
-                                write!(
-                                    f,
-                                    "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
-                                    self.mul_add
-                                )?;
-                            } else {
-                                write!(
-                                    f,
-                                    "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs);\n",
-                                    self.mul
-                                )?;
-                            }
-                        }
-                    }
-                }
-            } else {
-                write!(f, "for depth in 0..k as isize {{")?;
-                for i in 0..self.mr_div_n {
-                    self.write_load_lhs(i, f)?;
-                }
-                for j in 0..self.nr {
-                    write!(
-                        f,
-                        "let tmp_rhs = {}(*rhs.offset(depth * rhs_rs + {j} * rhs_cs));\n",
-                        self.set1
-                    )?;
-
-                    for i in 0..self.mr_div_n {
-                        write!(
-                            f,
-                            "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
-                            self.mul_add
-                        )?;
-                    }
-                }
-                write!(f, "}}")?;
-            }
-
-            write!(f, "if alpha == 1.0 {{")?;
-            write!(f, "let beta = {}(beta);\n", self.set1)?;
-            for j in 0..self.nr {
-                for i in 0..self.mr_div_n {
-                    write!(f, "{{")?;
-                    write!(f, "let dst = dst.offset({i} * N + {j} * dst_cs);")?;
-                    if i + 1 < self.mr_div_n || !self.need_mask {
-                        write!(
-                            f,
-                            "{}(dst, {}(beta, acc[{i}][{j}], {}(dst)));\n",
-                            self.store_unaligned, self.mul_add, self.load_unaligned
+                            "{}(dst, {}(beta, acc[{i}][{j}], {}(src)));\n",
+                            self.store_unaligned, self.mul_add, self.load_unaligned
                         )?;
                     } else {
                         write!(
@@ -1057,14 +1501,14 @@ pub mod x86 {
                             "{}(dst, last_mask, {}(beta, acc[{i}][{j}], {}));\n",
                             self.mask_store_unaligned,
                             self.mul_add,
-                            (self.mask_load_unaligned)(format!("dst"), "last_mask".to_string()),
+                            (self.mask_load_unaligned)("src".to_string(), "last_mask".to_string()),
                         )?;
                     }
                     write!(f, "}}")?;
                 }
             }
             write!(f, "}}")?;
-            write!(f, "else if alpha == 0.0 {{")?;
+            write!(f, "else if alpha == 0.0 && !read_dst {{")?;
             write!(f, "let beta = {}(beta);\n", self.set1)?;
             for j in 0..self.nr {
                 for i in 0..self.mr_div_n {
@@ -1094,10 +1538,11 @@ pub mod x86 {
                 for i in 0..self.mr_div_n {
                     write!(f, "{{")?;
                     write!(f, "let dst = dst.offset({i} * N + {j} * dst_cs);")?;
+                    write!(f, "let src = src.offset({i} * N + {j} * src_cs);")?;
                     if i + 1 < self.mr_div_n || !self.need_mask {
                         write!(
                             f,
-                            "{}(dst, {}(beta, acc[{i}][{j}], {}({}(dst), alpha)));\n",
+                            "{}(dst, {}(beta, acc[{i}][{j}], {}({}(src), alpha)));\n",
                             self.store_unaligned, self.mul_add, self.mul, self.load_unaligned
                         )?;
                     } else {
@@ -1107,7 +1552,7 @@ pub mod x86 {
                             self.mask_store_unaligned,
                             self.mul_add,
                             self.mul,
-                            (self.mask_load_unaligned)(format!("dst"), "last_mask".to_string()),
+                            (self.mask_load_unaligned)("src".to_string(), "last_mask".to_string()),
                         )?;
                     }
                     write!(f, "}}")?;
@@ -1119,6 +1564,206 @@ pub mod x86 {
         }
     }
 
+    impl GemvKernel {
+        // dst = alpha * dst + beta * acc, where `acc` holds a register
+        fn write_store_reg(
+            &self,
+            dst: &str,
+            acc: &str,
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> std::fmt::Result {
+            let Self {
+                set1,
+                load_unaligned,
+                store_unaligned,
+                mul,
+                mul_add,
+                ..
+            } = self;
+            write!(
+                f,
+                "if alpha == 1.0 {{ {store_unaligned}({dst}, {mul_add}({set1}(beta), {acc}, {load_unaligned}({dst}))); }}
+                else if alpha == 0.0 && !read_dst {{ {store_unaligned}({dst}, {mul}({set1}(beta), {acc})); }}
+                else {{ {store_unaligned}({dst}, {mul_add}({set1}(beta), {acc}, {mul}({load_unaligned}({dst}), {set1}(alpha)))); }}\n"
+            )
+        }
+
+        // dst = alpha * dst + beta * acc, where `acc` holds a scalar
+        fn write_store_scalar(
+            &self,
+            dst: &str,
+            acc: &str,
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> std::fmt::Result {
+            write!(
+                f,
+                "if alpha == 1.0 {{ *{dst} = *{dst} + beta * {acc}; }}
+                else if alpha == 0.0 && !read_dst {{ *{dst} = beta * {acc}; }}
+                else {{ *{dst} = alpha * *{dst} + beta * {acc}; }}\n"
+            )
+        }
+    }
+
+    impl Display for GemvKernel {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let Self {
+                ty,
+                reg_ty,
+                n,
+                target_features,
+                setzero,
+                set1,
+                load_unaligned,
+                add,
+                mul_add,
+                ..
+            } = self;
+
+            let header = format!(
+                r#"
+                #[cfg(target_arch = "x86_64")]
+                use core::arch::x86_64::*;
+                #[cfg(target_arch = "x86")]
+                use core::arch::x86::*;
+
+                type Reg = {reg_ty};
+                const N: usize = {n};
+                "#
+            );
+
+            // dot product of two contiguous vectors of length k
+            write!(
+                f,
+                r#"
+                #[target_feature(enable = "{target_features}")]
+                pub unsafe fn dot(k: usize, lhs: *const {ty}, rhs: *const {ty}) -> {ty} {{
+                {header}
+                let mut acc0: Reg = {setzero}();
+                let mut acc1: Reg = {setzero}();
+                let mut acc2: Reg = {setzero}();
+                let mut acc3: Reg = {setzero}();
+
+                let mut depth = 0usize;
+                while depth + 4 * N <= k {{
+                    acc0 = {mul_add}({load_unaligned}(lhs.add(depth)), {load_unaligned}(rhs.add(depth)), acc0);
+                    acc1 = {mul_add}({load_unaligned}(lhs.add(depth + N)), {load_unaligned}(rhs.add(depth + N)), acc1);
+                    acc2 = {mul_add}({load_unaligned}(lhs.add(depth + 2 * N)), {load_unaligned}(rhs.add(depth + 2 * N)), acc2);
+                    acc3 = {mul_add}({load_unaligned}(lhs.add(depth + 3 * N)), {load_unaligned}(rhs.add(depth + 3 * N)), acc3);
+                    depth += 4 * N;
+                }}
+                while depth + N <= k {{
+                    acc0 = {mul_add}({load_unaligned}(lhs.add(depth)), {load_unaligned}(rhs.add(depth)), acc0);
+                    depth += N;
+                }}
+
+                let acc: [{ty}; N] = core::mem::transmute({add}({add}(acc0, acc1), {add}(acc2, acc3)));
+                let mut sum = 0.0;
+                for x in acc {{
+                    sum += x;
+                }}
+                while depth < k {{
+                    sum += *lhs.add(depth) * *rhs.add(depth);
+                    depth += 1;
+                }}
+                sum
+                }}
+                "#
+            )?;
+
+            // lhs has a unit row stride, and dst is contiguous
+            write!(
+                f,
+                r#"
+                #[target_feature(enable = "{target_features}")]
+                pub unsafe fn gemv_colmajor(
+                    &nano_gemm_core::GemvData {{ alpha, beta, read_dst, m, k, lhs_cs, rhs_rs, .. }}: &nano_gemm_core::GemvData< {ty} >,
+                    dst: *mut {ty},
+                    lhs: *const {ty},
+                    rhs: *const {ty},
+                ) {{
+                {header}
+                let mut i = 0usize;
+                while i + 4 * N <= m {{
+                    let mut acc: [Reg; 4] = [{setzero}(); 4];
+                    for depth in 0..k as isize {{
+                        let x = {set1}(*rhs.offset(depth * rhs_rs));
+                        let lhs = lhs.add(i).offset(depth * lhs_cs);
+                        acc[0] = {mul_add}({load_unaligned}(lhs), x, acc[0]);
+                        acc[1] = {mul_add}({load_unaligned}(lhs.add(N)), x, acc[1]);
+                        acc[2] = {mul_add}({load_unaligned}(lhs.add(2 * N)), x, acc[2]);
+                        acc[3] = {mul_add}({load_unaligned}(lhs.add(3 * N)), x, acc[3]);
+                    }}
+                    for r in 0..4 {{
+                        let dst = dst.add(i + r * N);
+                "#
+            )?;
+            self.write_store_reg("dst", "acc[r]", f)?;
+            write!(
+                f,
+                r#"
+                    }}
+                    i += 4 * N;
+                }}
+                while i + N <= m {{
+                    let mut acc: Reg = {setzero}();
+                    for depth in 0..k as isize {{
+                        let x = {set1}(*rhs.offset(depth * rhs_rs));
+                        acc = {mul_add}({load_unaligned}(lhs.add(i).offset(depth * lhs_cs)), x, acc);
+                    }}
+                    let dst = dst.add(i);
+                "#
+            )?;
+            self.write_store_reg("dst", "acc", f)?;
+            write!(
+                f,
+                r#"
+                    i += N;
+                }}
+                while i < m {{
+                    let mut acc = 0.0;
+                    for depth in 0..k as isize {{
+                        acc += *lhs.add(i).offset(depth * lhs_cs) * *rhs.offset(depth * rhs_rs);
+                    }}
+                    let dst = dst.add(i);
+                "#
+            )?;
+            self.write_store_scalar("dst", "acc", f)?;
+            write!(
+                f,
+                r#"
+                    i += 1;
+                }}
+                }}
+                "#
+            )?;
+
+            // lhs has a unit column stride, and rhs is contiguous
+            write!(
+                f,
+                r#"
+                #[target_feature(enable = "{target_features}")]
+                pub unsafe fn gemv_rowmajor(
+                    &nano_gemm_core::GemvData {{ alpha, beta, read_dst, m, k, dst_rs, lhs_rs, .. }}: &nano_gemm_core::GemvData< {ty} >,
+                    dst: *mut {ty},
+                    lhs: *const {ty},
+                    rhs: *const {ty},
+                ) {{
+                for i in 0..m as isize {{
+                    let acc = dot(k, lhs.offset(i * lhs_rs), rhs);
+                    let dst = dst.offset(i * dst_rs);
+                "#
+            )?;
+            self.write_store_scalar("dst", "acc", f)?;
+            write!(
+                f,
+                r#"
+                }}
+                }}
+                "#
+            )
+        }
+    }
+
     impl Display for CplxKernel {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             // function that multiplies (mr_div_n * n, k) by (k, n_r)
@@ -1145,7 +1790,7 @@ pub mod x86 {
             write!(
                 f,
                 r#"pub unsafe fn matmul_{0:}_{1:}_{2:}(
-                &nano_gemm_core::MicroKernelData {{ alpha, beta, k, dst_cs, lhs_cs, rhs_rs, rhs_cs, last_mask, conj_lhs, conj_rhs }}: &nano_gemm_core::MicroKernelData<num_complex::Complex< {3:} >>,
+                &nano_gemm_core::MicroKernelData {{ alpha, beta, read_dst, k, dst_cs, src, src_cs, lhs_cs, rhs_rs, rhs_cs, last_mask, conj_lhs, conj_rhs, row_scale, col_scale, k_scale, row_bias, col_bias, activation: _ }}: &nano_gemm_core::MicroKernelData<num_complex::Complex< {3:} >>,
                 dst: *mut num_complex::Complex< {3:} >,
                 lhs: *const num_complex::Complex< {3:} >,
                 rhs: *const num_complex::Complex< {3:} >,
@@ -1167,6 +1812,7 @@ pub mod x86 {
             )?;
 
             write!(f, "_ = k;\n")?;
+            write_src(f)?;
             write!(f, "type Reg = {};\n", self.reg_ty)?;
             write!(f, "const N: isize = {};\n", self.n)?;
             write!(
@@ -1190,6 +1836,238 @@ pub mod x86 {
                 write!(f, "_ = last_mask;\n")?;
             }
 
+            write!(f, "if k_scale.is_null() {{")?;
+            self.write_depth_loops(f, false)?;
+            write!(f, "}} else {{")?;
+            self.write_depth_loops(f, true)?;
+            write!(f, "}}")?;
+
+            write!(
+                f,
+                "let mask = XOR_MASKS[conj_lhs as usize + 2 * conj_rhs as usize];"
+            )?;
+            for j in 0..self.nr {
+                for i in 0..self.mr_div_n {
+                    write!(f, "acc[{i}][{j}] = core::mem::transmute({xor}(core::mem::transmute(acc[{i}][{j}]), core::mem::transmute(mask)));")?;
+                }
+            }
+
+            self.write_scale(f)?;
+            self.write_bias(f)?;
+
+            write!(
+                f,
+                "if alpha == (num_complex::Complex {{ re: 1.0, im: 0.0 }}) {{"
+            )?;
+            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
+            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
+            for j in 0..self.nr {
+                for i in 0..self.mr_div_n {
+                    write!(f, "{{")?;
+                    write!(
+                        f,
+                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
+                        self.ty,
+                    )?;
+                    write!(
+                        f,
+                        "let src = src.offset({i} * N + {j} * src_cs) as *const {};",
+                        self.ty,
+                    )?;
+                    if i + 1 < self.mr_div_n || !self.need_mask {
+                        write!(
+                            f,
+                            "{store_unaligned}(
+                            dst,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    {load_unaligned}(src),
+                                ),
+                            ),
+                        );\n",
+                        )?;
+                    } else {
+                        write!(
+                            f,
+                            "{mask_store_unaligned}(
+                            dst,
+                            last_mask,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    {},
+                                ),
+                            ),
+                        );\n",
+                            (self.mask_load_unaligned)("src".to_string(), "last_mask".to_string()),
+                        )?;
+                    }
+                    write!(f, "}}")?;
+                }
+            }
+            write!(f, "}}")?;
+
+            write!(
+                f,
+                "else if alpha == (num_complex::Complex {{ re: 0.0, im: 0.0 }}) && !read_dst {{"
+            )?;
+            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
+            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
+            for j in 0..self.nr {
+                for i in 0..self.mr_div_n {
+                    write!(f, "{{")?;
+                    write!(
+                        f,
+                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
+                        self.ty,
+                    )?;
+                    if i + 1 < self.mr_div_n || !self.need_mask {
+                        write!(
+                            f,
+                            "{store_unaligned}(
+                            dst,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    core::mem::zeroed(),
+                                ),
+                            ),
+                        );\n",
+                        )?;
+                    } else {
+                        write!(
+                            f,
+                            "{mask_store_unaligned}(
+                            dst,
+                            last_mask,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    core::mem::zeroed(),
+                                ),
+                            ),
+                        );\n"
+                        )?;
+                    }
+                    write!(f, "}}")?;
+                }
+            }
+            write!(f, "}}")?;
+            write!(f, "else {{")?;
+            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
+            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
+            write!(f, "let alpha_re = {}(alpha.re);\n", self.set1)?;
+            write!(f, "let alpha_im = {}(alpha.im);\n", self.set1)?;
+            for j in 0..self.nr {
+                for i in 0..self.mr_div_n {
+                    write!(f, "{{")?;
+                    write!(
+                        f,
+                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
+                        self.ty,
+                    )?;
+                    write!(
+                        f,
+                        "let src = src.offset({i} * N + {j} * src_cs) as *const {};",
+                        self.ty,
+                    )?;
+                    if i + 1 < self.mr_div_n || !self.need_mask {
+                        write!(
+                            f,
+                            "let dst_conj = core::mem::transmute({xor}(
+                            core::mem::transmute({load_unaligned}(src)),
+                            core::mem::transmute(XOR_MASKS[1]),
+                        ));"
+                        )?;
+
+                        write!(
+                            f,
+                            "{store_unaligned}(
+                            dst,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    {mul_addsub}(
+                                        {swap_re_im}(dst_conj),
+                                        alpha_im,
+                                        {mul_addsub}(
+                                            dst_conj,
+                                            alpha_re,
+                                            core::mem::zeroed(),
+                                        ),
+                                    ),
+                                ),
+                            ),
+                        );\n",
+                        )?;
+                    } else {
+                        write!(
+                            f,
+                            "let dst_conj = core::mem::transmute({xor}(
+                            core::mem::transmute({}),
+                            core::mem::transmute(XOR_MASKS[1]),
+                        ));",
+                            (self.mask_load_unaligned)("src".to_string(), "last_mask".to_string())
+                        )?;
+
+                        write!(
+                            f,
+                            "{mask_store_unaligned}(
+                            dst,
+                            last_mask,
+                            {mul_addsub}(
+                                {swap_re_im}(acc[{i}][{j}]),
+                                beta_im,
+                                {mul_addsub}(
+                                    acc[{i}][{j}],
+                                    beta_re,
+                                    {mul_addsub}(
+                                        {swap_re_im}(dst_conj),
+                                        alpha_im,
+                                        {mul_addsub}(
+                                            dst_conj,
+                                            alpha_re,
+                                            core::mem::zeroed(),
+                                        ),
+                                    ),
+                                ),
+                            ),
+                        );\n"
+                        )?;
+                    }
+                    write!(f, "}}")?;
+                }
+            }
+            write!(f, "}}")?;
+
+            write!(f, "}}\n")
+        }
+    }
+
+    impl CplxKernel {
+        // accumulates the products over the depth, for both cases of the conjugation, with the
+        // rows of `rhs` scaled by `k_scale` if `scaled`
+        fn write_depth_loops(
+            &self,
+            f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
+        ) -> Result<(), std::fmt::Error> {
             for idx in 0..2 {
                 if idx == 0 {
                     write!(f, "if conj_lhs == conj_rhs {{\n")?;
@@ -1206,16 +2084,20 @@ pub mod x86 {
                 if let Some(k) = self.k {
                     for depth in 0..k {
                         write!(f, "let depth = {depth};\n")?;
+                        if scaled {
+                            write_rhs_scale(f, true)?;
+                        }
                         for i in 0..self.mr_div_n {
                             self.write_load_lhs(i, f)?;
                         }
 
                         for j in 0..self.nr {
                             write!(
-                            f,
-                            "let tmp_rhs = {}((*rhs.offset(depth * rhs_rs + {j} * rhs_cs)).re);\n",
-                            self.set1
-                        )?;
+                                f,
+                                "let tmp_rhs = {}(({}).re);\n",
+                                self.set1,
+                                rhs_elem(j, scaled)
+                            )?;
 
                             for i in 0..self.mr_div_n {
                                 write!(
@@ -1229,10 +2111,11 @@ pub mod x86 {
                         }
                         for j in 0..self.nr {
                             write!(
-                            f,
-                            "let tmp_rhs = {}((*rhs.offset(depth * rhs_rs + {j} * rhs_cs)).im);\n",
-                            self.set1
-                        )?;
+                                f,
+                                "let tmp_rhs = {}(({}).im);\n",
+                                self.set1,
+                                rhs_elem(j, scaled)
+                            )?;
 
                             for i in 0..self.mr_div_n {
                                 write!(
@@ -1244,6 +2127,9 @@ pub mod x86 {
                     }
                 } else {
                     write!(f, "for depth in 0..k as isize {{")?;
+                    if scaled {
+                        write_rhs_scale(f, true)?;
+                    }
                     for i in 0..self.mr_div_n {
                         self.write_load_lhs(i, f)?;
                     }
@@ -1251,8 +2137,9 @@ pub mod x86 {
                     for j in 0..self.nr {
                         write!(
                             f,
-                            "let tmp_rhs = {}((*rhs.offset(depth * rhs_rs + {j} * rhs_cs)).re);\n",
-                            self.set1
+                            "let tmp_rhs = {}(({}).re);\n",
+                            self.set1,
+                            rhs_elem(j, scaled)
                         )?;
 
                         for i in 0..self.mr_div_n {
@@ -1268,253 +2155,421 @@ pub mod x86 {
                     for j in 0..self.nr {
                         write!(
                             f,
-                            "let tmp_rhs = {}((*rhs.offset(depth * rhs_rs + {j} * rhs_cs)).im);\n",
-                            self.set1
+                            "let tmp_rhs = {}(({}).im);\n",
+                            self.set1,
+                            rhs_elem(j, scaled)
+                        )?;
+
+                        for i in 0..self.mr_div_n {
+                            write!(
+                            f,
+                            "acc[{i}][{j}] = {mul_add}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
                         )?;
+                        }
+                    }
+
+                    write!(f, "}}")?;
+                }
+                write!(f, "}}")?;
+            }
+            Ok(())
+        }
+
+        // multiplies the product by the row and column scales. the accumulators hold the conjugate
+        // of the product, like in `write_bias`
+        fn write_scale(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                ty,
+                set1,
+                swap_re_im,
+                load_unaligned,
+                store_unaligned,
+                mul_addsub,
+                xor,
+                mr_div_n,
+                nr,
+                ..
+            } = self;
+
+            // `scale_re` and `scale_im` hold the real and imaginary parts of the scale in both
+            // halves of each element
+            let write_mul = |f: &mut std::fmt::Formatter<'_>, i: usize, j: usize| {
+                write!(
+                    f,
+                    "acc[{i}][{j}] = core::mem::transmute({xor}(
+                        core::mem::transmute({mul_addsub}(
+                            {swap_re_im}(acc[{i}][{j}]),
+                            scale_im,
+                            {mul_addsub}(acc[{i}][{j}], scale_re, core::mem::zeroed()),
+                        )),
+                        core::mem::transmute(XOR_MASKS[1]),
+                    ));\n"
+                )
+            };
+
+            write!(f, "if !row_scale.is_null() {{")?;
+            for i in 0..*mr_div_n {
+                let scale = if i + 1 < *mr_div_n || !self.need_mask {
+                    format!("{load_unaligned}(row_scale.offset({i} * N) as *const {ty})")
+                } else {
+                    (self.mask_load_unaligned)(
+                        format!("row_scale.offset({i} * N) as *const {ty}"),
+                        "last_mask".to_string(),
+                    )
+                };
+                write!(
+                    f,
+                    "let mut scale = [num_complex::Complex::<{ty}> {{ re: 0.0, im: 0.0 }}; N as usize];\n"
+                )?;
+                write!(
+                    f,
+                    "{store_unaligned}(scale.as_mut_ptr() as *mut {ty}, {scale});\n"
+                )?;
+                write!(
+                    f,
+                    "let scale_re = {load_unaligned}(scale.map(|s| num_complex::Complex {{ re: s.re, im: s.re }}).as_ptr() as *const {ty});\n"
+                )?;
+                write!(
+                    f,
+                    "let scale_im = {load_unaligned}(scale.map(|s| num_complex::Complex {{ re: s.im, im: s.im }}).as_ptr() as *const {ty});\n"
+                )?;
+                for j in 0..*nr {
+                    write_mul(f, i, j)?;
+                }
+            }
+            write!(f, "}}\n")?;
+            write!(f, "if !col_scale.is_null() {{")?;
+            for j in 0..*nr {
+                write!(f, "let scale_re = {set1}((*col_scale.offset({j})).re);\n")?;
+                write!(f, "let scale_im = {set1}((*col_scale.offset({j})).im);\n")?;
+                for i in 0..*mr_div_n {
+                    write_mul(f, i, j)?;
+                }
+            }
+            write!(f, "}}")
+        }
+
+        // same as `RealKernel::write_bias`. the accumulators hold the conjugate of the product, so
+        // the result is conjugated back after the bias is added
+        fn write_bias(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                ty,
+                set1,
+                swap_re_im,
+                load_unaligned,
+                mul_addsub,
+                xor,
+                add,
+                mr_div_n,
+                nr,
+                ..
+            } = self;
+
+            write!(
+                f,
+                "let beta = if !row_bias.is_null() || !col_bias.is_null() {{"
+            )?;
+            write!(f, "let beta_re = {set1}(beta.re);\n")?;
+            write!(f, "let beta_im = {set1}(beta.im);\n")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
+                    write!(
+                        f,
+                        "acc[{i}][{j}] = {mul_addsub}(
+                            {swap_re_im}(acc[{i}][{j}]),
+                            beta_im,
+                            {mul_addsub}(acc[{i}][{j}], beta_re, core::mem::zeroed()),
+                        );\n"
+                    )?;
+                }
+            }
+            write!(f, "if !row_bias.is_null() {{")?;
+            for i in 0..*mr_div_n {
+                if i + 1 < *mr_div_n || !self.need_mask {
+                    write!(
+                        f,
+                        "let bias = {load_unaligned}(row_bias.offset({i} * N) as *const {ty});\n"
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "let bias = {};\n",
+                        (self.mask_load_unaligned)(
+                            format!("row_bias.offset({i} * N) as *const {ty}"),
+                            "last_mask".to_string()
+                        ),
+                    )?;
+                }
+                for j in 0..*nr {
+                    write!(f, "acc[{i}][{j}] = {add}(acc[{i}][{j}], bias);\n")?;
+                }
+            }
+            write!(f, "}}\n")?;
+            write!(f, "if !col_bias.is_null() {{")?;
+            for j in 0..*nr {
+                write!(
+                    f,
+                    "let bias = {load_unaligned}([*col_bias.offset({j}); N as usize].as_ptr() as *const {ty});\n"
+                )?;
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = {add}(acc[{i}][{j}], bias);\n")?;
+                }
+            }
+            write!(f, "}}")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = core::mem::transmute({xor}(core::mem::transmute(acc[{i}][{j}]), core::mem::transmute(XOR_MASKS[1])));")?;
+                }
+            }
+            write!(
+                f,
+                "num_complex::Complex {{ re: 1.0, im: 0.0 }} }} else {{ beta }};\n"
+            )
+        }
+
+        fn write_load_lhs(
+            &self,
+            i: usize,
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> Result<(), std::fmt::Error> {
+            Ok(if i + 1 < self.mr_div_n || !self.need_mask {
+                write!(
+                    f,
+                    "tmp_lhs[{i}] = {}(lhs.offset(depth * lhs_cs + {i} * N) as *const {});\n",
+                    self.load_unaligned, self.ty,
+                )?;
+            } else {
+                write!(
+                    f,
+                    "tmp_lhs[{i}] = {};\n",
+                    (self.mask_load_unaligned)(
+                        format!("lhs.offset(depth * lhs_cs + {i} * N) as *const {}", self.ty,),
+                        "last_mask".to_string()
+                    ),
+                )?;
+            })
+        }
+    }
+    impl RealKernel {
+        // accumulates the products over the depth, with the rows of `rhs` scaled by `k_scale` if
+        // `scaled`
+        fn write_depth_loop(
+            &self,
+            f: &mut std::fmt::Formatter<'_>,
+            scaled: bool,
+        ) -> Result<(), std::fmt::Error> {
+            if let Some(k) = self.k {
+                for depth in 0..k {
+                    write!(f, "let depth = {depth};\n")?;
+                    if scaled {
+                        write_rhs_scale(f, false)?;
+                    }
+                    for i in 0..self.mr_div_n {
+                        self.write_load_lhs(i, f)?;
+                    }
+                    for j in 0..self.nr {
+                        write!(f, "let tmp_rhs = {}({});\n", self.set1, rhs_elem(j, scaled))?;
+
+                        for i in 0..self.mr_div_n {
+                            if depth > 0 {
+                                write!(
+                                    f,
+                                    "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
+                                    self.mul_add
+                                )?;
+                            } else {
+                                write!(
+                                    f,
+                                    "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs);\n",
+                                    self.mul
+                                )?;
+                            }
+                        }
+                    }
+                }
+            } else {
+                write!(f, "for depth in 0..k as isize {{")?;
+                if scaled {
+                    write_rhs_scale(f, false)?;
+                }
+                for i in 0..self.mr_div_n {
+                    self.write_load_lhs(i, f)?;
+                }
+                for j in 0..self.nr {
+                    write!(f, "let tmp_rhs = {}({});\n", self.set1, rhs_elem(j, scaled))?;
 
-                        for i in 0..self.mr_div_n {
-                            write!(
+                    for i in 0..self.mr_div_n {
+                        write!(
                             f,
-                            "acc[{i}][{j}] = {mul_add}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
+                            "acc[{i}][{j}] = {}(tmp_lhs[{i}], tmp_rhs, acc[{i}][{j}]);\n",
+                            self.mul_add
                         )?;
-                        }
                     }
-
-                    write!(f, "}}")?;
                 }
                 write!(f, "}}")?;
             }
+            Ok(())
+        }
 
-            write!(
-                f,
-                "let mask = XOR_MASKS[conj_lhs as usize + 2 * conj_rhs as usize];"
-            )?;
-            for j in 0..self.nr {
-                for i in 0..self.mr_div_n {
-                    write!(f, "acc[{i}][{j}] = core::mem::transmute({xor}(core::mem::transmute(acc[{i}][{j}]), core::mem::transmute(mask)));")?;
+        // multiplies the product by the row and column scales
+        fn write_scale(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                set1,
+                load_unaligned,
+                mul,
+                mr_div_n,
+                nr,
+                ..
+            } = self;
+
+            write!(f, "if !row_scale.is_null() {{")?;
+            for i in 0..*mr_div_n {
+                if i + 1 < *mr_div_n || !self.need_mask {
+                    write!(
+                        f,
+                        "let scale = {load_unaligned}(row_scale.offset({i} * N));\n"
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "let scale = {};\n",
+                        (self.mask_load_unaligned)(
+                            format!("row_scale.offset({i} * N)"),
+                            "last_mask".to_string()
+                        ),
+                    )?;
+                }
+                for j in 0..*nr {
+                    write!(f, "acc[{i}][{j}] = {mul}(scale, acc[{i}][{j}]);\n")?;
+                }
+            }
+            write!(f, "}}\n")?;
+            write!(f, "if !col_scale.is_null() {{")?;
+            for j in 0..*nr {
+                write!(f, "let scale = {set1}(*col_scale.offset({j}));\n")?;
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = {mul}(scale, acc[{i}][{j}]);\n")?;
                 }
             }
+            write!(f, "}}")
+        }
+
+        // adds the bias vectors to the product scaled by `beta`, then resets `beta` to one so that
+        // the stores only combine the result with `dst`
+        fn write_bias(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                set1,
+                load_unaligned,
+                mul,
+                add,
+                mr_div_n,
+                nr,
+                ..
+            } = self;
 
             write!(
                 f,
-                "if alpha == (num_complex::Complex {{ re: 1.0, im: 0.0 }}) {{"
+                "let beta = if !row_bias.is_null() || !col_bias.is_null() {{"
             )?;
-            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
-            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
-            for j in 0..self.nr {
-                for i in 0..self.mr_div_n {
-                    write!(f, "{{")?;
+            write!(f, "let beta = {set1}(beta);\n")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = {mul}(beta, acc[{i}][{j}]);\n")?;
+                }
+            }
+            write!(f, "if !row_bias.is_null() {{")?;
+            for i in 0..*mr_div_n {
+                if i + 1 < *mr_div_n || !self.need_mask {
                     write!(
                         f,
-                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
-                        self.ty,
+                        "let bias = {load_unaligned}(row_bias.offset({i} * N));\n"
                     )?;
-                    if i + 1 < self.mr_div_n || !self.need_mask {
-                        write!(
-                            f,
-                            "{store_unaligned}(
-                            dst,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    {load_unaligned}(dst),
-                                ),
-                            ),
-                        );\n",
-                        )?;
-                    } else {
-                        write!(
-                            f,
-                            "{mask_store_unaligned}(
-                            dst,
-                            last_mask,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    {},
-                                ),
-                            ),
-                        );\n",
-                            (self.mask_load_unaligned)(format!("dst"), "last_mask".to_string()),
-                        )?;
-                    }
-                    write!(f, "}}")?;
+                } else {
+                    write!(
+                        f,
+                        "let bias = {};\n",
+                        (self.mask_load_unaligned)(
+                            format!("row_bias.offset({i} * N)"),
+                            "last_mask".to_string()
+                        ),
+                    )?;
+                }
+                for j in 0..*nr {
+                    write!(f, "acc[{i}][{j}] = {add}(acc[{i}][{j}], bias);\n")?;
+                }
+            }
+            write!(f, "}}\n")?;
+            write!(f, "if !col_bias.is_null() {{")?;
+            for j in 0..*nr {
+                write!(f, "let bias = {set1}(*col_bias.offset({j}));\n")?;
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = {add}(acc[{i}][{j}], bias);\n")?;
                 }
             }
             write!(f, "}}")?;
+            write!(f, "1.0 }} else {{ beta }};\n")
+        }
+
+        // combines the accumulators with `dst` and applies the activation, then resets `alpha`,
+        // `beta` and `read_dst` so that the stores only write the accumulators
+        fn write_activation(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+            let Self {
+                set1,
+                load_unaligned,
+                mul,
+                mul_add,
+                activate,
+                mr_div_n,
+                nr,
+                ..
+            } = self;
 
             write!(
                 f,
-                "else if alpha == (num_complex::Complex {{ re: 0.0, im: 0.0 }}) {{"
+                "let (alpha, beta, read_dst) = if !matches!(activation, nano_gemm_core::Activation::None) {{"
             )?;
-            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
-            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
-            for j in 0..self.nr {
-                for i in 0..self.mr_div_n {
+            write!(f, "let beta = {set1}(beta);\n")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
+                    write!(f, "acc[{i}][{j}] = {mul}(beta, acc[{i}][{j}]);\n")?;
+                }
+            }
+            write!(f, "if alpha != 0.0 || read_dst {{")?;
+            write!(f, "let alpha = {set1}(alpha);\n")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
                     write!(f, "{{")?;
-                    write!(
-                        f,
-                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
-                        self.ty,
-                    )?;
-                    if i + 1 < self.mr_div_n || !self.need_mask {
+                    write!(f, "let src = src.offset({i} * N + {j} * src_cs);")?;
+                    if i + 1 < *mr_div_n || !self.need_mask {
                         write!(
                             f,
-                            "{store_unaligned}(
-                            dst,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    core::mem::zeroed(),
-                                ),
-                            ),
-                        );\n",
+                            "acc[{i}][{j}] = {mul_add}(alpha, {load_unaligned}(src), acc[{i}][{j}]);\n"
                         )?;
                     } else {
                         write!(
                             f,
-                            "{mask_store_unaligned}(
-                            dst,
-                            last_mask,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    core::mem::zeroed(),
-                                ),
-                            ),
-                        );\n"
+                            "acc[{i}][{j}] = {mul_add}(alpha, {}, acc[{i}][{j}]);\n",
+                            (self.mask_load_unaligned)("src".to_string(), "last_mask".to_string()),
                         )?;
                     }
                     write!(f, "}}")?;
                 }
             }
             write!(f, "}}")?;
-            write!(f, "else {{")?;
-            write!(f, "let beta_re = {}(beta.re);\n", self.set1)?;
-            write!(f, "let beta_im = {}(beta.im);\n", self.set1)?;
-            write!(f, "let alpha_re = {}(alpha.re);\n", self.set1)?;
-            write!(f, "let alpha_im = {}(alpha.im);\n", self.set1)?;
-            for j in 0..self.nr {
-                for i in 0..self.mr_div_n {
-                    write!(f, "{{")?;
+            for j in 0..*nr {
+                for i in 0..*mr_div_n {
                     write!(
                         f,
-                        "let dst = dst.offset({i} * N + {j} * dst_cs) as *mut {};",
-                        self.ty,
+                        "acc[{i}][{j}] = {activate}(activation, acc[{i}][{j}]);\n"
                     )?;
-                    if i + 1 < self.mr_div_n || !self.need_mask {
-                        write!(
-                            f,
-                            "let dst_conj = core::mem::transmute({xor}(
-                            core::mem::transmute({load_unaligned}(dst)),
-                            core::mem::transmute(XOR_MASKS[1]),
-                        ));"
-                        )?;
-
-                        write!(
-                            f,
-                            "{store_unaligned}(
-                            dst,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    {mul_addsub}(
-                                        {swap_re_im}(dst_conj),
-                                        alpha_im,
-                                        {mul_addsub}(
-                                            dst_conj,
-                                            alpha_re,
-                                            core::mem::zeroed(),
-                                        ),
-                                    ),
-                                ),
-                            ),
-                        );\n",
-                        )?;
-                    } else {
-                        write!(
-                            f,
-                            "let dst_conj = core::mem::transmute({xor}(
-                            core::mem::transmute({}),
-                            core::mem::transmute(XOR_MASKS[1]),
-                        ));",
-                            (self.mask_load_unaligned)(format!("dst"), "last_mask".to_string())
-                        )?;
-
-                        write!(
-                            f,
-                            "{mask_store_unaligned}(
-                            dst,
-                            last_mask,
-                            {mul_addsub}(
-                                {swap_re_im}(acc[{i}][{j}]),
-                                beta_im,
-                                {mul_addsub}(
-                                    acc[{i}][{j}],
-                                    beta_re,
-                                    {mul_addsub}(
-                                        {swap_re_im}(dst_conj),
-                                        alpha_im,
-                                        {mul_addsub}(
-                                            dst_conj,
-                                            alpha_re,
-                                            core::mem::zeroed(),
-                                        ),
-                                    ),
-                                ),
-                            ),
-                        );\n"
-                        )?;
-                    }
-                    write!(f, "}}")?;
                 }
             }
-            write!(f, "}}")?;
-
-            write!(f, "}}\n")
+            write!(
+                f,
+                "(0.0, 1.0, false) }} else {{ (alpha, beta, read_dst) }};\n"
+            )
         }
-    }
 
-    impl CplxKernel {
-        fn write_load_lhs(
-            &self,
-            i: usize,
-            f: &mut std::fmt::Formatter<'_>,
-        ) -> Result<(), std::fmt::Error> {
-            Ok(if i + 1 < self.mr_div_n || !self.need_mask {
-                write!(
-                    f,
-                    "tmp_lhs[{i}] = {}(lhs.offset(depth * lhs_cs + {i} * N) as *const {});\n",
-                    self.load_unaligned, self.ty,
-                )?;
-            } else {
-                write!(
-                    f,
-                    "tmp_lhs[{i}] = {};\n",
-                    (self.mask_load_unaligned)(
-                        format!("lhs.offset(depth * lhs_cs + {i} * N) as *const {}", self.ty,),
-                        "last_mask".to_string()
-                    ),
-                )?;
-            })
-        }
-    }
-    impl RealKernel {
         fn write_load_lhs(
             &self,
             i: usize,
@@ -1543,6 +2598,13 @@ pub mod x86 {
         let mut code = String::new();
 
         write!(code, "pub mod f32 {{\n")?;
+        for ops in [
+            ActivationOps::x86("f32", 128, ""),
+            ActivationOps::x86("f32", 256, ""),
+            ActivationOps::x86("f32", 512, "#[cfg(feature = \"nightly\")]"),
+        ] {
+            write!(code, "{ops}")?;
+        }
         write!(code, "pub mod f32x1 {{\n")?;
         {
             write!(
@@ -1569,6 +2631,8 @@ pub mod x86 {
                             mask_store_unaligned: "",
                             mul_add: "_mm_fmadd_ss",
                             mul: "_mm_mul_ss",
+                            add: "_mm_add_ss",
+                            activate: "super::activate_128",
                             need_mask: false,
                         };
 
@@ -1625,6 +2689,8 @@ pub mod x86 {
                             mask_store_unaligned: "",
                             mul_add: "_mm_fmadd_ps",
                             mul: "_mm_mul_ps",
+                            add: "_mm_add_ps",
+                            activate: "super::activate_128",
                         };
 
                         write!(code, "{kernel}")?;
@@ -1683,6 +2749,8 @@ pub mod x86 {
                             mask_store_unaligned: "_mm_maskstore_ps",
                             mul_add: "_mm_fmadd_ps",
                             mul: "_mm_mul_ps",
+                            add: "_mm_add_ps",
+                            activate: "super::activate_128",
                         };
 
                         write!(code, "{kernel}")?;
@@ -1753,6 +2821,8 @@ pub mod x86 {
                             mask_store_unaligned: "_mm256_maskstore_ps",
                             mul_add: "_mm256_fmadd_ps",
                             mul: "_mm256_mul_ps",
+                            add: "_mm256_add_ps",
+                            activate: "super::activate_256",
                         };
 
                         write!(code, "{kernel}")?;
@@ -1796,6 +2866,21 @@ pub mod x86 {
             ]) }};
         "
             )?;
+
+            let kernel = GemvKernel {
+                ty: "f32",
+                reg_ty: "__m256",
+                n: 8,
+                target_features: "avx,avx2,fma",
+                setzero: "_mm256_setzero_ps",
+                set1: "_mm256_set1_ps",
+                load_unaligned: "_mm256_loadu_ps",
+                store_unaligned: "_mm256_storeu_ps",
+                add: "_mm256_add_ps",
+                mul: "_mm256_mul_ps",
+                mul_add: "_mm256_fmadd_ps",
+            };
+            write!(code, "pub mod gemv {{\n{kernel}}}\n")?;
         }
         write!(code, "}}\n")?;
 
@@ -1828,6 +2913,8 @@ pub mod x86 {
                             mask_store_unaligned: "_mm512_mask_storeu_ps",
                             mul_add: "_mm512_fmadd_ps",
                             mul: "_mm512_mul_ps",
+                            add: "_mm512_add_ps",
+                            activate: "super::activate_512",
                         };
 
                         write!(code, "{kernel}")?;
@@ -1889,6 +2976,13 @@ pub mod x86 {
         let mut code = String::new();
 
         write!(code, "pub mod f64 {{\n")?;
+        for ops in [
+            ActivationOps::x86("f64", 128, ""),
+            ActivationOps::x86("f64", 256, ""),
+            ActivationOps::x86("f64", 512, "#[cfg(feature = \"nightly\")]"),
+        ] {
+            write!(code, "{ops}")?;
+        }
         write!(code, "pub mod f64x1 {{\n")?;
         {
             write!(
@@ -1916,6 +3010,8 @@ pub mod x86 {
                             mask_store_unaligned: "",
                             mul_add: "_mm_fmadd_sd",
                             mul: "_mm_mul_sd",
+                            add: "_mm_add_sd",
+                            activate: "super::activate_128",
                         };
 
                         write!(code, "{kernel}")?;
@@ -1971,6 +3067,8 @@ pub mod x86 {
                             mask_store_unaligned: "",
                             mul_add: "_mm_fmadd_pd",
                             mul: "_mm_mul_pd",
+                            add: "_mm_add_pd",
+                            activate: "super::activate_128",
                         };
 
                         write!(code, "{kernel}")?;
@@ -2035,6 +3133,8 @@ pub mod x86 {
                                 mask_store_unaligned: "_mm256_maskstore_pd",
                                 mul_add: "_mm256_fmadd_pd",
                                 mul: "_mm256_mul_pd",
+                                add: "_mm256_add_pd",
+                                activate: "super::activate_256",
                             };
 
                             write!(code, "{kernel}")?;
@@ -2074,6 +3174,21 @@ pub mod x86 {
             ]) }};
         "
                 )?;
+
+                let kernel = GemvKernel {
+                    ty: "f64",
+                    reg_ty: "__m256d",
+                    n: 4,
+                    target_features: "avx,avx2,fma",
+                    setzero: "_mm256_setzero_pd",
+                    set1: "_mm256_set1_pd",
+                    load_unaligned: "_mm256_loadu_pd",
+                    store_unaligned: "_mm256_storeu_pd",
+                    add: "_mm256_add_pd",
+                    mul: "_mm256_mul_pd",
+                    mul_add: "_mm256_fmadd_pd",
+                };
+                write!(code, "pub mod gemv {{\n{kernel}}}\n")?;
             }
         }
         write!(code, "}}\n")?;
@@ -2112,6 +3227,8 @@ pub mod x86 {
                             mask_store_unaligned: "_mm512_mask_storeu_pd",
                             mul_add: "_mm512_fmadd_pd",
                             mul: "_mm512_mul_pd",
+                            add: "_mm512_add_pd",
+                            activate: "super::activate_512",
                         };
 
                         write!(code, "{kernel}")?;
@@ -2203,6 +3320,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm_maskstore_ps",
                             swap_re_im: "_mm_permute_ps::<0b10_11_00_01>",
                             mul_addsub: "_mm_fmsubadd_ps",
+                            add: "_mm_add_ps",
                             mul_subadd: "_mm_fmaddsub_ps",
                             xor: "_mm_xor_ps",
                         };
@@ -2273,6 +3391,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm_maskstore_ps",
                             swap_re_im: "_mm_permute_ps::<0b10_11_00_01>",
                             mul_addsub: "_mm_fmsubadd_ps",
+                            add: "_mm_add_ps",
                             mul_subadd: "_mm_fmaddsub_ps",
                             xor: "_mm_xor_ps",
                         };
@@ -2342,6 +3461,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm256_maskstore_ps",
                             swap_re_im: "_mm256_permute_ps::<0b10_11_00_01>",
                             mul_addsub: "_mm256_fmsubadd_ps",
+                            add: "_mm256_add_ps",
                             mul_subadd: "_mm256_fmaddsub_ps",
                             xor: "_mm256_xor_ps",
                         };
@@ -2429,6 +3549,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm512_mask_storeu_ps",
                             swap_re_im: "_mm512_permute_ps::<0b10_11_00_01>",
                             mul_addsub: "crate::x86::subadd_ps",
+                            add: "_mm512_add_ps",
                             mul_subadd: "_mm512_fmaddsub_ps",
                             xor: "_mm512_xor_si512",
                         };
@@ -2522,6 +3643,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm_maskstore_pd",
                             swap_re_im: "_mm_permute_pd::<0b01>",
                             mul_addsub: "_mm_fmsubadd_pd",
+                            add: "_mm_add_pd",
                             mul_subadd: "_mm_fmaddsub_pd",
                             xor: "_mm_xor_pd",
                         };
@@ -2592,6 +3714,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm256_maskstore_pd",
                             swap_re_im: "_mm256_permute_pd::<0b0101>",
                             mul_addsub: "_mm256_fmsubadd_pd",
+                            add: "_mm256_add_pd",
                             mul_subadd: "_mm256_fmaddsub_pd",
                             xor: "_mm256_xor_pd",
                         };
@@ -2677,6 +3800,7 @@ pub mod x86 {
                             mask_store_unaligned: "_mm512_mask_storeu_pd",
                             swap_re_im: "_mm512_permute_pd::<0b01010101>",
                             mul_addsub: "crate::x86::subadd_pd",
+                            add: "_mm512_add_pd",
                             mul_subadd: "_mm512_fmaddsub_pd",
                             xor: "_mm512_xor_si512",
                         };
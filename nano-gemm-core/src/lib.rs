@@ -1,20 +1,76 @@
 #![no_std]
 
+/// Activation function applied to each element of the result before it's stored.
+///
+/// The smooth functions are evaluated with a polynomial approximation of `exp`, and can be slightly
+/// less accurate than the ones of the standard library.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Activation<T> {
+    /// `x`
+    None,
+    /// `max(x, 0)`
+    Relu,
+    /// `x` if `x > 0`, `slope * x` otherwise
+    LeakyRelu(T),
+    /// `0.5 * x * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3)))`
+    Gelu,
+    /// `1 / (1 + exp(-x))`
+    Sigmoid,
+    /// `tanh(x)`
+    Tanh,
+}
+
 #[derive(Copy, Clone)]
 pub struct MicroKernelData<T> {
     pub alpha: T,
     pub beta: T,
+    /// whether `dst` is read even when `alpha` is zero, so that NaN and infinite values propagate
+    pub read_dst: bool,
     pub conj_lhs: bool,
     pub conj_rhs: bool,
     pub k: usize,
     pub dst_cs: isize,
+    /// if not null, read in place of `dst`, as a matrix with a unit row stride and a column stride
+    /// of `src_cs`. must be offset like `dst` for each tile
+    pub src: *const T,
+    pub src_cs: isize,
     pub lhs_cs: isize,
     pub rhs_rs: isize,
     pub rhs_cs: isize,
     pub last_mask: *const (),
+    /// if not null, the row `i` of the product is multiplied by `row_scale[i]`
+    pub row_scale: *const T,
+    /// if not null, the column `j` of the product is multiplied by `col_scale[j]`
+    pub col_scale: *const T,
+    /// if not null, the row `depth` of `rhs` is multiplied by `k_scale[depth]` while it's read
+    pub k_scale: *const T,
+    /// if not null, `row_bias[i]` is added to the row `i` of the scaled product
+    pub row_bias: *const T,
+    /// if not null, `col_bias[j]` is added to the column `j` of the scaled product
+    pub col_bias: *const T,
+    /// applied to the result before it's stored. only supported by the real kernels
+    pub activation: Activation<T>,
 }
 unsafe impl<T: Sync> Sync for MicroKernelData<T> {}
 unsafe impl<T: Send> Send for MicroKernelData<T> {}
 
 pub type MicroKernel<T> =
     unsafe fn(data: &MicroKernelData<T>, dst: *mut T, lhs: *const T, rhs: *const T);
+
+/// Arguments of a matrix-vector kernel, computing `dst = alpha * dst + beta * lhs * rhs` where
+/// `lhs` is an `m × k` matrix, and `rhs` and `dst` are column vectors.
+#[derive(Copy, Clone)]
+pub struct GemvData<T> {
+    pub alpha: T,
+    pub beta: T,
+    /// whether `dst` is read even when `alpha` is zero, so that NaN and infinite values propagate
+    pub read_dst: bool,
+    pub m: usize,
+    pub k: usize,
+    pub dst_rs: isize,
+    pub lhs_rs: isize,
+    pub lhs_cs: isize,
+    pub rhs_rs: isize,
+}
+
+pub type GemvKernel<T> = unsafe fn(data: &GemvData<T>, dst: *mut T, lhs: *const T, rhs: *const T);
@@ -0,0 +1,245 @@
+use crate::{
+    structured_workspace_size, triangular_workspace_size, GemmScalar, Op, Plan, Structure, Triangle,
+};
+
+/// Builder for a [`Plan`], where any of the strides of the operands can be fixed at plan time.
+///
+/// Strides that are left unset may take any value when executing the plan, while the fixed ones
+/// must match the operands. Knowing the strides in advance lets the plan pick, once, whether to
+/// compute `dst` or `dst^T`, and whether its kernels work on the operands in place or on copies
+/// of them. The microkernels are the same as the ones of [`Plan::new`], and aren't specialized on
+/// the values of the strides.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlanBuilder {
+    m: usize,
+    n: usize,
+    k: usize,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    lhs_op: Op,
+    rhs_op: Op,
+    dst_op: Op,
+    dst_triangle: Option<Triangle>,
+    lhs_structure: Option<Structure>,
+    rhs_structure: Option<Structure>,
+}
+
+impl PlanBuilder {
+    /// Creates a builder for a plan computing `op(dst) = alpha * op(dst) + beta * op(lhs) *
+    /// op(rhs)`, where `op(lhs)` is an `m × k` matrix and `op(rhs)` is a `k × n` matrix.
+    #[inline]
+    pub fn new(m: usize, n: usize, k: usize) -> Self {
+        Self {
+            m,
+            n,
+            k,
+            dst_rs: isize::MIN,
+            dst_cs: isize::MIN,
+            lhs_rs: isize::MIN,
+            lhs_cs: isize::MIN,
+            rhs_rs: isize::MIN,
+            rhs_cs: isize::MIN,
+            lhs_op: Op::NoTrans,
+            rhs_op: Op::NoTrans,
+            dst_op: Op::NoTrans,
+            dst_triangle: None,
+            lhs_structure: None,
+            rhs_structure: None,
+        }
+    }
+
+    /// Fixes the row stride of the stored `dst`.
+    #[inline]
+    pub fn dst_rs(mut self, dst_rs: isize) -> Self {
+        self.dst_rs = dst_rs;
+        self
+    }
+    /// Fixes the column stride of the stored `dst`.
+    #[inline]
+    pub fn dst_cs(mut self, dst_cs: isize) -> Self {
+        self.dst_cs = dst_cs;
+        self
+    }
+    /// Fixes the row stride of the stored `lhs`.
+    #[inline]
+    pub fn lhs_rs(mut self, lhs_rs: isize) -> Self {
+        self.lhs_rs = lhs_rs;
+        self
+    }
+    /// Fixes the column stride of the stored `lhs`.
+    #[inline]
+    pub fn lhs_cs(mut self, lhs_cs: isize) -> Self {
+        self.lhs_cs = lhs_cs;
+        self
+    }
+    /// Fixes the row stride of the stored `rhs`.
+    #[inline]
+    pub fn rhs_rs(mut self, rhs_rs: isize) -> Self {
+        self.rhs_rs = rhs_rs;
+        self
+    }
+    /// Fixes the column stride of the stored `rhs`.
+    #[inline]
+    pub fn rhs_cs(mut self, rhs_cs: isize) -> Self {
+        self.rhs_cs = rhs_cs;
+        self
+    }
+
+    /// Sets the operation applied to `lhs`.
+    #[inline]
+    pub fn lhs_op(mut self, lhs_op: Op) -> Self {
+        self.lhs_op = lhs_op;
+        self
+    }
+    /// Sets the operation applied to `rhs`.
+    #[inline]
+    pub fn rhs_op(mut self, rhs_op: Op) -> Self {
+        self.rhs_op = rhs_op;
+        self
+    }
+    /// Sets the operation applied to `dst`.
+    #[inline]
+    pub fn dst_op(mut self, dst_op: Op) -> Self {
+        self.dst_op = dst_op;
+        self
+    }
+
+    /// Restricts the plan to the `triangle` of the stored `dst`, as in BLAS `gemmt`.
+    ///
+    /// The elements of `dst` outside of the triangle are neither computed, read nor written.
+    #[inline]
+    pub fn dst_triangle(mut self, triangle: Triangle) -> Self {
+        self.dst_triangle = Some(triangle);
+        self
+    }
+
+    /// Sets the structure of the stored `lhs`, which must be square. Only the triangle given by
+    /// the structure is read.
+    #[inline]
+    pub fn lhs_structure(mut self, structure: Structure) -> Self {
+        self.lhs_structure = Some(structure);
+        self
+    }
+    /// Sets the structure of the stored `rhs`, which must be square. Only the triangle given by
+    /// the structure is read.
+    #[inline]
+    pub fn rhs_structure(mut self, structure: Structure) -> Self {
+        self.rhs_structure = Some(structure);
+        self
+    }
+
+    /// Creates the plan.
+    ///
+    /// # Panics
+    /// Panics if both `lhs` and `rhs` have a structure, if the structured operand isn't square, or
+    /// if a structure is combined with a triangular `dst`.
+    #[track_caller]
+    pub fn build<T: GemmScalar>(&self) -> Plan<T> {
+        let &Self {
+            m,
+            n,
+            k,
+            lhs_op,
+            rhs_op,
+            dst_op,
+            dst_triangle,
+            lhs_structure,
+            rhs_structure,
+            ..
+        } = self;
+
+        equator::assert!(all(
+            lhs_structure.is_none() || rhs_structure.is_none(),
+            lhs_structure.is_none() || m == k,
+            rhs_structure.is_none() || k == n,
+            dst_triangle.is_none() || (lhs_structure.is_none() && rhs_structure.is_none()),
+        ));
+
+        // strides of `op(mat)`
+        let strides = |op: Op, rs: isize, cs: isize| {
+            if op == Op::NoTrans {
+                (rs, cs)
+            } else {
+                (cs, rs)
+            }
+        };
+        let (dst_rs, dst_cs) = strides(dst_op, self.dst_rs, self.dst_cs);
+        let (lhs_rs, _) = strides(lhs_op, self.lhs_rs, self.lhs_cs);
+        let (_, rhs_cs) = strides(rhs_op, self.rhs_rs, self.rhs_cs);
+
+        // the kernels can write to `dst` and read from `lhs` directly if both have a unit row
+        // stride. otherwise we may be able to compute `dst^T = rhs^T * lhs^T` instead.
+        // if neither works, we prefer the formulation where `dst` doesn't need to be copied
+        let direct = dst_rs == 1 && lhs_rs == 1;
+        let direct_transposed = dst_cs == 1 && rhs_cs == 1;
+        // the structured operand must be the `lhs` of the kernels
+        let transpose = if lhs_structure.is_some() {
+            false
+        } else if rhs_structure.is_some() {
+            true
+        } else if direct {
+            false
+        } else if direct_transposed {
+            true
+        } else if dst_rs == 1 {
+            false
+        } else if dst_cs == 1 {
+            true
+        } else {
+            dst_op != Op::NoTrans
+        };
+        // structured operands are multiplied by strips of `dst`, which the millikernels sized for
+        // the whole of `dst` can't handle. the copying millikernel works on any strip, and still
+        // skips the copies when the strides allow it
+        let is_col_major = (if transpose { direct_transposed } else { direct })
+            && lhs_structure.is_none()
+            && rhs_structure.is_none();
+
+        // triangular operands are multiplied by parts of varying depth, which the kernels
+        // specialized for a fixed depth can't handle
+        let triangular = lhs_structure
+            .or(rhs_structure)
+            .is_some_and(|structure| structure.nonzero_triangle().is_some());
+        let kernel_k = if triangular && k != 0 {
+            Ord::max(k, 17)
+        } else {
+            k
+        };
+
+        let mut plan = if transpose {
+            T::new_plan_impl(n, m, kernel_k, is_col_major)
+        } else {
+            T::new_plan_impl(m, n, kernel_k, is_col_major)
+        };
+
+        plan.m = m;
+        plan.n = n;
+        plan.k = k;
+        plan.dst_rs = self.dst_rs;
+        plan.dst_cs = self.dst_cs;
+        plan.lhs_rs = self.lhs_rs;
+        plan.lhs_cs = self.lhs_cs;
+        plan.rhs_rs = self.rhs_rs;
+        plan.rhs_cs = self.rhs_cs;
+        plan.lhs_op = lhs_op;
+        plan.rhs_op = rhs_op;
+        plan.dst_op = dst_op;
+        plan.transpose = transpose;
+        plan.triangle = dst_triangle;
+        plan.lhs_structure = lhs_structure;
+        plan.rhs_structure = rhs_structure;
+        if (lhs_structure.is_some() || rhs_structure.is_some()) && m != 0 && n != 0 {
+            // the reconstructed strips of the structured operand come first
+            plan.workspace_size += structured_workspace_size(k);
+        }
+        if dst_triangle.is_some() && m != 0 && n != 0 {
+            // the blocks crossing the diagonal come first
+            plan.workspace_size += triangular_workspace_size(if transpose { m } else { n });
+        }
+        plan
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{GemmScalar, Plan, PlanBuilder};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+};
+
+/// Thread-safe cache of plans, keyed by the scalar type, the dimensions and the layout of the
+/// operands.
+///
+/// Retrieving a cached plan skips the CPU feature detection and the kernel table lookups done when
+/// creating a plan.
+#[derive(Debug, Default)]
+pub struct PlanCache {
+    plans: RwLock<HashMap<(TypeId, PlanBuilder), Box<dyn Any + Send + Sync>>>,
+}
+
+impl PlanCache {
+    /// Creates an empty cache.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process-global cache.
+    #[inline]
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<PlanCache> = OnceLock::new();
+        GLOBAL.get_or_init(Self::new)
+    }
+
+    /// Returns the plan described by `builder`, creating it and inserting it in the cache if it
+    /// isn't already present.
+    #[track_caller]
+    pub fn get<T: GemmScalar>(&self, builder: &PlanBuilder) -> Plan<T> {
+        let key = (TypeId::of::<T>(), *builder);
+
+        if let Some(plan) = self.plans.read().unwrap().get(&key) {
+            return *plan.downcast_ref::<Plan<T>>().unwrap();
+        }
+
+        let plan = builder.build::<T>();
+        *self
+            .plans
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Box::new(plan))
+            .downcast_ref::<Plan<T>>()
+            .unwrap()
+    }
+
+    /// Returns the number of cached plans.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.plans.read().unwrap().len()
+    }
+
+    /// Returns `true` if the cache contains no plans.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the plans from the cache.
+    #[inline]
+    pub fn clear(&self) {
+        self.plans.write().unwrap().clear();
+    }
+}
@@ -0,0 +1,113 @@
+use crate::Isa;
+use core::fmt;
+
+/// Error returned when the arguments passed to a plan don't match it, or describe matrices that
+/// can't be safely operated on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlanError {
+    /// `(m, n, k)` differs from the dimensions the plan was created with.
+    ShapeMismatch {
+        expected: (usize, usize, usize),
+        found: (usize, usize, usize),
+    },
+    /// A stride differs from the value the plan was created with.
+    StrideMismatch {
+        stride: &'static str,
+        expected: isize,
+        found: isize,
+    },
+    /// The byte offset of one of the matrix elements doesn't fit in an `isize`.
+    OffsetOverflow { operand: &'static str },
+    /// The memory spanned by `dst` overlaps with the memory spanned by `lhs` or `rhs`.
+    Aliasing { operand: &'static str },
+    /// The kernels of the requested instruction set aren't available.
+    UnsupportedIsa { isa: Isa },
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PlanError::ShapeMismatch { expected, found } => write!(
+                f,
+                "shape mismatch: the plan was created with (m, n, k) = {expected:?}, found {found:?}"
+            ),
+            PlanError::StrideMismatch {
+                stride,
+                expected,
+                found,
+            } => write!(
+                f,
+                "stride mismatch: the plan was created with {stride} = {expected}, found {found}"
+            ),
+            PlanError::OffsetOverflow { operand } => {
+                write!(f, "the byte offsets of `{operand}` overflow an isize")
+            }
+            PlanError::Aliasing { operand } => {
+                write!(f, "`dst` overlaps with `{operand}`")
+            }
+            PlanError::UnsupportedIsa { isa } => {
+                write!(f, "the {isa} kernels aren't available")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PlanError {}
+
+/// Error returned when a matrix can't be factored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FactorError {
+    /// The matrix isn't positive definite, as found when factoring column `col`.
+    NotPositiveDefinite { col: usize },
+    /// The matrix is singular, as found when factoring column `col`.
+    Singular { col: usize },
+}
+
+impl fmt::Display for FactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FactorError::NotPositiveDefinite { col } => {
+                write!(f, "the matrix isn't positive definite (column {col})")
+            }
+            FactorError::Singular { col } => {
+                write!(f, "the matrix is singular (column {col})")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FactorError {}
+
+/// Returns the range of addresses spanned by the elements of the matrix, or `None` if one of the
+/// byte offsets overflows an `isize`.
+#[inline]
+pub(crate) fn byte_range<T>(
+    ptr: *const T,
+    nrows: usize,
+    ncols: usize,
+    row_stride: isize,
+    col_stride: isize,
+) -> Option<core::ops::Range<usize>> {
+    let size = core::mem::size_of::<T>() as isize;
+    if nrows == 0 || ncols == 0 {
+        return Some(0..0);
+    }
+
+    let last_row = isize::try_from(nrows - 1).ok()?;
+    let last_col = isize::try_from(ncols - 1).ok()?;
+    let row = last_row.checked_mul(row_stride)?.checked_mul(size)?;
+    let col = last_col.checked_mul(col_stride)?.checked_mul(size)?;
+
+    let min = Ord::min(row, 0).checked_add(Ord::min(col, 0))?;
+    let max = Ord::max(row, 0)
+        .checked_add(Ord::max(col, 0))?
+        .checked_add(size)?;
+    max.checked_sub(min)?;
+
+    let addr = ptr as usize;
+    let start = addr.checked_add_signed(min)?;
+    let end = addr.checked_add_signed(max)?;
+    Some(start..end)
+}
@@ -0,0 +1,399 @@
+use crate::{c32, c64, sealed::Activate, Activation};
+
+/// Element-wise operations fused into the kernels of a [`Plan`](crate::Plan), which are applied
+/// to the tiles of the result while they're stored, instead of in a separate pass over `dst`.
+///
+/// See [`Plan::execute_fused`](crate::Plan::execute_fused) for the full formula.
+#[derive(Copy, Clone, Debug)]
+pub struct FusedOps<'a, T> {
+    pub(crate) row_scale: Option<&'a [T]>,
+    pub(crate) col_scale: Option<&'a [T]>,
+    pub(crate) k_scale: Option<&'a [T]>,
+    pub(crate) row_bias: Option<&'a [T]>,
+    pub(crate) col_bias: Option<&'a [T]>,
+    pub(crate) activation: Activation<T>,
+}
+
+impl<T> Default for FusedOps<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> FusedOps<'a, T> {
+    /// Creates an empty set of operations.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            row_scale: None,
+            col_scale: None,
+            k_scale: None,
+            row_bias: None,
+            col_bias: None,
+            activation: Activation::None,
+        }
+    }
+
+    /// Multiplies the row `i` of the product by `scale[i]`. `scale` must have one element per
+    /// row.
+    #[inline]
+    pub fn row_scale(mut self, scale: &'a [T]) -> Self {
+        self.row_scale = Some(scale);
+        self
+    }
+
+    /// Multiplies the column `j` of the product by `scale[j]`. `scale` must have one element per
+    /// column.
+    #[inline]
+    pub fn col_scale(mut self, scale: &'a [T]) -> Self {
+        self.col_scale = Some(scale);
+        self
+    }
+
+    /// Multiplies the column `depth` of `lhs` and the row `depth` of `rhs` by `scale[depth]`, so
+    /// that the product is `lhs * diag(scale) * rhs`. `scale` must have one element per column
+    /// of `lhs`.
+    #[inline]
+    pub fn k_scale(mut self, scale: &'a [T]) -> Self {
+        self.k_scale = Some(scale);
+        self
+    }
+
+    /// Adds `bias[i]` to the row `i` of the result. `bias` must have one element per row.
+    #[inline]
+    pub fn row_bias(mut self, bias: &'a [T]) -> Self {
+        self.row_bias = Some(bias);
+        self
+    }
+
+    /// Adds `bias[j]` to the column `j` of the result. `bias` must have one element per column.
+    #[inline]
+    pub fn col_bias(mut self, bias: &'a [T]) -> Self {
+        self.col_bias = Some(bias);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.row_scale.is_none()
+            && self.col_scale.is_none()
+            && self.k_scale.is_none()
+            && self.row_bias.is_none()
+            && self.col_bias.is_none()
+            && matches!(self.activation, Activation::None)
+    }
+
+    #[inline]
+    pub(crate) fn as_ptrs(&self) -> FusedPtrs<T>
+    where
+        T: Copy,
+    {
+        let ptr = |v: Option<&[T]>| v.map_or(core::ptr::null(), <[T]>::as_ptr);
+        FusedPtrs {
+            src: core::ptr::null(),
+            src_rs: 0,
+            src_cs: 0,
+            row_scale: ptr(self.row_scale),
+            col_scale: ptr(self.col_scale),
+            k_scale: ptr(self.k_scale),
+            row_bias: ptr(self.row_bias),
+            col_bias: ptr(self.col_bias),
+            activation: self.activation,
+        }
+    }
+}
+
+impl FusedOps<'_, f32> {
+    /// Applies `activation` to the result, after the biases are added.
+    #[inline]
+    pub fn activation(mut self, activation: Activation<f32>) -> Self {
+        self.activation = activation;
+        self
+    }
+}
+
+impl FusedOps<'_, f64> {
+    /// Applies `activation` to the result, after the biases are added.
+    #[inline]
+    pub fn activation(mut self, activation: Activation<f64>) -> Self {
+        self.activation = activation;
+        self
+    }
+}
+
+/// Same as [`FusedOps`], as passed to the millikernels. The pointers are null if unset.
+///
+/// This also holds the matrix read in place of `dst`, if it's separate.
+#[derive(Copy, Clone)]
+pub(crate) struct FusedPtrs<T> {
+    pub src: *const T,
+    pub src_rs: isize,
+    pub src_cs: isize,
+    pub row_scale: *const T,
+    pub col_scale: *const T,
+    pub k_scale: *const T,
+    pub row_bias: *const T,
+    pub col_bias: *const T,
+    pub activation: Activation<T>,
+}
+
+impl<T: Copy> FusedPtrs<T> {
+    pub const NONE: Self = Self {
+        src: core::ptr::null(),
+        src_rs: 0,
+        src_cs: 0,
+        row_scale: core::ptr::null(),
+        col_scale: core::ptr::null(),
+        k_scale: core::ptr::null(),
+        row_bias: core::ptr::null(),
+        col_bias: core::ptr::null(),
+        activation: Activation::None,
+    };
+
+    #[inline(always)]
+    pub fn is_none(&self) -> bool {
+        self.src.is_null()
+            && self.row_scale.is_null()
+            && self.col_scale.is_null()
+            && self.k_scale.is_null()
+            && self.row_bias.is_null()
+            && self.col_bias.is_null()
+            && matches!(self.activation, Activation::None)
+    }
+
+    /// Returns the operations applied to the transpose of the result.
+    #[inline(always)]
+    pub fn transpose(self) -> Self {
+        Self {
+            src: self.src,
+            src_rs: self.src_cs,
+            src_cs: self.src_rs,
+            row_scale: self.col_scale,
+            col_scale: self.row_scale,
+            k_scale: self.k_scale,
+            row_bias: self.col_bias,
+            col_bias: self.row_bias,
+            activation: self.activation,
+        }
+    }
+
+    /// Returns the operations applied to the block of the result starting at row `i` and column
+    /// `j`.
+    #[inline(always)]
+    pub fn block(&self, i: usize, j: usize) -> Self {
+        let offset = |ptr: *const T, offset: usize| {
+            if ptr.is_null() {
+                ptr
+            } else {
+                ptr.wrapping_add(offset)
+            }
+        };
+        Self {
+            src: if self.src.is_null() {
+                self.src
+            } else {
+                self.src
+                    .wrapping_offset(i as isize * self.src_rs + j as isize * self.src_cs)
+            },
+            src_rs: self.src_rs,
+            src_cs: self.src_cs,
+            row_scale: offset(self.row_scale, i),
+            col_scale: offset(self.col_scale, j),
+            k_scale: self.k_scale,
+            row_bias: offset(self.row_bias, i),
+            col_bias: offset(self.col_bias, j),
+            activation: self.activation,
+        }
+    }
+
+    /// Returns the operations applied to a partial product, when the depth is split in blocks
+    /// and the current one starts at `depth`. `src` is read and the biases are added with the first
+    /// block, and the activation is applied with the `last` one, once the result is complete.
+    #[inline(always)]
+    pub fn depth_block(&self, depth: usize, last: bool) -> Self {
+        let first = depth == 0;
+        Self {
+            src: if first { self.src } else { core::ptr::null() },
+            src_rs: self.src_rs,
+            src_cs: self.src_cs,
+            row_scale: self.row_scale,
+            col_scale: self.col_scale,
+            k_scale: if self.k_scale.is_null() {
+                self.k_scale
+            } else {
+                self.k_scale.wrapping_add(depth)
+            },
+            row_bias: if first {
+                self.row_bias
+            } else {
+                core::ptr::null()
+            },
+            col_bias: if first {
+                self.col_bias
+            } else {
+                core::ptr::null()
+            },
+            activation: if last {
+                self.activation
+            } else {
+                Activation::None
+            },
+        }
+    }
+
+    /// Reads the element at row `i` and column `j` of `src`, or `*dst` if it's not set.
+    #[inline(always)]
+    pub unsafe fn read_src(&self, i: usize, j: usize, dst: *const T) -> T {
+        if self.src.is_null() {
+            *dst
+        } else {
+            *self
+                .src
+                .offset(i as isize * self.src_rs + j as isize * self.src_cs)
+        }
+    }
+
+    /// Returns the `src` passed to the kernels for the `m × n` block of `dst`, that must have a
+    /// unit row stride. The kernels can't read other layouts of `src`, so they're copied to `dst`
+    /// instead, and read from there. Nothing is copied if the kernels don't `read` `src`.
+    #[inline(always)]
+    pub unsafe fn src_block(
+        &self,
+        m: usize,
+        n: usize,
+        dst: *mut T,
+        dst_cs: isize,
+        read: bool,
+    ) -> *const T {
+        if !read || self.src.is_null() || self.src_rs == 1 {
+            return self.src;
+        }
+        for j in 0..n {
+            for i in 0..m {
+                *dst.offset(i as isize + j as isize * dst_cs) = self.read_src(i, j, dst);
+            }
+        }
+        core::ptr::null()
+    }
+
+    /// Scales the element at row `i` and column `j` of the product.
+    #[inline(always)]
+    pub unsafe fn scale(&self, i: usize, j: usize, mut x: T) -> T
+    where
+        T: core::ops::Mul<Output = T>,
+    {
+        if !self.row_scale.is_null() {
+            x = *self.row_scale.add(i) * x;
+        }
+        if !self.col_scale.is_null() {
+            x = x * *self.col_scale.add(j);
+        }
+        x
+    }
+
+    /// Scales the element of `rhs` at row `depth`.
+    #[inline(always)]
+    pub unsafe fn scale_depth(&self, depth: usize, x: T) -> T
+    where
+        T: core::ops::Mul<Output = T>,
+    {
+        if self.k_scale.is_null() {
+            x
+        } else {
+            *self.k_scale.add(depth) * x
+        }
+    }
+
+    /// Applies the operations to the element at row `i` and column `j` of the result.
+    #[inline(always)]
+    pub unsafe fn apply(&self, i: usize, j: usize, mut x: T) -> T
+    where
+        T: core::ops::Add<Output = T> + Activate,
+    {
+        if !self.row_bias.is_null() {
+            x = x + *self.row_bias.add(i);
+        }
+        if !self.col_bias.is_null() {
+            x = x + *self.col_bias.add(j);
+        }
+        x.activate(self.activation)
+    }
+}
+
+// same algorithm as the kernels, see `ActivationOps` in `nano-gemm-codegen`
+fn exp(x: f64) -> f64 {
+    const MAGIC: f64 = 4503599627371519.0;
+    const LN2_HI: f64 = 6.931471803691238e-1;
+    const LN2_LO: f64 = 1.9082149292705877e-10;
+
+    let x = x.clamp(-708.0, 709.0);
+    let t = x * core::f64::consts::LOG2_E + MAGIC;
+    let n = t - MAGIC;
+    let r = x - n * LN2_HI - n * LN2_LO;
+    let mut p = 1.0;
+    for k in (1..=12).rev() {
+        p = 1.0 + p * r / k as f64;
+    }
+    p * f64::from_bits(t.to_bits() << 52)
+}
+
+impl Activate for f64 {
+    #[inline]
+    fn activate(self, activation: Activation<Self>) -> Self {
+        let x = self;
+        match activation {
+            Activation::None => x,
+            Activation::Relu => {
+                if x < 0.0 {
+                    0.0
+                } else {
+                    x
+                }
+            }
+            Activation::LeakyRelu(slope) => {
+                if x < 0.0 {
+                    slope * x
+                } else {
+                    x
+                }
+            }
+            Activation::Gelu => {
+                let c = -2.0 * core::f64::consts::FRAC_2_SQRT_PI * core::f64::consts::FRAC_1_SQRT_2;
+                x / (1.0 + exp(c * (x + 0.044715 * x * x * x)))
+            }
+            Activation::Sigmoid => 1.0 / (1.0 + exp(-x)),
+            Activation::Tanh => 1.0 - 2.0 / (1.0 + exp(x + x)),
+        }
+    }
+}
+
+impl Activate for f32 {
+    #[inline]
+    fn activate(self, activation: Activation<Self>) -> Self {
+        let activation = match activation {
+            Activation::None => Activation::None,
+            Activation::Relu => Activation::Relu,
+            Activation::LeakyRelu(slope) => Activation::LeakyRelu(slope as f64),
+            Activation::Gelu => Activation::Gelu,
+            Activation::Sigmoid => Activation::Sigmoid,
+            Activation::Tanh => Activation::Tanh,
+        };
+        (self as f64).activate(activation) as f32
+    }
+}
+
+// the activations can only be set for real types
+impl Activate for c32 {
+    #[inline]
+    fn activate(self, _: Activation<Self>) -> Self {
+        self
+    }
+}
+
+impl Activate for c64 {
+    #[inline]
+    fn activate(self, _: Activation<Self>) -> Self {
+        self
+    }
+}
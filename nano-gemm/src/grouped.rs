@@ -0,0 +1,143 @@
+use crate::{GemmScalar, Plan};
+
+/// Description of one of the problems executed by a [`GroupedGemm`].
+///
+/// The fields have the same meaning as the arguments of [`Plan::execute_unchecked`].
+#[derive(Copy, Clone, Debug)]
+pub struct GemmProblem<T> {
+    pub m: usize,
+    pub n: usize,
+    pub k: usize,
+    pub dst: *mut T,
+    pub dst_rs: isize,
+    pub dst_cs: isize,
+    pub lhs: *const T,
+    pub lhs_rs: isize,
+    pub lhs_cs: isize,
+    pub rhs: *const T,
+    pub rhs_rs: isize,
+    pub rhs_cs: isize,
+    pub alpha: T,
+    pub beta: T,
+    pub conj_lhs: bool,
+    pub conj_rhs: bool,
+}
+
+/// Shape and layout a plan of a [`GroupedGemm`] was created for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct PlanKey {
+    m: usize,
+    n: usize,
+    k: usize,
+    is_col_major: bool,
+}
+
+/// Executes groups of matrix multiplications with different shapes, reusing one plan per distinct
+/// shape and layout across calls.
+#[derive(Clone)]
+pub struct GroupedGemm<T> {
+    // sorted by key
+    plans: Vec<(PlanKey, Plan<T>)>,
+    order: Vec<usize>,
+    sort_by_shape: bool,
+}
+
+impl<T: GemmScalar> Default for GroupedGemm<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: GemmScalar> GroupedGemm<T> {
+    /// Creates an empty group, executing the problems in the order they're given.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            plans: Vec::new(),
+            order: Vec::new(),
+            sort_by_shape: false,
+        }
+    }
+
+    /// Sets whether the problems are executed in order of their shape rather than in the order
+    /// they're given, so that consecutive problems share the same kernels.
+    #[inline]
+    pub fn sort_by_shape(mut self, sort_by_shape: bool) -> Self {
+        self.sort_by_shape = sort_by_shape;
+        self
+    }
+
+    /// Returns the number of plans created so far.
+    #[inline]
+    pub fn plan_count(&self) -> usize {
+        self.plans.len()
+    }
+
+    /// Returns the index of the plan for the given problem, creating it if needed.
+    #[track_caller]
+    fn plan_index(&mut self, problem: &GemmProblem<T>) -> usize {
+        let key = PlanKey {
+            m: problem.m,
+            n: problem.n,
+            k: problem.k,
+            is_col_major: problem.dst_rs == 1 && problem.lhs_rs == 1,
+        };
+        match self.plans.binary_search_by_key(&key, |&(key, _)| key) {
+            Ok(idx) => idx,
+            Err(idx) => {
+                let plan = if key.is_col_major {
+                    Plan::new_colmajor_lhs_and_dst(problem.m, problem.n, problem.k)
+                } else {
+                    Plan::new(problem.m, problem.n, problem.k)
+                };
+                self.plans.insert(idx, (key, plan));
+                idx
+            }
+        }
+    }
+
+    /// Executes all the problems.
+    ///
+    /// # Safety
+    /// The conditions of [`Plan::execute_unchecked`] must hold for each problem, and the `dst` of a
+    /// problem must not overlap with the operands of the other problems.
+    #[track_caller]
+    pub unsafe fn execute(&mut self, problems: &[GemmProblem<T>]) {
+        let mut order = core::mem::take(&mut self.order);
+        order.clear();
+        order.extend(0..problems.len());
+        if self.sort_by_shape {
+            order.sort_unstable_by_key(|&idx| {
+                let problem = &problems[idx];
+                (problem.m, problem.n, problem.k)
+            });
+        }
+
+        for &idx in &order {
+            let problem = &problems[idx];
+            let plan = self.plan_index(problem);
+            let plan = &self.plans[plan].1;
+            plan.execute_unchecked(
+                problem.m,
+                problem.n,
+                problem.k,
+                problem.dst,
+                problem.dst_rs,
+                problem.dst_cs,
+                problem.lhs,
+                problem.lhs_rs,
+                problem.lhs_cs,
+                problem.rhs,
+                problem.rhs_rs,
+                problem.rhs_cs,
+                problem.alpha,
+                problem.beta,
+                problem.conj_lhs,
+                problem.conj_rhs,
+            );
+        }
+
+        self.order = order;
+    }
+}
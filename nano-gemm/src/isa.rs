@@ -0,0 +1,186 @@
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Instruction set used by the kernels of a plan.
+///
+/// On x86, plans with few rows use narrower vector registers than the full width of the
+/// instruction set, while still requiring it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Isa {
+    /// Portable kernels, without SIMD.
+    Scalar,
+    /// AVX2 and FMA kernels.
+    Avx2,
+    /// AVX-512 kernels. Requires the `nightly` feature.
+    Avx512,
+    /// NEON kernels. Complex kernels additionally require the FCMA extension.
+    Neon,
+}
+
+/// Name of the environment variable capping the instruction set selected by default. Only read
+/// when the `std` feature is enabled.
+///
+/// Accepts `scalar`, `avx2`, `avx512` or `neon`, case-insensitively. Other values are ignored.
+pub const MAX_ISA_ENV_VAR: &str = "NANO_GEMM_MAX_ISA";
+
+const INIT: u8 = 1 << 0;
+const AVX2: u8 = 1 << 1;
+const AVX512: u8 = 1 << 2;
+const NEON: u8 = 1 << 3;
+const FCMA: u8 = 1 << 4;
+
+/// Features enabled at compile time, e.g. with `-C target-feature=+avx2,+fma`. These are the only
+/// ones used when the `std` feature is disabled, since they can't be detected at runtime.
+const STATIC_FEATURES: u8 =
+    INIT | if cfg!(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "avx2",
+        target_feature = "fma",
+    )) {
+        AVX2
+    } else {
+        0
+    } | if cfg!(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        feature = "nightly",
+        target_feature = "avx512f",
+    )) {
+        AVX512
+    } else {
+        0
+    } | if cfg!(all(target_arch = "aarch64", target_feature = "neon")) {
+        NEON
+    } else {
+        0
+    } | if cfg!(all(
+        target_arch = "aarch64",
+        target_feature = "neon",
+        target_feature = "fcma",
+    )) {
+        FCMA
+    } else {
+        0
+    };
+
+#[cfg(feature = "std")]
+static FEATURES: AtomicU8 = AtomicU8::new(0);
+
+#[cfg(feature = "std")]
+#[cold]
+fn detect() -> u8 {
+    let mut features = STATIC_FEATURES;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if std::is_x86_feature_detected!("avx2") && std::is_x86_feature_detected!("fma") {
+            features |= AVX2;
+        }
+        #[cfg(feature = "nightly")]
+        if std::is_x86_feature_detected!("avx512f") {
+            features |= AVX512;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            features |= NEON;
+            if std::arch::is_aarch64_feature_detected!("fcma") {
+                features |= FCMA;
+            }
+        }
+    }
+
+    if let Some(cap) = std::env::var(MAX_ISA_ENV_VAR)
+        .ok()
+        .and_then(|name| Isa::from_name(&name))
+    {
+        features &= match cap {
+            Isa::Scalar => INIT,
+            Isa::Avx2 => INIT | AVX2,
+            Isa::Avx512 => INIT | AVX2 | AVX512,
+            Isa::Neon => INIT | NEON | FCMA,
+        };
+    }
+
+    features
+}
+
+/// Returns the features enabled at compile time.
+#[cfg(not(feature = "std"))]
+#[inline]
+fn features() -> u8 {
+    STATIC_FEATURES
+}
+
+/// Returns the detected CPU features, after applying the cap from [`MAX_ISA_ENV_VAR`].
+#[cfg(feature = "std")]
+#[inline]
+fn features() -> u8 {
+    let features = FEATURES.load(Ordering::Relaxed);
+    if features != 0 {
+        features
+    } else {
+        let features = detect();
+        FEATURES.store(features, Ordering::Relaxed);
+        features
+    }
+}
+
+impl Isa {
+    #[cfg(feature = "std")]
+    fn from_name(name: &str) -> Option<Self> {
+        [Isa::Scalar, Isa::Avx2, Isa::Avx512, Isa::Neon]
+            .into_iter()
+            .find(|isa| isa.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Returns the lowercase name of the instruction set.
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Isa::Scalar => "scalar",
+            Isa::Avx2 => "avx2",
+            Isa::Avx512 => "avx512",
+            Isa::Neon => "neon",
+        }
+    }
+
+    /// Returns `true` if the instruction set is supported by the current CPU, was enabled at
+    /// compile time, and isn't excluded by [`MAX_ISA_ENV_VAR`].
+    ///
+    /// The result is detected once, then cached for the lifetime of the process. Without the `std`
+    /// feature, only the target features enabled at compile time are considered.
+    #[inline]
+    pub fn is_available(self) -> bool {
+        let features = features();
+        match self {
+            Isa::Scalar => true,
+            Isa::Avx2 => features & AVX2 != 0,
+            Isa::Avx512 => features & AVX512 != 0,
+            Isa::Neon => features & NEON != 0,
+        }
+    }
+
+    /// Returns the most capable available instruction set.
+    #[inline]
+    pub fn best() -> Self {
+        [Isa::Avx512, Isa::Avx2, Isa::Neon]
+            .into_iter()
+            .find(|isa| isa.is_available())
+            .unwrap_or(Isa::Scalar)
+    }
+
+    /// Returns `true` if the FCMA extension, required by the complex NEON kernels, is available.
+    #[cfg(target_arch = "aarch64")]
+    #[inline]
+    pub(crate) fn has_fcma() -> bool {
+        features() & FCMA != 0
+    }
+}
+
+impl core::fmt::Display for Isa {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -6,6 +6,32 @@
 
 use core::mem::MaybeUninit;
 use equator::debug_assert;
+use fused::FusedPtrs;
+
+mod builder;
+#[cfg(feature = "std")]
+mod cache;
+mod error;
+mod fused;
+#[cfg(feature = "std")]
+mod grouped;
+mod isa;
+#[cfg(feature = "std")]
+pub mod linalg;
+mod mat;
+mod syrk;
+mod trsm;
+pub use builder::PlanBuilder;
+#[cfg(feature = "std")]
+pub use cache::PlanCache;
+pub use error::{FactorError, PlanError};
+pub use fused::FusedOps;
+#[cfg(feature = "std")]
+pub use grouped::{GemmProblem, GroupedGemm};
+pub use isa::{Isa, MAX_ISA_ENV_VAR};
+pub use mat::{MatMut, MatRef};
+pub use syrk::SyrkPlan;
+pub use trsm::{Side, TrsmPlan};
 
 #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
 pub mod x86 {
@@ -29,6 +55,137 @@ pub type c64 = num_complex::Complex64;
 
 pub use nano_gemm_core::*;
 
+/// Describes how the product `lhs * rhs` is combined with the previous contents of `dst`.
+///
+/// Unlike the `alpha`/`beta` arguments of [`Plan::execute_unchecked`], the semantics don't depend
+/// on the value of the scaling factors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Accumulate<T> {
+    /// `dst = lhs * rhs`.
+    ///
+    /// `dst` is never read, so NaN and infinite values it contains are discarded.
+    Overwrite,
+    /// `dst = dst + lhs * rhs`.
+    Add,
+    /// `dst = dst_scale * dst + prod_scale * lhs * rhs`.
+    ///
+    /// `dst` is always read, so NaN and infinite values it contains propagate to the result, even
+    /// if `dst_scale` is zero.
+    Scale { dst_scale: T, prod_scale: T },
+}
+
+impl<T: GemmScalar> Accumulate<T> {
+    /// Returns `(alpha, beta, read_dst)` as expected by the millikernels.
+    #[inline]
+    fn to_alpha_beta(self) -> (T, T, bool) {
+        match self {
+            Accumulate::Overwrite => (unsafe { core::mem::zeroed() }, T::ONE, false),
+            Accumulate::Add => (T::ONE, T::ONE, true),
+            Accumulate::Scale {
+                dst_scale,
+                prod_scale,
+            } => (dst_scale, prod_scale, true),
+        }
+    }
+}
+
+/// Triangular part of a square matrix, including the diagonal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Triangle {
+    /// Elements on or below the diagonal.
+    Lower,
+    /// Elements on or above the diagonal.
+    Upper,
+}
+
+impl Triangle {
+    /// Returns the triangle that the elements of this one are mapped to by a transposition.
+    #[inline]
+    fn transpose(self) -> Self {
+        match self {
+            Triangle::Lower => Triangle::Upper,
+            Triangle::Upper => Triangle::Lower,
+        }
+    }
+
+    /// Returns `true` if the element at row `i` and column `j` lies in the triangle.
+    #[inline(always)]
+    fn contains(self, i: usize, j: usize) -> bool {
+        match self {
+            Triangle::Lower => i >= j,
+            Triangle::Upper => i <= j,
+        }
+    }
+}
+
+/// Structure of a square operand of a plan, of which only one triangle is read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Structure {
+    /// Symmetric matrix, where the other triangle is the transpose of the stored one.
+    Symmetric(Triangle),
+    /// Hermitian matrix, where the other triangle is the adjoint of the stored one. The imaginary
+    /// parts of the diagonal are assumed to be zero.
+    Hermitian(Triangle),
+    /// Triangular matrix, where the other triangle is zero.
+    Triangular(Triangle),
+    /// Triangular matrix with an implicit unit diagonal, where the other triangle is zero. The
+    /// stored diagonal isn't read.
+    UnitTriangular(Triangle),
+}
+
+impl Structure {
+    /// Returns the structure of the transpose of the matrix.
+    #[inline]
+    fn transpose(self) -> Self {
+        match self {
+            Structure::Symmetric(triangle) => Structure::Symmetric(triangle.transpose()),
+            Structure::Hermitian(triangle) => Structure::Hermitian(triangle.transpose()),
+            Structure::Triangular(triangle) => Structure::Triangular(triangle.transpose()),
+            Structure::UnitTriangular(triangle) => Structure::UnitTriangular(triangle.transpose()),
+        }
+    }
+
+    /// Returns the triangle holding the nonzero elements, if the other one is zero.
+    #[inline]
+    fn nonzero_triangle(self) -> Option<Triangle> {
+        match self {
+            Structure::Symmetric(_) | Structure::Hermitian(_) => None,
+            Structure::Triangular(triangle) | Structure::UnitTriangular(triangle) => Some(triangle),
+        }
+    }
+}
+
+/// Operation applied to a matrix operand of a plan.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// The matrix is used as is.
+    #[default]
+    NoTrans,
+    /// The matrix is transposed.
+    Trans,
+    /// The matrix is transposed and conjugated.
+    ConjTrans,
+}
+
+impl Op {
+    /// Returns the dimensions of `op(mat)`, given the dimensions of `mat`.
+    #[inline]
+    fn dims(self, nrows: usize, ncols: usize) -> (usize, usize) {
+        if self == Op::NoTrans {
+            (nrows, ncols)
+        } else {
+            (ncols, nrows)
+        }
+    }
+}
+
+/// Plan for multiplying matrices of given dimensions, that can be executed any number of times.
+///
+/// On x86 with AVX2 or AVX-512, `f32` and `f64` plans with `n == 1` compute matrix-vector products
+/// with dedicated kernels, when `lhs` has a unit row or column stride and no fused operations are
+/// requested. Dot products are the plans with `m == 1` and `n == 1`, and don't have an entry point
+/// of their own. Other plans, including the ones for complex types or for other targets, always
+/// go through the general kernels.
 #[derive(Copy, Clone)]
 pub struct Plan<T> {
     microkernels: [[MaybeUninit<MicroKernel<T>>; 2]; 2],
@@ -50,10 +207,17 @@ pub struct Plan<T> {
         rhs_cs: isize,
         alpha: T,
         beta: T,
+        read_dst: bool,
         conj_lhs: bool,
         conj_rhs: bool,
         full_mask: *const (),
         last_mask: *const (),
+        workspace: *mut T,
+        batch: usize,
+        dst_bs: isize,
+        lhs_bs: isize,
+        rhs_bs: isize,
+        fused: &FusedPtrs<T>,
     ),
     mr: usize,
     nr: usize,
@@ -68,6 +232,44 @@ pub struct Plan<T> {
     lhs_rs: isize,
     rhs_cs: isize,
     rhs_rs: isize,
+    lhs_op: Op,
+    rhs_op: Op,
+    dst_op: Op,
+    transpose: bool,
+    isa: Isa,
+    workspace_size: usize,
+    gemv: Option<GemvKernels<T>>,
+    // only this part of the stored `dst` is computed, if set
+    triangle: Option<Triangle>,
+    // structure of the stored `lhs` and `rhs`. at most one of them is set, and it's always the
+    // `lhs` of the kernels once `transpose` is applied
+    lhs_structure: Option<Structure>,
+    rhs_structure: Option<Structure>,
+}
+
+/// Matrix-vector kernels, used in place of the millikernel when `n == 1` and the layout allows it.
+/// They're only generated for x86, and other targets always go through the millikernel.
+#[derive(Copy, Clone)]
+struct GemvKernels<T> {
+    // `lhs` has a unit row stride, `dst` is contiguous
+    colmajor: GemvKernel<T>,
+    // `lhs` has a unit column stride, `rhs` is contiguous
+    rowmajor: GemvKernel<T>,
+}
+
+// the masks point to static data, and the kernels don't hold any state
+unsafe impl<T: Sync> Sync for Plan<T> {}
+unsafe impl<T: Send> Send for Plan<T> {}
+
+/// Hints the CPU to fetch the cache line containing `ptr`. The pointer doesn't need to be valid.
+#[inline(always)]
+#[allow(unused_variables)]
+fn prefetch<T>(ptr: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr as *const i8);
+    }
 }
 
 #[allow(unused_variables)]
@@ -89,16 +291,23 @@ unsafe fn noop_millikernel<T: Copy>(
     rhs_cs: isize,
     alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
 ) {
 }
 
 #[allow(unused_variables)]
 unsafe fn naive_millikernel<
-    T: Copy + core::ops::Mul<Output = T> + core::ops::Add<Output = T> + PartialEq + Conj,
+    T: Copy + core::ops::Mul<Output = T> + core::ops::Add<Output = T> + PartialEq + Conj + Activate,
 >(
     microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
     mr: usize,
@@ -117,46 +326,66 @@ unsafe fn naive_millikernel<
     rhs_cs: isize,
     alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
 ) {
     let zero: T = core::mem::zeroed();
-    if alpha == zero {
-        for j in 0..n {
-            for i in 0..m {
-                let mut acc = zero;
-                for depth in 0..k {
-                    let lhs = *lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
-                    let rhs = *rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
-                    acc = acc
-                        + if conj_lhs { lhs.conj() } else { lhs }
-                            * if conj_rhs { rhs.conj() } else { rhs };
+    for b in 0..batch {
+        let dst = dst.offset(b as isize * dst_bs);
+        let lhs = lhs.offset(b as isize * lhs_bs);
+        let rhs = rhs.offset(b as isize * rhs_bs);
+
+        if alpha == zero && !read_dst {
+            for j in 0..n {
+                for i in 0..m {
+                    let mut acc = zero;
+                    for depth in 0..k {
+                        let lhs = *lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
+                        let rhs = *rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
+                        acc = acc
+                            + if conj_lhs { lhs.conj() } else { lhs }
+                                * fused.scale_depth(depth, if conj_rhs { rhs.conj() } else { rhs });
+                    }
+                    *dst.offset(dst_rs * i as isize + dst_cs * j as isize) =
+                        fused.apply(i, j, beta * fused.scale(i, j, acc));
                 }
-                *dst.offset(dst_rs * i as isize + dst_cs * j as isize) = beta * acc;
             }
-        }
-    } else {
-        for j in 0..n {
-            for i in 0..m {
-                let mut acc = zero;
-                for depth in 0..k {
-                    let lhs = *lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
-                    let rhs = *rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
-                    acc = acc
-                        + if conj_lhs { lhs.conj() } else { lhs }
-                            * if conj_rhs { rhs.conj() } else { rhs };
+        } else {
+            for j in 0..n {
+                for i in 0..m {
+                    let mut acc = zero;
+                    for depth in 0..k {
+                        let lhs = *lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
+                        let rhs = *rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
+                        acc = acc
+                            + if conj_lhs { lhs.conj() } else { lhs }
+                                * fused.scale_depth(depth, if conj_rhs { rhs.conj() } else { rhs });
+                    }
+                    let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
+                    *dst = fused.apply(
+                        i,
+                        j,
+                        alpha * fused.read_src(i, j, dst) + beta * fused.scale(i, j, acc),
+                    );
                 }
-                let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
-                *dst = alpha * *dst + beta * acc;
             }
         }
     }
 }
 
 #[allow(unused_variables)]
-unsafe fn fill_millikernel<T: Copy + PartialEq + core::ops::Mul<Output = T>>(
+unsafe fn fill_millikernel<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Activate,
+>(
     microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
     mr: usize,
     nr: usize,
@@ -174,31 +403,46 @@ unsafe fn fill_millikernel<T: Copy + PartialEq + core::ops::Mul<Output = T>>(
     rhs_cs: isize,
     alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
 ) {
     let zero: T = core::mem::zeroed();
-    if alpha == zero {
-        for j in 0..n {
-            for i in 0..m {
-                *dst.offset(dst_rs * i as isize + dst_cs * j as isize) = core::mem::zeroed();
+    for b in 0..batch {
+        let dst = dst.offset(b as isize * dst_bs);
+        let lhs = lhs.offset(b as isize * lhs_bs);
+        let rhs = rhs.offset(b as isize * rhs_bs);
+
+        if alpha == zero && !read_dst {
+            for j in 0..n {
+                for i in 0..m {
+                    *dst.offset(dst_rs * i as isize + dst_cs * j as isize) =
+                        fused.apply(i, j, core::mem::zeroed());
+                }
             }
-        }
-    } else {
-        for j in 0..n {
-            for i in 0..m {
-                let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
-                *dst = alpha * *dst;
+        } else {
+            for j in 0..n {
+                for i in 0..m {
+                    let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
+                    *dst = fused.apply(i, j, alpha * fused.read_src(i, j, dst));
+                }
             }
         }
     }
 }
 
+#[allow(unused_variables)]
 #[inline(always)]
 unsafe fn small_direct_millikernel<
-    T: Copy,
+    T: Copy + PartialEq,
     const M_DIVCEIL_MR: usize,
     const N_DIVCEIL_NR: usize,
 >(
@@ -219,17 +463,24 @@ unsafe fn small_direct_millikernel<
     rhs_cs: isize,
     alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
 ) {
-    _ = (m, n);
     debug_assert!(all(lhs_rs == 1, dst_rs == 1));
 
     let mut data = MicroKernelData {
         alpha,
         beta,
+        read_dst,
         conj_lhs,
         conj_rhs,
         k,
@@ -238,45 +489,80 @@ unsafe fn small_direct_millikernel<
         rhs_rs,
         rhs_cs,
         last_mask,
+        src: core::ptr::null(),
+        src_cs: fused.src_cs,
+        row_scale: core::ptr::null(),
+        col_scale: core::ptr::null(),
+        k_scale: fused.k_scale,
+        row_bias: core::ptr::null(),
+        col_bias: core::ptr::null(),
+        activation: fused.activation,
     };
+    // the kernels only read `src` in place of `dst`
+    let read_src = alpha != core::mem::zeroed() || read_dst;
+
+    for b in 0..batch {
+        let dst = dst.offset(b as isize * dst_bs);
+        let lhs = lhs.offset(b as isize * lhs_bs);
+        let rhs = rhs.offset(b as isize * rhs_bs);
+        if b + 1 < batch {
+            prefetch(dst.wrapping_offset(dst_bs));
+            prefetch(lhs.wrapping_offset(lhs_bs));
+            prefetch(rhs.wrapping_offset(rhs_bs));
+        }
 
-    let mut i = 0usize;
-    while i < M_DIVCEIL_MR {
-        data.last_mask = if i + 1 < M_DIVCEIL_MR {
-            full_mask
-        } else {
-            last_mask
-        };
+        let mut i = 0usize;
+        while i < M_DIVCEIL_MR {
+            data.last_mask = if i + 1 < M_DIVCEIL_MR {
+                full_mask
+            } else {
+                last_mask
+            };
 
-        let microkernels = microkernels.get_unchecked((i + 1 >= M_DIVCEIL_MR) as usize);
-        {
-            let i = i * mr;
-            let dst = dst.offset(i as isize);
+            let microkernels = microkernels.get_unchecked((i + 1 >= M_DIVCEIL_MR) as usize);
+            {
+                let i = i * mr;
+                let dst = dst.offset(i as isize);
 
-            let mut j = 0usize;
-            while j < N_DIVCEIL_NR {
-                let microkernel = microkernels
-                    .get_unchecked((j + 1 >= N_DIVCEIL_NR) as usize)
-                    .assume_init();
+                let mut j = 0usize;
+                while j < N_DIVCEIL_NR {
+                    let microkernel = microkernels
+                        .get_unchecked((j + 1 >= N_DIVCEIL_NR) as usize)
+                        .assume_init();
+
+                    {
+                        let j = j * nr;
+                        let tile = fused.block(i, j);
+                        let dst = dst.offset(j as isize * dst_cs);
+                        data.src = tile.src_block(
+                            Ord::min(mr, m - i),
+                            Ord::min(nr, n - j),
+                            dst,
+                            dst_cs,
+                            read_src,
+                        );
+                        data.row_scale = tile.row_scale;
+                        data.col_scale = tile.col_scale;
+                        data.row_bias = tile.row_bias;
+                        data.col_bias = tile.col_bias;
+                        microkernel(
+                            &data,
+                            dst,
+                            lhs.offset(i as isize),
+                            rhs.offset(j as isize * rhs_cs),
+                        );
+                    }
 
-                {
-                    let j = j * nr;
-                    microkernel(
-                        &data,
-                        dst.offset(j as isize * dst_cs),
-                        lhs.offset(i as isize),
-                        rhs.offset(j as isize * rhs_cs),
-                    );
+                    j += 1;
                 }
-
-                j += 1;
             }
+            i += 1;
         }
-        i += 1;
     }
 }
 
-unsafe fn direct_millikernel<T: Copy>(
+#[allow(unused_variables)]
+unsafe fn direct_millikernel<T: Copy + PartialEq>(
     microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
     mr: usize,
     nr: usize,
@@ -294,16 +580,24 @@ unsafe fn direct_millikernel<T: Copy>(
     rhs_cs: isize,
     alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
 ) {
     debug_assert!(all(lhs_rs == 1, dst_rs == 1));
 
     let mut data = MicroKernelData {
         alpha,
         beta,
+        read_dst,
         conj_lhs,
         conj_rhs,
         k,
@@ -312,82 +606,108 @@ unsafe fn direct_millikernel<T: Copy>(
         rhs_rs,
         rhs_cs,
         last_mask,
+        src: core::ptr::null(),
+        src_cs: fused.src_cs,
+        row_scale: core::ptr::null(),
+        col_scale: core::ptr::null(),
+        k_scale: fused.k_scale,
+        row_bias: core::ptr::null(),
+        col_bias: core::ptr::null(),
+        activation: fused.activation,
     };
+    // the kernels only read `src` in place of `dst`
+    let read_src = alpha != core::mem::zeroed() || read_dst;
+
+    for b in 0..batch {
+        let dst = dst.offset(b as isize * dst_bs);
+        let lhs = lhs.offset(b as isize * lhs_bs);
+        let rhs = rhs.offset(b as isize * rhs_bs);
+        if b + 1 < batch {
+            prefetch(dst.wrapping_offset(dst_bs));
+            prefetch(lhs.wrapping_offset(lhs_bs));
+            prefetch(rhs.wrapping_offset(rhs_bs));
+        }
 
-    let mut i = 0usize;
-    while i < m {
-        data.last_mask = if i + mr <= m { full_mask } else { last_mask };
-        let microkernels = microkernels.get_unchecked((i + mr > m) as usize);
-        let dst = dst.offset(i as isize);
+        let mut i = 0usize;
+        while i < m {
+            data.last_mask = if i + mr <= m { full_mask } else { last_mask };
+            let microkernels = microkernels.get_unchecked((i + mr > m) as usize);
+            let dst = dst.offset(i as isize);
 
-        let mut j = 0usize;
-        while j < n {
-            let microkernel = microkernels
-                .get_unchecked((j + nr > n) as usize)
-                .assume_init();
+            let mut j = 0usize;
+            while j < n {
+                let microkernel = microkernels
+                    .get_unchecked((j + nr > n) as usize)
+                    .assume_init();
 
-            microkernel(
-                &data,
-                dst.offset(j as isize * dst_cs),
-                lhs.offset(i as isize),
-                rhs.offset(j as isize * rhs_cs),
-            );
+                let tile = fused.block(i, j);
+                let dst = dst.offset(j as isize * dst_cs);
+                data.src = tile.src_block(
+                    Ord::min(mr, m - i),
+                    Ord::min(nr, n - j),
+                    dst,
+                    dst_cs,
+                    read_src,
+                );
+                data.row_scale = tile.row_scale;
+                data.col_scale = tile.col_scale;
+                data.row_bias = tile.row_bias;
+                data.col_bias = tile.col_bias;
+                microkernel(
+                    &data,
+                    dst,
+                    lhs.offset(i as isize),
+                    rhs.offset(j as isize * rhs_cs),
+                );
 
-            j += nr;
-        }
+                j += nr;
+            }
 
-        i += mr;
+            i += mr;
+        }
     }
 }
 
-trait One {
-    const ONE: Self;
-}
-trait Conj {
-    fn conj(self) -> Self;
-}
-
-impl One for f32 {
-    const ONE: Self = 1.0;
-}
-impl One for f64 {
-    const ONE: Self = 1.0;
-}
-impl One for c32 {
-    const ONE: Self = Self { re: 1.0, im: 0.0 };
-}
-impl One for c64 {
-    const ONE: Self = Self { re: 1.0, im: 0.0 };
-}
-
-impl Conj for f32 {
-    #[inline]
-    fn conj(self) -> Self {
-        self
-    }
-}
-impl Conj for f64 {
-    #[inline]
-    fn conj(self) -> Self {
-        self
-    }
-}
+// large enough for the tiles of every microkernel table
+const TILE_SIZE: usize = 128;
 
-impl Conj for c32 {
-    #[inline]
-    fn conj(self) -> Self {
-        Self::conj(&self)
-    }
-}
-impl Conj for c64 {
-    #[inline]
-    fn conj(self) -> Self {
-        Self::conj(&self)
+/// Stores the part of the `m × n` block `tmp` that lies in `triangle` to `dst`, where `(i, j)` is
+/// the position of the block, as `dst = alpha * dst + tmp`.
+#[inline(always)]
+unsafe fn store_triangle<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T>,
+>(
+    triangle: Triangle,
+    i: usize,
+    j: usize,
+    m: usize,
+    n: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    tmp: *const T,
+    tmp_cs: isize,
+    alpha: T,
+    read_dst: bool,
+) {
+    let overwrite = alpha == core::mem::zeroed() && !read_dst;
+    for jj in 0..n {
+        for ii in 0..m {
+            if triangle.contains(i + ii, j + jj) {
+                let dst = dst.offset(ii as isize * dst_rs + jj as isize * dst_cs);
+                let tmp = *tmp.offset(ii as isize + jj as isize * tmp_cs);
+                *dst = if overwrite { tmp } else { alpha * *dst + tmp };
+            }
+        }
     }
 }
 
-unsafe fn copy_millikernel<
-    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
+/// Same as `direct_millikernel`, but only computes the elements of `dst` in `triangle`.
+///
+/// Tiles outside of the triangle are skipped, and tiles crossing the diagonal are computed in a
+/// temporary buffer, so that the elements outside of the triangle are left untouched.
+unsafe fn direct_triangular_millikernel<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T>,
 >(
     microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
     mr: usize,
@@ -396,433 +716,763 @@ unsafe fn copy_millikernel<
     n: usize,
     k: usize,
     dst: *mut T,
-    dst_rs: isize,
     dst_cs: isize,
     lhs: *const T,
-    lhs_rs: isize,
     lhs_cs: isize,
     rhs: *const T,
     rhs_rs: isize,
     rhs_cs: isize,
-    mut alpha: T,
+    alpha: T,
     beta: T,
+    read_dst: bool,
     conj_lhs: bool,
     conj_rhs: bool,
     full_mask: *const (),
     last_mask: *const (),
+    triangle: Triangle,
 ) {
-    if dst_rs == 1 && lhs_rs == 1 {
-        let gemm_dst = dst;
-        let gemm_lhs = lhs;
-        let gemm_dst_cs = dst_cs;
-        let gemm_lhs_cs = lhs_cs;
-
-        direct_millikernel(
-            microkernels,
-            mr,
-            nr,
-            m,
-            n,
-            k,
-            gemm_dst,
-            1,
-            gemm_dst_cs,
-            gemm_lhs,
-            1,
-            gemm_lhs_cs,
-            rhs,
-            rhs_rs,
-            rhs_cs,
-            alpha,
-            beta,
-            conj_lhs,
-            conj_rhs,
-            full_mask,
-            last_mask,
-        );
-    } else {
-        // 32 is always a multiple of both MR and NR
-        const M_BS: usize = 64;
-        const N_BS: usize = 64;
-        const K_BS: usize = 64;
-        let mut dst_tmp: MaybeUninit<[T; M_BS * N_BS]> = core::mem::MaybeUninit::uninit();
-        let mut lhs_tmp: MaybeUninit<[T; M_BS * K_BS]> = core::mem::MaybeUninit::uninit();
-
-        let dst_tmp = &mut *((&mut dst_tmp) as *mut _ as *mut [[MaybeUninit<T>; M_BS]; N_BS]);
-        let lhs_tmp = &mut *((&mut lhs_tmp) as *mut _ as *mut [[MaybeUninit<T>; M_BS]; K_BS]);
-
-        let gemm_dst = if dst_rs == 1 {
-            dst
-        } else {
-            dst_tmp.as_mut_ptr() as *mut T
-        };
-        let gemm_lhs = lhs_tmp.as_mut_ptr() as *mut T;
-        let gemm_dst_cs = if dst_rs == 1 { dst_cs } else { M_BS as isize };
-        let gemm_lhs_cs = M_BS as isize;
-
-        let mut depth = 0usize;
-        while depth < k {
-            let depth_bs = Ord::min(K_BS, k - depth);
-
-            let mut i = 0usize;
-            while i < m {
-                let i_bs = Ord::min(M_BS, m - i);
-
-                let lhs = lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
-
-                for ii in 0..i_bs {
-                    for jj in 0..depth_bs {
-                        let ii = ii as isize;
-                        let jj = jj as isize;
-                        *(gemm_lhs.offset(ii + gemm_lhs_cs * jj)) =
-                            *(lhs.offset(lhs_rs * ii + lhs_cs * jj));
-                    }
-                }
-
-                let mut j = 0usize;
-                while j < n {
-                    let j_bs = Ord::min(N_BS, n - j);
+    debug_assert!(mr * nr <= TILE_SIZE);
 
-                    let rhs = rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
+    let mut data = MicroKernelData {
+        alpha,
+        beta,
+        read_dst,
+        conj_lhs,
+        conj_rhs,
+        k,
+        dst_cs,
+        lhs_cs,
+        rhs_rs,
+        rhs_cs,
+        last_mask,
+        src: core::ptr::null(),
+        src_cs: 0,
+        row_scale: core::ptr::null(),
+        col_scale: core::ptr::null(),
+        k_scale: core::ptr::null(),
+        row_bias: core::ptr::null(),
+        col_bias: core::ptr::null(),
+        activation: Activation::None,
+    };
+    let mut tmp: MaybeUninit<[T; TILE_SIZE]> = MaybeUninit::uninit();
+    let tmp = tmp.as_mut_ptr() as *mut T;
 
-                    let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
-                    let gemm_dst = gemm_dst.offset(i as isize + gemm_dst_cs * j as isize);
-
-                    direct_millikernel(
-                        microkernels,
-                        mr,
-                        nr,
-                        i_bs,
-                        j_bs,
-                        depth_bs,
-                        gemm_dst,
-                        1,
-                        gemm_dst_cs,
-                        gemm_lhs,
-                        1,
-                        gemm_lhs_cs,
-                        rhs,
-                        rhs_rs,
-                        rhs_cs,
-                        if dst_rs == 1 {
-                            alpha
-                        } else {
-                            core::mem::zeroed()
-                        },
-                        beta,
-                        conj_lhs,
-                        conj_rhs,
-                        full_mask,
-                        if i + i_bs == m { last_mask } else { full_mask },
-                    );
+    let mut i = 0usize;
+    while i < m {
+        let i_bs = Ord::min(mr, m - i);
+        data.last_mask = if i + mr <= m { full_mask } else { last_mask };
+        let microkernels = microkernels.get_unchecked((i + mr > m) as usize);
 
-                    if dst_rs != 1 {
-                        if alpha == core::mem::zeroed() {
-                            for ii in 0..i_bs {
-                                for jj in 0..j_bs {
-                                    let ii = ii as isize;
-                                    let jj = jj as isize;
-                                    *(dst.offset(dst_rs * ii + dst_cs * jj)) =
-                                        *(gemm_dst.offset(ii + gemm_dst_cs * jj));
-                                }
-                            }
-                        } else {
-                            for ii in 0..i_bs {
-                                for jj in 0..j_bs {
-                                    let ii = ii as isize;
-                                    let jj = jj as isize;
-                                    let dst = dst.offset(dst_rs * ii + dst_cs * jj);
-                                    *dst = alpha * *dst + *(gemm_dst.offset(ii + gemm_dst_cs * jj));
-                                }
-                            }
-                        }
-                    }
+        // the tiles must stay aligned to `nr`, so that only the last one uses the edge kernel
+        let mut j = match triangle {
+            Triangle::Lower => 0,
+            Triangle::Upper => i / nr * nr,
+        };
+        while j < n {
+            let j_bs = Ord::min(nr, n - j);
+            if triangle == Triangle::Lower && j >= i + i_bs {
+                break;
+            }
 
-                    j += j_bs;
-                }
+            let microkernel = microkernels
+                .get_unchecked((j + nr > n) as usize)
+                .assume_init();
+            let lhs = lhs.offset(i as isize);
+            let rhs = rhs.offset(j as isize * rhs_cs);
+            let dst = dst.offset(i as isize + j as isize * dst_cs);
 
-                i += i_bs;
+            let inside = match triangle {
+                Triangle::Lower => j + j_bs <= i + 1,
+                Triangle::Upper => i + i_bs <= j + 1,
+            };
+            if inside {
+                microkernel(&data, dst, lhs, rhs);
+            } else {
+                microkernel(
+                    &MicroKernelData {
+                        alpha: core::mem::zeroed(),
+                        read_dst: false,
+                        dst_cs: mr as isize,
+                        ..data
+                    },
+                    tmp,
+                    lhs,
+                    rhs,
+                );
+                store_triangle(
+                    triangle,
+                    i,
+                    j,
+                    i_bs,
+                    j_bs,
+                    dst,
+                    1,
+                    dst_cs,
+                    tmp,
+                    mr as isize,
+                    alpha,
+                    read_dst,
+                );
             }
 
-            alpha = T::ONE;
-            depth += depth_bs;
+            j += nr;
         }
+
+        i += mr;
     }
 }
 
-impl<T> Plan<T> {
-    #[allow(dead_code)]
-    #[inline(always)]
-    fn from_masked_impl<const MR_DIV_N: usize, const NR: usize, const N: usize, Mask>(
-        const_microkernels: &[[[MicroKernel<T>; NR]; MR_DIV_N]; 17],
-        const_masks: Option<&[Mask; N]>,
-        m: usize,
-        n: usize,
-        k: usize,
-        is_col_major: bool,
-    ) -> Self
-    where
-        T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
-    {
-        let mut microkernels = [[MaybeUninit::<MicroKernel<T>>::uninit(); 2]; 2];
-
-        let mr = MR_DIV_N * N;
-        let nr = NR;
+/// Returns the number of elements of the buffer holding the blocks of a triangular `dst` that
+/// cross the diagonal, in addition to the ones used by the millikernel.
+#[inline]
+fn triangular_workspace_size(n: usize) -> usize {
+    COPY_M_BS * Ord::min(n, COPY_N_BS)
+}
 
-        {
-            let k = Ord::min(k.wrapping_sub(1), 16);
-            let m = (m.wrapping_sub(1) / N) % (mr / N);
-            let n = n.wrapping_sub(1) % nr;
+/// Same as `triangular_diagonal_block`, with the block stored on the stack.
+///
+/// This is kept out of line so that the other blocks, and callers providing a workspace, don't
+/// pay for the stack space.
+#[inline(never)]
+unsafe fn triangular_diagonal_block_stack<T: GemmScalar>(
+    plan: &Plan<T>,
+    triangle: Triangle,
+    i: usize,
+    j: usize,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    last_mask: *const (),
+) {
+    let mut tmp: MaybeUninit<[T; COPY_M_BS * COPY_N_BS]> = MaybeUninit::uninit();
+    triangular_diagonal_block(
+        plan,
+        triangle,
+        i,
+        j,
+        m,
+        n,
+        k,
+        dst,
+        dst_rs,
+        dst_cs,
+        lhs,
+        lhs_rs,
+        lhs_cs,
+        rhs,
+        rhs_rs,
+        rhs_cs,
+        alpha,
+        beta,
+        read_dst,
+        conj_lhs,
+        conj_rhs,
+        last_mask,
+        tmp.as_mut_ptr() as *mut T,
+        core::ptr::null_mut(),
+    );
+}
 
-            microkernels[0][0].write(const_microkernels[k][MR_DIV_N - 1][NR - 1]);
-            microkernels[0][1].write(const_microkernels[k][MR_DIV_N - 1][n]);
-            microkernels[1][0].write(const_microkernels[k][m][NR - 1]);
-            microkernels[1][1].write(const_microkernels[k][m][n]);
-        }
+/// Computes the elements of the block of `dst` at `(i, j)` that lie in `triangle`, using the
+/// millikernel of `plan`.
+///
+/// `tmp` must have room for `triangular_workspace_size(n)` elements.
+unsafe fn triangular_diagonal_block<T: GemmScalar>(
+    plan: &Plan<T>,
+    triangle: Triangle,
+    i: usize,
+    j: usize,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    last_mask: *const (),
+    tmp: *mut T,
+    workspace: *mut T,
+) {
+    (plan.millikernel)(
+        &plan.microkernels,
+        plan.mr,
+        plan.nr,
+        m,
+        n,
+        k,
+        tmp,
+        1,
+        COPY_M_BS as isize,
+        lhs,
+        lhs_rs,
+        lhs_cs,
+        rhs,
+        rhs_rs,
+        rhs_cs,
+        core::mem::zeroed(),
+        beta,
+        false,
+        conj_lhs,
+        conj_rhs,
+        plan.full_mask,
+        last_mask,
+        workspace,
+        1,
+        0,
+        0,
+        0,
+        &FusedPtrs::NONE,
+    );
+    store_triangle(
+        triangle,
+        i,
+        j,
+        m,
+        n,
+        dst,
+        dst_rs,
+        dst_cs,
+        tmp,
+        COPY_M_BS as isize,
+        alpha,
+        read_dst,
+    );
+}
 
-        Self {
-            microkernels,
-            millikernel: if m == 0 || n == 0 {
-                noop_millikernel
-            } else if k == 0 {
-                fill_millikernel
-            } else if is_col_major {
-                if m <= mr && n <= nr {
-                    small_direct_millikernel::<_, 1, 1>
-                } else if m <= mr && n <= 2 * nr {
-                    small_direct_millikernel::<_, 1, 2>
-                } else if m <= 2 * mr && n <= nr {
-                    small_direct_millikernel::<_, 2, 1>
-                } else if m <= 2 * mr && n <= 2 * nr {
-                    small_direct_millikernel::<_, 2, 2>
-                } else {
-                    direct_millikernel
-                }
-            } else {
-                copy_millikernel
-            },
-            mr,
-            nr,
+/// Computes the elements of `dst` that lie in `triangle`, using the microkernels of `plan` when
+/// the layout allows it, and its millikernel on blocks of `dst` otherwise.
+///
+/// If `workspace` isn't null, it must hold `triangular_workspace_size(n)` elements for the blocks
+/// crossing the diagonal, followed by the workspace of the millikernel.
+unsafe fn triangular_millikernel<T: GemmScalar>(
+    plan: &Plan<T>,
+    triangle: Triangle,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    workspace: *mut T,
+) {
+    if plan.mr != 0 && k != 0 && dst_rs == 1 && lhs_rs == 1 {
+        direct_triangular_millikernel(
+            &plan.microkernels,
+            plan.mr,
+            plan.nr,
             m,
             n,
             k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
-            full_mask: if let Some(const_masks) = const_masks {
-                (&const_masks[0]) as *const _ as *const ()
-            } else {
-                &()
-            },
-            last_mask: if let Some(const_masks) = const_masks {
-                (&const_masks[m % N]) as *const _ as *const ()
-            } else {
-                &()
-            },
-        }
+            dst,
+            dst_cs,
+            lhs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            read_dst,
+            conj_lhs,
+            conj_rhs,
+            plan.full_mask,
+            plan.last_mask,
+            triangle,
+        );
+        return;
     }
 
-    #[allow(dead_code)]
-    #[inline(always)]
-    fn from_non_masked_impl<const MR: usize, const NR: usize>(
-        const_microkernels: &[[[MicroKernel<T>; NR]; MR]; 17],
-        m: usize,
-        n: usize,
-        k: usize,
-        is_col_major: bool,
-    ) -> Self
-    where
-        T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
-    {
-        let mut microkernels = [[MaybeUninit::<MicroKernel<T>>::uninit(); 2]; 2];
+    const M_BS: usize = COPY_M_BS;
+    const N_BS: usize = COPY_N_BS;
 
-        let mr = MR;
-        let nr = NR;
+    let mut i = 0usize;
+    while i < m {
+        let i_bs = Ord::min(M_BS, m - i);
+        let last_mask = if i + i_bs == m {
+            plan.last_mask
+        } else {
+            plan.full_mask
+        };
+        let lhs = lhs.offset(i as isize * lhs_rs);
 
-        {
-            let k = Ord::min(k.wrapping_sub(1), 16);
-            let m = m.wrapping_sub(1) % mr;
-            let n = n.wrapping_sub(1) % nr;
+        let mut j = 0usize;
+        while j < n {
+            let j_bs = Ord::min(N_BS, n - j);
+            let (inside, outside) = match triangle {
+                Triangle::Lower => (j + j_bs <= i + 1, j >= i + i_bs),
+                Triangle::Upper => (i + i_bs <= j + 1, i >= j + j_bs),
+            };
+            let rhs = rhs.offset(j as isize * rhs_cs);
+            let dst = dst.offset(i as isize * dst_rs + j as isize * dst_cs);
+
+            if inside {
+                (plan.millikernel)(
+                    &plan.microkernels,
+                    plan.mr,
+                    plan.nr,
+                    i_bs,
+                    j_bs,
+                    k,
+                    dst,
+                    dst_rs,
+                    dst_cs,
+                    lhs,
+                    lhs_rs,
+                    lhs_cs,
+                    rhs,
+                    rhs_rs,
+                    rhs_cs,
+                    alpha,
+                    beta,
+                    read_dst,
+                    conj_lhs,
+                    conj_rhs,
+                    plan.full_mask,
+                    last_mask,
+                    workspace,
+                    1,
+                    0,
+                    0,
+                    0,
+                    &FusedPtrs::NONE,
+                );
+            } else if !outside && workspace.is_null() {
+                triangular_diagonal_block_stack(
+                    plan, triangle, i, j, i_bs, j_bs, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs,
+                    rhs, rhs_rs, rhs_cs, alpha, beta, read_dst, conj_lhs, conj_rhs, last_mask,
+                );
+            } else if !outside {
+                // the diagonal blocks come first in the workspace
+                triangular_diagonal_block(
+                    plan,
+                    triangle,
+                    i,
+                    j,
+                    i_bs,
+                    j_bs,
+                    k,
+                    dst,
+                    dst_rs,
+                    dst_cs,
+                    lhs,
+                    lhs_rs,
+                    lhs_cs,
+                    rhs,
+                    rhs_rs,
+                    rhs_cs,
+                    alpha,
+                    beta,
+                    read_dst,
+                    conj_lhs,
+                    conj_rhs,
+                    last_mask,
+                    workspace,
+                    workspace.add(triangular_workspace_size(n)),
+                );
+            }
 
-            microkernels[0][0].write(const_microkernels[k][MR - 1][NR - 1]);
-            microkernels[0][1].write(const_microkernels[k][MR - 1][n]);
-            microkernels[1][0].write(const_microkernels[k][m][NR - 1]);
-            microkernels[1][1].write(const_microkernels[k][m][n]);
+            j += j_bs;
         }
 
-        Self {
-            microkernels,
-            millikernel: if m == 0 || n == 0 {
-                noop_millikernel
-            } else if k == 0 {
-                fill_millikernel
-            } else if is_col_major {
-                if m <= mr && n <= nr {
-                    small_direct_millikernel::<_, 1, 1>
-                } else if m <= mr && n <= 2 * nr {
-                    small_direct_millikernel::<_, 1, 2>
-                } else if m <= 2 * mr && n <= nr {
-                    small_direct_millikernel::<_, 2, 1>
-                } else if m <= 2 * mr && n <= 2 * nr {
-                    small_direct_millikernel::<_, 2, 2>
-                } else {
-                    direct_millikernel
-                }
-            } else {
-                copy_millikernel
-            },
-            mr,
-            nr,
-            m,
-            n,
-            k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
-            full_mask: &(),
-            last_mask: &(),
-        }
+        i += i_bs;
     }
 }
 
-impl Plan<f32> {
-    fn new_f32_scalar(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        Self {
-            microkernels: [[MaybeUninit::<MicroKernel<f32>>::uninit(); 2]; 2],
-            millikernel: naive_millikernel,
-            mr: 0,
-            nr: 0,
-            full_mask: core::ptr::null(),
-            last_mask: core::ptr::null(),
+/// Returns the number of elements of the buffer holding the tiles of the product passed to an
+/// epilogue, in addition to the ones used by the millikernel.
+#[inline]
+fn epilogue_workspace_size(n: usize) -> usize {
+    Ord::max(TILE_SIZE, COPY_M_BS * Ord::min(n, COPY_N_BS))
+}
+
+/// Computes `dst[i, j] = epilogue(i, j, (lhs * rhs)[i, j], dst[i, j])`, using the
+/// microkernels of `plan` on tiles of a temporary buffer when the layout allows it, and its
+/// millikernel on blocks of a temporary buffer otherwise.
+///
+/// If `workspace` isn't null, it must hold `epilogue_workspace_size(n)` elements for the
+/// temporary buffer, followed by the workspace of the millikernel.
+unsafe fn epilogue_millikernel<T: GemmScalar>(
+    plan: &Plan<T>,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    workspace: *mut T,
+    epilogue: &mut impl FnMut(usize, usize, T, T) -> T,
+) {
+    if workspace.is_null() {
+        epilogue_millikernel_stack(
+            plan, m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, conj_lhs,
+            conj_rhs, epilogue,
+        );
+    } else {
+        epilogue_millikernel_impl(
+            plan,
             m,
             n,
             k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
-        }
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            conj_lhs,
+            conj_rhs,
+            workspace,
+            workspace.add(epilogue_workspace_size(n)),
+            epilogue,
+        );
     }
 }
-impl Plan<f64> {
-    fn new_f64_scalar(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        Self {
-            microkernels: [[MaybeUninit::<MicroKernel<f64>>::uninit(); 2]; 2],
-            millikernel: naive_millikernel,
-            mr: 0,
-            nr: 0,
-            full_mask: core::ptr::null(),
-            last_mask: core::ptr::null(),
-            m,
-            n,
+
+/// Same as `epilogue_millikernel`, with the temporary buffer allocated on the stack.
+///
+/// This is kept out of line so that callers providing a workspace don't pay for the stack space.
+#[inline(never)]
+unsafe fn epilogue_millikernel_stack<T: GemmScalar>(
+    plan: &Plan<T>,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    epilogue: &mut impl FnMut(usize, usize, T, T) -> T,
+) {
+    let mut tmp: MaybeUninit<[T; COPY_M_BS * COPY_N_BS]> = MaybeUninit::uninit();
+    epilogue_millikernel_impl(
+        plan,
+        m,
+        n,
+        k,
+        dst,
+        dst_rs,
+        dst_cs,
+        lhs,
+        lhs_rs,
+        lhs_cs,
+        rhs,
+        rhs_rs,
+        rhs_cs,
+        conj_lhs,
+        conj_rhs,
+        tmp.as_mut_ptr() as *mut T,
+        core::ptr::null_mut(),
+        epilogue,
+    );
+}
+
+/// `tmp` must have room for `epilogue_workspace_size(n)` elements.
+#[inline(always)]
+unsafe fn epilogue_millikernel_impl<T: GemmScalar>(
+    plan: &Plan<T>,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    tmp: *mut T,
+    workspace: *mut T,
+    epilogue: &mut impl FnMut(usize, usize, T, T) -> T,
+) {
+    // stores the `m × n` block `tmp` at `(i, j)`
+    let mut store = |i: usize, j: usize, m: usize, n: usize, tmp: *const T, tmp_cs: isize| {
+        for jj in 0..n {
+            for ii in 0..m {
+                let dst = dst.offset((i + ii) as isize * dst_rs + (j + jj) as isize * dst_cs);
+                *dst = epilogue(
+                    i + ii,
+                    j + jj,
+                    *tmp.offset(ii as isize + jj as isize * tmp_cs),
+                    *dst,
+                );
+            }
+        }
+    };
+
+    if plan.mr != 0 && k != 0 && lhs_rs == 1 {
+        let (mr, nr) = (plan.mr, plan.nr);
+        debug_assert!(mr * nr <= TILE_SIZE);
+
+        let mut data = MicroKernelData {
+            alpha: core::mem::zeroed(),
+            beta: T::ONE,
+            read_dst: false,
+            conj_lhs,
+            conj_rhs,
             k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
+            dst_cs: mr as isize,
+            lhs_cs,
+            rhs_rs,
+            rhs_cs,
+            last_mask: plan.last_mask,
+            src: core::ptr::null(),
+            src_cs: 0,
+            row_scale: core::ptr::null(),
+            col_scale: core::ptr::null(),
+            k_scale: core::ptr::null(),
+            row_bias: core::ptr::null(),
+            col_bias: core::ptr::null(),
+            activation: Activation::None,
+        };
+        let mut i = 0usize;
+        while i < m {
+            let i_bs = Ord::min(mr, m - i);
+            data.last_mask = if i + mr <= m {
+                plan.full_mask
+            } else {
+                plan.last_mask
+            };
+            let microkernels = plan.microkernels.get_unchecked((i + mr > m) as usize);
+
+            let mut j = 0usize;
+            while j < n {
+                let j_bs = Ord::min(nr, n - j);
+                let microkernel = microkernels
+                    .get_unchecked((j + nr > n) as usize)
+                    .assume_init();
+                microkernel(
+                    &data,
+                    tmp,
+                    lhs.offset(i as isize),
+                    rhs.offset(j as isize * rhs_cs),
+                );
+                store(i, j, i_bs, j_bs, tmp, mr as isize);
+
+                j += nr;
+            }
+            i += mr;
         }
+        return;
     }
-}
-impl Plan<c32> {
-    fn new_c32_scalar(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        Self {
-            microkernels: [[MaybeUninit::<MicroKernel<c32>>::uninit(); 2]; 2],
-            millikernel: naive_millikernel,
-            mr: 0,
-            nr: 0,
-            full_mask: core::ptr::null(),
-            last_mask: core::ptr::null(),
-            m,
-            n,
-            k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
+
+    const M_BS: usize = COPY_M_BS;
+    const N_BS: usize = COPY_N_BS;
+
+    let mut i = 0usize;
+    while i < m {
+        let i_bs = Ord::min(M_BS, m - i);
+        let last_mask = if i + i_bs == m {
+            plan.last_mask
+        } else {
+            plan.full_mask
+        };
+
+        let mut j = 0usize;
+        while j < n {
+            let j_bs = Ord::min(N_BS, n - j);
+            (plan.millikernel)(
+                &plan.microkernels,
+                plan.mr,
+                plan.nr,
+                i_bs,
+                j_bs,
+                k,
+                tmp,
+                1,
+                M_BS as isize,
+                lhs.offset(i as isize * lhs_rs),
+                lhs_rs,
+                lhs_cs,
+                rhs.offset(j as isize * rhs_cs),
+                rhs_rs,
+                rhs_cs,
+                core::mem::zeroed(),
+                T::ONE,
+                false,
+                conj_lhs,
+                conj_rhs,
+                plan.full_mask,
+                last_mask,
+                workspace,
+                1,
+                0,
+                0,
+                0,
+                &FusedPtrs::NONE,
+            );
+            store(i, j, i_bs, j_bs, tmp, M_BS as isize);
+
+            j += j_bs;
         }
+        i += i_bs;
     }
 }
-impl Plan<c64> {
-    fn new_c64_scalar(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        Self {
-            microkernels: [[MaybeUninit::<MicroKernel<c64>>::uninit(); 2]; 2],
-            millikernel: naive_millikernel,
-            mr: 0,
-            nr: 0,
-            full_mask: core::ptr::null(),
-            last_mask: core::ptr::null(),
-            m,
-            n,
-            k,
-            dst_rs: if is_col_major { 1 } else { isize::MIN },
-            dst_cs: isize::MIN,
-            lhs_rs: if is_col_major { 1 } else { isize::MIN },
-            lhs_cs: isize::MIN,
-            rhs_cs: isize::MIN,
-            rhs_rs: isize::MIN,
+
+/// Packs the `m × k` block at `(i, depth)` of the square matrix `lhs`, of which only the triangle
+/// given by `structure` is read, to `dst` with a unit row stride.
+#[inline(always)]
+unsafe fn pack_structured<T: GemmScalar>(
+    structure: Structure,
+    i: usize,
+    depth: usize,
+    m: usize,
+    k: usize,
+    dst: *mut T,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+) {
+    let at = |i: usize, j: usize| *lhs.offset(i as isize * lhs_rs + j as isize * lhs_cs);
+    for jj in 0..k {
+        for ii in 0..m {
+            let (i, j) = (i + ii, depth + jj);
+            *dst.offset(ii as isize + jj as isize * dst_cs) = match structure {
+                Structure::Symmetric(triangle) => {
+                    if triangle.contains(i, j) {
+                        at(i, j)
+                    } else {
+                        at(j, i)
+                    }
+                }
+                Structure::Hermitian(triangle) => {
+                    if i == j {
+                        at(i, i).real()
+                    } else if triangle.contains(i, j) {
+                        at(i, j)
+                    } else {
+                        at(j, i).conj()
+                    }
+                }
+                Structure::Triangular(triangle) => {
+                    if triangle.contains(i, j) {
+                        at(i, j)
+                    } else {
+                        core::mem::zeroed()
+                    }
+                }
+                Structure::UnitTriangular(triangle) => {
+                    if i == j {
+                        T::ONE
+                    } else if triangle.contains(i, j) {
+                        at(i, j)
+                    } else {
+                        core::mem::zeroed()
+                    }
+                }
+            };
         }
     }
 }
 
-impl<T> Plan<T> {
-    #[inline(always)]
-    pub unsafe fn execute_unchecked(
-        &self,
-        m: usize,
-        n: usize,
-        k: usize,
-        dst: *mut T,
-        dst_rs: isize,
-        dst_cs: isize,
-        lhs: *const T,
-        lhs_rs: isize,
-        lhs_cs: isize,
-        rhs: *const T,
-        rhs_rs: isize,
-        rhs_cs: isize,
-        alpha: T,
-        beta: T,
-        conj_lhs: bool,
-        conj_rhs: bool,
-    ) {
-        debug_assert!(m == self.m);
-        debug_assert!(n == self.n);
-        debug_assert!(k == self.k);
-        if self.dst_cs != isize::MIN {
-            debug_assert!(dst_cs == self.dst_cs);
-        }
-        if self.dst_rs != isize::MIN {
-            debug_assert!(dst_rs == self.dst_rs);
-        }
-        if self.lhs_cs != isize::MIN {
-            debug_assert!(lhs_cs == self.lhs_cs);
-        }
-        if self.lhs_rs != isize::MIN {
-            debug_assert!(lhs_rs == self.lhs_rs);
-        }
-        if self.rhs_cs != isize::MIN {
-            debug_assert!(rhs_cs == self.rhs_cs);
-        }
-        if self.rhs_rs != isize::MIN {
-            debug_assert!(rhs_rs == self.rhs_rs);
-        }
+/// Returns the number of elements of the buffer holding the reconstructed strips of a structured
+/// `lhs`, in addition to the ones used by the millikernel.
+#[inline]
+fn structured_workspace_size(k: usize) -> usize {
+    COPY_M_BS * Ord::min(k, COPY_K_BS)
+}
 
-        (self.millikernel)(
-            &self.microkernels,
-            self.mr,
-            self.nr,
+/// Computes `dst = alpha * dst + beta * lhs * rhs`, where `lhs` is a square matrix with the given
+/// structure.
+///
+/// The blocks of `lhs` are reconstructed from the stored triangle, then passed to the millikernel
+/// of `plan`. For triangular matrices, `dst` is processed in strips of `mr` rows, each one only
+/// multiplied by the columns of `lhs` that aren't known to be zero.
+///
+/// If `workspace` isn't null, it must hold `structured_workspace_size(k)` elements for the
+/// reconstructed strips, followed by the workspace of the millikernel.
+#[inline(never)]
+unsafe fn structured_millikernel<T: GemmScalar>(
+    plan: &Plan<T>,
+    structure: Structure,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    workspace: *mut T,
+) {
+    if m == 0 || n == 0 {
+        return;
+    }
+    if workspace.is_null() {
+        structured_millikernel_stack(
+            plan, structure, m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs,
+            rhs_cs, alpha, beta, read_dst, conj_lhs, conj_rhs,
+        );
+    } else {
+        structured_millikernel_impl(
+            plan,
+            structure,
             m,
             n,
             k,
@@ -837,795 +1487,5615 @@ impl<T> Plan<T> {
             rhs_cs,
             alpha,
             beta,
+            read_dst,
             conj_lhs,
             conj_rhs,
-            self.full_mask,
-            self.last_mask,
+            workspace.add(structured_workspace_size(k)),
+            workspace,
         );
     }
 }
 
-impl Plan<f32> {
-    #[track_caller]
-    pub fn new_f32_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        #[cfg(feature = "std")]
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            #[cfg(feature = "nightly")]
-            if m > 8 && std::is_x86_feature_detected!("avx512f") {
-                return Self::new_f32_avx512(m, n, k, is_col_major);
+/// Same as `structured_millikernel`, with the reconstructed strips stored on the stack.
+///
+/// This is kept out of line so that callers providing a workspace don't pay for the stack space.
+#[inline(never)]
+unsafe fn structured_millikernel_stack<T: GemmScalar>(
+    plan: &Plan<T>,
+    structure: Structure,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+) {
+    let mut lhs_tmp: MaybeUninit<[T; COPY_M_BS * COPY_K_BS]> = MaybeUninit::uninit();
+    structured_millikernel_impl(
+        plan,
+        structure,
+        m,
+        n,
+        k,
+        dst,
+        dst_rs,
+        dst_cs,
+        lhs,
+        lhs_rs,
+        lhs_cs,
+        rhs,
+        rhs_rs,
+        rhs_cs,
+        alpha,
+        beta,
+        read_dst,
+        conj_lhs,
+        conj_rhs,
+        core::ptr::null_mut(),
+        lhs_tmp.as_mut_ptr() as *mut T,
+    );
+}
+
+/// `lhs_tmp` must have room for `structured_workspace_size(k)` elements.
+#[inline(always)]
+unsafe fn structured_millikernel_impl<T: GemmScalar>(
+    plan: &Plan<T>,
+    structure: Structure,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    workspace: *mut T,
+    lhs_tmp: *mut T,
+) {
+    const M_BS: usize = COPY_M_BS;
+    const K_BS: usize = COPY_K_BS;
+
+    if k == 0 {
+        (plan.millikernel)(
+            &plan.microkernels,
+            plan.mr,
+            plan.nr,
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            read_dst,
+            conj_lhs,
+            conj_rhs,
+            plan.full_mask,
+            plan.last_mask,
+            workspace,
+            1,
+            0,
+            0,
+            0,
+            &FusedPtrs::NONE,
+        );
+        return;
+    }
+
+    let nonzero = structure.nonzero_triangle();
+    let strip = if nonzero.is_some() && plan.mr != 0 {
+        plan.mr
+    } else {
+        M_BS
+    };
+
+    let mut i = 0usize;
+    while i < m {
+        let i_bs = Ord::min(strip, m - i);
+        let last_mask = if i + i_bs == m {
+            plan.last_mask
+        } else {
+            plan.full_mask
+        };
+        let dst = dst.offset(i as isize * dst_rs);
+
+        // columns of `lhs` that may hold nonzero elements in the rows of the strip
+        let (depth_begin, depth_end) = match nonzero {
+            None => (0, k),
+            Some(Triangle::Lower) => (0, Ord::min(k, i + i_bs)),
+            Some(Triangle::Upper) => (i, k),
+        };
+
+        let mut depth = depth_begin;
+        while depth < depth_end {
+            let depth_bs = Ord::min(K_BS, depth_end - depth);
+            pack_structured(
+                structure,
+                i,
+                depth,
+                i_bs,
+                depth_bs,
+                lhs_tmp,
+                M_BS as isize,
+                lhs,
+                lhs_rs,
+                lhs_cs,
+            );
+
+            // the first block initializes `dst`, the next ones accumulate to it
+            let first = depth == depth_begin;
+            (plan.millikernel)(
+                &plan.microkernels,
+                plan.mr,
+                plan.nr,
+                i_bs,
+                n,
+                depth_bs,
+                dst,
+                dst_rs,
+                dst_cs,
+                lhs_tmp,
+                1,
+                M_BS as isize,
+                rhs.offset(depth as isize * rhs_rs),
+                rhs_rs,
+                rhs_cs,
+                if first { alpha } else { T::ONE },
+                beta,
+                if first { read_dst } else { true },
+                conj_lhs,
+                conj_rhs,
+                plan.full_mask,
+                last_mask,
+                workspace,
+                1,
+                0,
+                0,
+                0,
+                &FusedPtrs::NONE,
+            );
+
+            depth += depth_bs;
+        }
+
+        i += i_bs;
+    }
+}
+
+mod sealed {
+    pub trait One {
+        const ONE: Self;
+    }
+    pub trait Conj {
+        fn conj(self) -> Self;
+    }
+    pub trait Real {
+        fn real(self) -> Self;
+        fn re(self) -> f64;
+        fn abs2(self) -> f64;
+        fn from_real(re: f64) -> Self;
+    }
+    /// Scalar counterpart of the activation functions of the kernels.
+    pub trait Activate: Sized {
+        fn activate(self, activation: crate::Activation<Self>) -> Self;
+    }
+}
+use sealed::{Activate, Conj, One, Real};
+
+/// Element type supported by [`Plan`] and [`planless::execute`].
+///
+/// This trait is sealed and implemented for [`f32`], [`f64`], [`c32`] and [`c64`].
+pub trait GemmScalar:
+    Copy
+    + PartialEq
+    + core::ops::Add<Output = Self>
+    + core::ops::Sub<Output = Self>
+    + core::ops::Mul<Output = Self>
+    + core::ops::Div<Output = Self>
+    + core::ops::Neg<Output = Self>
+    + Conj
+    + One
+    + Real
+    + Activate
+    + Send
+    + Sync
+    + 'static
+{
+    #[doc(hidden)]
+    fn new_plan_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Plan<Self>;
+    #[doc(hidden)]
+    fn new_plan_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Plan<Self>>;
+}
+
+impl GemmScalar for f32 {
+    #[inline]
+    #[track_caller]
+    fn new_plan_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Plan<Self> {
+        Plan::new_f32_impl(m, n, k, is_col_major)
+    }
+    #[inline]
+    #[track_caller]
+    fn new_plan_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Plan<Self>> {
+        Plan::new_f32_with_isa_impl(m, n, k, is_col_major, isa)
+    }
+}
+impl GemmScalar for f64 {
+    #[inline]
+    #[track_caller]
+    fn new_plan_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Plan<Self> {
+        Plan::new_f64_impl(m, n, k, is_col_major)
+    }
+    #[inline]
+    #[track_caller]
+    fn new_plan_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Plan<Self>> {
+        Plan::new_f64_with_isa_impl(m, n, k, is_col_major, isa)
+    }
+}
+impl GemmScalar for c32 {
+    #[inline]
+    #[track_caller]
+    fn new_plan_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Plan<Self> {
+        Plan::new_c32_impl(m, n, k, is_col_major)
+    }
+    #[inline]
+    #[track_caller]
+    fn new_plan_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Plan<Self>> {
+        Plan::new_c32_with_isa_impl(m, n, k, is_col_major, isa)
+    }
+}
+impl GemmScalar for c64 {
+    #[inline]
+    #[track_caller]
+    fn new_plan_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Plan<Self> {
+        Plan::new_c64_impl(m, n, k, is_col_major)
+    }
+    #[inline]
+    #[track_caller]
+    fn new_plan_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Plan<Self>> {
+        Plan::new_c64_with_isa_impl(m, n, k, is_col_major, isa)
+    }
+}
+
+impl One for f32 {
+    const ONE: Self = 1.0;
+}
+impl One for f64 {
+    const ONE: Self = 1.0;
+}
+impl One for c32 {
+    const ONE: Self = Self { re: 1.0, im: 0.0 };
+}
+impl One for c64 {
+    const ONE: Self = Self { re: 1.0, im: 0.0 };
+}
+
+impl Conj for f32 {
+    #[inline]
+    fn conj(self) -> Self {
+        self
+    }
+}
+impl Conj for f64 {
+    #[inline]
+    fn conj(self) -> Self {
+        self
+    }
+}
+
+impl Conj for c32 {
+    #[inline]
+    fn conj(self) -> Self {
+        Self::conj(&self)
+    }
+}
+impl Conj for c64 {
+    #[inline]
+    fn conj(self) -> Self {
+        Self::conj(&self)
+    }
+}
+
+impl Real for f32 {
+    #[inline]
+    fn real(self) -> Self {
+        self
+    }
+    #[inline]
+    fn re(self) -> f64 {
+        self as f64
+    }
+    #[inline]
+    fn abs2(self) -> f64 {
+        (self * self) as f64
+    }
+    #[inline]
+    fn from_real(re: f64) -> Self {
+        re as f32
+    }
+}
+impl Real for f64 {
+    #[inline]
+    fn real(self) -> Self {
+        self
+    }
+    #[inline]
+    fn re(self) -> f64 {
+        self
+    }
+    #[inline]
+    fn abs2(self) -> f64 {
+        self * self
+    }
+    #[inline]
+    fn from_real(re: f64) -> Self {
+        re
+    }
+}
+impl Real for c32 {
+    #[inline]
+    fn real(self) -> Self {
+        Self::new(self.re, 0.0)
+    }
+    #[inline]
+    fn re(self) -> f64 {
+        self.re as f64
+    }
+    #[inline]
+    fn abs2(self) -> f64 {
+        (self.re * self.re + self.im * self.im) as f64
+    }
+    #[inline]
+    fn from_real(re: f64) -> Self {
+        Self::new(re as f32, 0.0)
+    }
+}
+impl Real for c64 {
+    #[inline]
+    fn real(self) -> Self {
+        Self::new(self.re, 0.0)
+    }
+    #[inline]
+    fn re(self) -> f64 {
+        self.re
+    }
+    #[inline]
+    fn abs2(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+    #[inline]
+    fn from_real(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+// 64 is always a multiple of both MR and NR
+const COPY_M_BS: usize = 64;
+const COPY_N_BS: usize = 64;
+const COPY_K_BS: usize = 64;
+
+/// Returns the number of elements of the packing buffers used by `copy_millikernel`.
+#[inline]
+fn copy_workspace_size(n: usize, k: usize) -> usize {
+    COPY_M_BS * (Ord::min(k, COPY_K_BS) + Ord::min(n, COPY_N_BS))
+}
+
+unsafe fn copy_millikernel<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
+>(
+    microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
+    mr: usize,
+    nr: usize,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    full_mask: *const (),
+    last_mask: *const (),
+    workspace: *mut T,
+    batch: usize,
+    dst_bs: isize,
+    lhs_bs: isize,
+    rhs_bs: isize,
+    fused: &FusedPtrs<T>,
+) {
+    if dst_rs == 1 && lhs_rs == 1 {
+        direct_millikernel(
+            microkernels,
+            mr,
+            nr,
+            m,
+            n,
+            k,
+            dst,
+            1,
+            dst_cs,
+            lhs,
+            1,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            read_dst,
+            conj_lhs,
+            conj_rhs,
+            full_mask,
+            last_mask,
+            core::ptr::null_mut(),
+            batch,
+            dst_bs,
+            lhs_bs,
+            rhs_bs,
+            fused,
+        );
+        return;
+    }
+
+    for b in 0..batch {
+        let dst = dst.offset(b as isize * dst_bs);
+        let lhs = lhs.offset(b as isize * lhs_bs);
+        let rhs = rhs.offset(b as isize * rhs_bs);
+
+        if workspace.is_null() {
+            copy_millikernel_stack(
+                microkernels,
+                mr,
+                nr,
+                m,
+                n,
+                k,
+                dst,
+                dst_rs,
+                dst_cs,
+                lhs,
+                lhs_rs,
+                lhs_cs,
+                rhs,
+                rhs_rs,
+                rhs_cs,
+                alpha,
+                beta,
+                read_dst,
+                conj_lhs,
+                conj_rhs,
+                full_mask,
+                last_mask,
+                fused,
+            );
+        } else {
+            let lhs_tmp = workspace;
+            let dst_tmp = workspace.add(COPY_M_BS * Ord::min(k, COPY_K_BS));
+            copy_millikernel_impl(
+                microkernels,
+                mr,
+                nr,
+                m,
+                n,
+                k,
+                dst,
+                dst_rs,
+                dst_cs,
+                lhs,
+                lhs_rs,
+                lhs_cs,
+                rhs,
+                rhs_rs,
+                rhs_cs,
+                alpha,
+                beta,
+                read_dst,
+                conj_lhs,
+                conj_rhs,
+                full_mask,
+                last_mask,
+                dst_tmp,
+                lhs_tmp,
+                fused,
+            );
+        }
+    }
+}
+
+/// Same as `copy_millikernel`, with the packing buffers allocated on the stack.
+///
+/// This is kept out of line so that callers providing a workspace don't pay for the stack space.
+#[inline(never)]
+unsafe fn copy_millikernel_stack<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
+>(
+    microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
+    mr: usize,
+    nr: usize,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    full_mask: *const (),
+    last_mask: *const (),
+    fused: &FusedPtrs<T>,
+) {
+    let mut dst_tmp: MaybeUninit<[T; COPY_M_BS * COPY_N_BS]> = core::mem::MaybeUninit::uninit();
+    let mut lhs_tmp: MaybeUninit<[T; COPY_M_BS * COPY_K_BS]> = core::mem::MaybeUninit::uninit();
+
+    copy_millikernel_impl(
+        microkernels,
+        mr,
+        nr,
+        m,
+        n,
+        k,
+        dst,
+        dst_rs,
+        dst_cs,
+        lhs,
+        lhs_rs,
+        lhs_cs,
+        rhs,
+        rhs_rs,
+        rhs_cs,
+        alpha,
+        beta,
+        read_dst,
+        conj_lhs,
+        conj_rhs,
+        full_mask,
+        last_mask,
+        dst_tmp.as_mut_ptr() as *mut T,
+        lhs_tmp.as_mut_ptr() as *mut T,
+        fused,
+    );
+}
+
+/// `dst_tmp` must have room for `COPY_M_BS * min(n, COPY_N_BS)` elements, and `lhs_tmp` for
+/// `COPY_M_BS * min(k, COPY_K_BS)` elements.
+#[inline(always)]
+unsafe fn copy_millikernel_impl<
+    T: Copy + PartialEq + core::ops::Add<Output = T> + core::ops::Mul<Output = T> + Conj + One,
+>(
+    microkernels: &[[MaybeUninit<MicroKernel<T>>; 2]; 2],
+    mr: usize,
+    nr: usize,
+    m: usize,
+    n: usize,
+    k: usize,
+    dst: *mut T,
+    dst_rs: isize,
+    dst_cs: isize,
+    lhs: *const T,
+    lhs_rs: isize,
+    lhs_cs: isize,
+    rhs: *const T,
+    rhs_rs: isize,
+    rhs_cs: isize,
+    mut alpha: T,
+    beta: T,
+    read_dst: bool,
+    conj_lhs: bool,
+    conj_rhs: bool,
+    full_mask: *const (),
+    last_mask: *const (),
+    dst_tmp: *mut T,
+    lhs_tmp: *mut T,
+    fused: &FusedPtrs<T>,
+) {
+    const M_BS: usize = COPY_M_BS;
+    const N_BS: usize = COPY_N_BS;
+    const K_BS: usize = COPY_K_BS;
+
+    let gemm_dst = if dst_rs == 1 { dst } else { dst_tmp };
+    let gemm_lhs = lhs_tmp;
+    let gemm_dst_cs = if dst_rs == 1 { dst_cs } else { M_BS as isize };
+    let gemm_lhs_cs = M_BS as isize;
+
+    let mut depth = 0usize;
+    while depth < k {
+        let depth_bs = Ord::min(K_BS, k - depth);
+
+        let mut i = 0usize;
+        while i < m {
+            let i_bs = Ord::min(M_BS, m - i);
+
+            let lhs = lhs.offset(lhs_rs * i as isize + lhs_cs * depth as isize);
+
+            for ii in 0..i_bs {
+                for jj in 0..depth_bs {
+                    let ii = ii as isize;
+                    let jj = jj as isize;
+                    *(gemm_lhs.offset(ii + gemm_lhs_cs * jj)) =
+                        *(lhs.offset(lhs_rs * ii + lhs_cs * jj));
+                }
+            }
+
+            let mut j = 0usize;
+            while j < n {
+                let j_bs = Ord::min(N_BS, n - j);
+
+                let rhs = rhs.offset(rhs_rs * depth as isize + rhs_cs * j as isize);
+
+                let dst = dst.offset(dst_rs * i as isize + dst_cs * j as isize);
+                // the temporary buffer only holds the current block
+                let gemm_dst = if dst_rs == 1 {
+                    gemm_dst.offset(i as isize + gemm_dst_cs * j as isize)
+                } else {
+                    gemm_dst
+                };
+
+                let fused = fused.block(i, j).depth_block(depth, depth + depth_bs == k);
+
+                // strided blocks of `dst` are copied to the temporary buffer, so that the kernels
+                // can apply the fused operations to the complete result. they're read from `src`
+                // instead if it's set
+                if dst_rs != 1 && (alpha != core::mem::zeroed() || read_dst) && fused.src.is_null()
+                {
+                    for ii in 0..i_bs {
+                        for jj in 0..j_bs {
+                            let ii = ii as isize;
+                            let jj = jj as isize;
+                            *(gemm_dst.offset(ii + gemm_dst_cs * jj)) =
+                                *(dst.offset(dst_rs * ii + dst_cs * jj));
+                        }
+                    }
+                }
+
+                direct_millikernel(
+                    microkernels,
+                    mr,
+                    nr,
+                    i_bs,
+                    j_bs,
+                    depth_bs,
+                    gemm_dst,
+                    1,
+                    gemm_dst_cs,
+                    gemm_lhs,
+                    1,
+                    gemm_lhs_cs,
+                    rhs,
+                    rhs_rs,
+                    rhs_cs,
+                    alpha,
+                    beta,
+                    read_dst,
+                    conj_lhs,
+                    conj_rhs,
+                    full_mask,
+                    if i + i_bs == m { last_mask } else { full_mask },
+                    core::ptr::null_mut(),
+                    1,
+                    0,
+                    0,
+                    0,
+                    &fused,
+                );
+
+                if dst_rs != 1 {
+                    for ii in 0..i_bs {
+                        for jj in 0..j_bs {
+                            let ii = ii as isize;
+                            let jj = jj as isize;
+                            *(dst.offset(dst_rs * ii + dst_cs * jj)) =
+                                *(gemm_dst.offset(ii + gemm_dst_cs * jj));
+                        }
+                    }
+                }
+
+                j += j_bs;
+            }
+
+            i += i_bs;
+        }
+
+        alpha = T::ONE;
+        depth += depth_bs;
+    }
+}
+
+impl<T> Plan<T> {
+    #[allow(dead_code)]
+    #[inline(always)]
+    fn from_masked_impl<const MR_DIV_N: usize, const NR: usize, const N: usize, Mask>(
+        const_microkernels: &[[[MicroKernel<T>; NR]; MR_DIV_N]; 17],
+        const_masks: Option<&[Mask; N]>,
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Self
+    where
+        T: Copy
+            + PartialEq
+            + core::ops::Add<Output = T>
+            + core::ops::Mul<Output = T>
+            + Conj
+            + One
+            + Activate,
+    {
+        let mut microkernels = [[MaybeUninit::<MicroKernel<T>>::uninit(); 2]; 2];
+
+        let mr = MR_DIV_N * N;
+        let nr = NR;
+
+        {
+            let k = Ord::min(k.wrapping_sub(1), 16);
+            let m = (m.wrapping_sub(1) / N) % (mr / N);
+            let n = n.wrapping_sub(1) % nr;
+
+            microkernels[0][0].write(const_microkernels[k][MR_DIV_N - 1][NR - 1]);
+            microkernels[0][1].write(const_microkernels[k][MR_DIV_N - 1][n]);
+            microkernels[1][0].write(const_microkernels[k][m][NR - 1]);
+            microkernels[1][1].write(const_microkernels[k][m][n]);
+        }
+
+        Self {
+            microkernels,
+            millikernel: if m == 0 || n == 0 {
+                noop_millikernel
+            } else if k == 0 {
+                fill_millikernel
+            } else if is_col_major {
+                if m <= mr && n <= nr {
+                    small_direct_millikernel::<_, 1, 1>
+                } else if m <= mr && n <= 2 * nr {
+                    small_direct_millikernel::<_, 1, 2>
+                } else if m <= 2 * mr && n <= nr {
+                    small_direct_millikernel::<_, 2, 1>
+                } else if m <= 2 * mr && n <= 2 * nr {
+                    small_direct_millikernel::<_, 2, 2>
+                } else {
+                    direct_millikernel
+                }
+            } else {
+                copy_millikernel
+            },
+            mr,
+            nr,
+            m,
+            n,
+            k,
+            dst_rs: if is_col_major { 1 } else { isize::MIN },
+            dst_cs: isize::MIN,
+            lhs_rs: if is_col_major { 1 } else { isize::MIN },
+            lhs_cs: isize::MIN,
+            rhs_cs: isize::MIN,
+            rhs_rs: isize::MIN,
+            lhs_op: Op::NoTrans,
+            rhs_op: Op::NoTrans,
+            dst_op: Op::NoTrans,
+            transpose: false,
+            isa,
+            workspace_size: if m == 0 || n == 0 || k == 0 || is_col_major {
+                0
+            } else {
+                copy_workspace_size(n, k)
+            },
+            gemv: None,
+            triangle: None,
+            lhs_structure: None,
+            rhs_structure: None,
+            full_mask: if let Some(const_masks) = const_masks {
+                (&const_masks[0]) as *const _ as *const ()
+            } else {
+                &()
+            },
+            last_mask: if let Some(const_masks) = const_masks {
+                (&const_masks[m % N]) as *const _ as *const ()
+            } else {
+                &()
+            },
+        }
+    }
+
+    #[allow(dead_code)]
+    #[inline(always)]
+    fn from_non_masked_impl<const MR: usize, const NR: usize>(
+        const_microkernels: &[[[MicroKernel<T>; NR]; MR]; 17],
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Self
+    where
+        T: Copy
+            + PartialEq
+            + core::ops::Add<Output = T>
+            + core::ops::Mul<Output = T>
+            + Conj
+            + One
+            + Activate,
+    {
+        let mut microkernels = [[MaybeUninit::<MicroKernel<T>>::uninit(); 2]; 2];
+
+        let mr = MR;
+        let nr = NR;
+
+        {
+            let k = Ord::min(k.wrapping_sub(1), 16);
+            let m = m.wrapping_sub(1) % mr;
+            let n = n.wrapping_sub(1) % nr;
+
+            microkernels[0][0].write(const_microkernels[k][MR - 1][NR - 1]);
+            microkernels[0][1].write(const_microkernels[k][MR - 1][n]);
+            microkernels[1][0].write(const_microkernels[k][m][NR - 1]);
+            microkernels[1][1].write(const_microkernels[k][m][n]);
+        }
+
+        Self {
+            microkernels,
+            millikernel: if m == 0 || n == 0 {
+                noop_millikernel
+            } else if k == 0 {
+                fill_millikernel
+            } else if is_col_major {
+                if m <= mr && n <= nr {
+                    small_direct_millikernel::<_, 1, 1>
+                } else if m <= mr && n <= 2 * nr {
+                    small_direct_millikernel::<_, 1, 2>
+                } else if m <= 2 * mr && n <= nr {
+                    small_direct_millikernel::<_, 2, 1>
+                } else if m <= 2 * mr && n <= 2 * nr {
+                    small_direct_millikernel::<_, 2, 2>
+                } else {
+                    direct_millikernel
+                }
+            } else {
+                copy_millikernel
+            },
+            mr,
+            nr,
+            m,
+            n,
+            k,
+            dst_rs: if is_col_major { 1 } else { isize::MIN },
+            dst_cs: isize::MIN,
+            lhs_rs: if is_col_major { 1 } else { isize::MIN },
+            lhs_cs: isize::MIN,
+            rhs_cs: isize::MIN,
+            rhs_rs: isize::MIN,
+            lhs_op: Op::NoTrans,
+            rhs_op: Op::NoTrans,
+            dst_op: Op::NoTrans,
+            transpose: false,
+            isa,
+            workspace_size: if m == 0 || n == 0 || k == 0 || is_col_major {
+                0
+            } else {
+                copy_workspace_size(n, k)
+            },
+            gemv: None,
+            triangle: None,
+            lhs_structure: None,
+            rhs_structure: None,
+            full_mask: &(),
+            last_mask: &(),
+        }
+    }
+}
+
+impl<T: GemmScalar> Plan<T> {
+    fn new_scalar(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+        Self {
+            microkernels: [[MaybeUninit::<MicroKernel<T>>::uninit(); 2]; 2],
+            millikernel: naive_millikernel,
+            mr: 0,
+            nr: 0,
+            full_mask: core::ptr::null(),
+            last_mask: core::ptr::null(),
+            m,
+            n,
+            k,
+            dst_rs: if is_col_major { 1 } else { isize::MIN },
+            dst_cs: isize::MIN,
+            lhs_rs: if is_col_major { 1 } else { isize::MIN },
+            lhs_cs: isize::MIN,
+            rhs_cs: isize::MIN,
+            rhs_rs: isize::MIN,
+            lhs_op: Op::NoTrans,
+            rhs_op: Op::NoTrans,
+            dst_op: Op::NoTrans,
+            transpose: false,
+            isa: Isa::Scalar,
+            workspace_size: 0,
+            gemv: None,
+            triangle: None,
+            lhs_structure: None,
+            rhs_structure: None,
+        }
+    }
+
+    /// Creates a plan for multiplying an `m × k` matrix by a `k × n` matrix, with arbitrary
+    /// strides.
+    #[track_caller]
+    pub fn new(m: usize, n: usize, k: usize) -> Self {
+        T::new_plan_impl(m, n, k, false)
+    }
+
+    /// Creates a plan for multiplying an `m × k` matrix by a `k × n` matrix, with arbitrary
+    /// strides, using the kernels of `isa`.
+    ///
+    /// # Errors
+    /// Returns an error if the kernels of `isa` aren't available for `T`. See
+    /// [`Isa::is_available`].
+    #[track_caller]
+    pub fn new_with_isa(m: usize, n: usize, k: usize, isa: Isa) -> Result<Self, PlanError> {
+        T::new_plan_with_isa_impl(m, n, k, false, isa).ok_or(PlanError::UnsupportedIsa { isa })
+    }
+
+    /// Creates a plan for multiplying an `m × k` matrix by a `k × n` matrix, where `lhs` and
+    /// `dst` must have a unit row stride.
+    #[track_caller]
+    pub fn new_colmajor_lhs_and_dst(m: usize, n: usize, k: usize) -> Self {
+        T::new_plan_impl(m, n, k, true)
+    }
+
+    /// Creates a plan computing `op(dst) = alpha * op(dst) + beta * op(lhs) * op(rhs)`, where
+    /// `op(lhs)` is an `m × k` matrix and `op(rhs)` is a `k × n` matrix, with arbitrary strides.
+    ///
+    /// The pointers and strides passed when executing the plan are the ones of the stored
+    /// matrices, before `op` is applied.
+    #[track_caller]
+    pub fn new_with_ops(m: usize, n: usize, k: usize, lhs_op: Op, rhs_op: Op, dst_op: Op) -> Self {
+        PlanBuilder::new(m, n, k)
+            .lhs_op(lhs_op)
+            .rhs_op(rhs_op)
+            .dst_op(dst_op)
+            .build()
+    }
+
+    /// Creates a plan computing only the `triangle` of `dst = alpha * dst + beta * lhs * rhs`,
+    /// where `lhs` is an `n × k` matrix and `rhs` is a `k × n` matrix, with arbitrary strides.
+    ///
+    /// The elements of `dst` outside of the triangle are neither computed, read nor written.
+    #[track_caller]
+    pub fn new_with_triangle(n: usize, k: usize, triangle: Triangle) -> Self {
+        PlanBuilder::new(n, n, k).dst_triangle(triangle).build()
+    }
+
+    /// Same as [`Plan::new_with_ops`], but the stored `lhs`, `rhs` and `dst` must have a unit row
+    /// stride.
+    #[track_caller]
+    pub fn new_colmajor_with_ops(
+        m: usize,
+        n: usize,
+        k: usize,
+        lhs_op: Op,
+        rhs_op: Op,
+        dst_op: Op,
+    ) -> Self {
+        PlanBuilder::new(m, n, k)
+            .dst_rs(1)
+            .lhs_rs(1)
+            .rhs_rs(1)
+            .lhs_op(lhs_op)
+            .rhs_op(rhs_op)
+            .dst_op(dst_op)
+            .build()
+    }
+}
+
+impl<T> Plan<T> {
+    /// Returns the instruction set used by the kernels of the plan.
+    #[inline]
+    pub fn isa(&self) -> Isa {
+        self.isa
+    }
+
+    /// Returns the number of elements of the workspace needed by
+    /// [`Plan::execute_with_workspace`].
+    #[inline]
+    pub fn workspace_size(&self) -> usize {
+        self.workspace_size
+    }
+
+    /// Returns the number of elements of the workspace needed by
+    /// [`Plan::execute_with_epilogue_with_workspace`], which also holds the tiles of the product
+    /// passed to the epilogue.
+    #[inline]
+    pub fn epilogue_workspace_size(&self) -> usize {
+        let n = if self.transpose { self.m } else { self.n };
+        if self.m == 0 || self.n == 0 {
+            self.workspace_size
+        } else {
+            self.workspace_size + epilogue_workspace_size(n)
+        }
+    }
+}
+
+impl<T: GemmScalar> Plan<T> {
+    /// Computes `dst = alpha * dst + beta * lhs * rhs`, where `lhs` and `rhs` are optionally
+    /// conjugated, or `dst = beta * lhs * rhs` if `alpha` is zero.
+    ///
+    /// # Safety
+    /// The dimensions and strides must match the ones the plan was created with. This is only
+    /// checked in debug builds, see [`Plan::try_execute`] for a version that always checks them.
+    /// `dst` must be valid for reads and writes, and `lhs` and `rhs` must be valid for reads, at
+    /// the offsets of all the elements described by the dimensions and strides. `dst` must not
+    /// overlap with `lhs` or `rhs`.
+    #[inline(always)]
+    pub unsafe fn execute_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            false,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            1,
+            0,
+            0,
+            0,
+            FusedPtrs::NONE,
+        )
+    }
+
+    /// Same as [`Plan::execute_unchecked`], but uses `workspace` for the temporary buffers instead
+    /// of allocating them on the stack.
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_unchecked`]. Additionally, `workspace` must be valid for reads and
+    /// writes of [`Plan::workspace_size`] elements, and must not overlap with the operands.
+    #[inline(always)]
+    pub unsafe fn execute_unchecked_with_workspace(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        workspace: *mut MaybeUninit<T>,
+    ) {
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            false,
+            conj_lhs,
+            conj_rhs,
+            workspace as *mut T,
+            1,
+            0,
+            0,
+            0,
+            FusedPtrs::NONE,
+        )
+    }
+
+    /// Same as [`Plan::execute_unchecked`], for `batch` independent problems of the same shape.
+    ///
+    /// The operands of the `b`-th problem start at `dst + b * dst_bs`, `lhs + b * lhs_bs` and
+    /// `rhs + b * rhs_bs`. A batch stride of zero means that the operand is shared by all the
+    /// problems. If `dst` is shared, the problems are applied to it one after the other.
+    ///
+    /// # Safety
+    /// The conditions of [`Plan::execute_unchecked`] must hold for each problem of the batch, and
+    /// the `dst` of a problem must not overlap with the operands of the other problems, unless
+    /// it's the same matrix.
+    #[inline(always)]
+    pub unsafe fn execute_batched(
+        &self,
+        batch: usize,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_bs: isize,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_bs: isize,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_bs: isize,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            false,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            batch,
+            dst_bs,
+            lhs_bs,
+            rhs_bs,
+            FusedPtrs::NONE,
+        )
+    }
+
+    /// Same as [`Plan::execute_unchecked`], for independent problems of the same shape whose
+    /// operands are given by the pointers at the same index in `dst`, `lhs` and `rhs`.
+    ///
+    /// Since the operands of consecutive problems aren't a fixed distance apart, the millikernels
+    /// can't step from one problem to the next like they do for [`Plan::execute_batched`], and
+    /// each problem is dispatched on its own. This only adds the cost of picking the kernels,
+    /// which is the same as calling [`Plan::execute_unchecked`] in a loop. Operands that are
+    /// evenly spaced in memory should go through [`Plan::execute_batched`] instead.
+    ///
+    /// # Panics
+    /// Panics if `dst`, `lhs` and `rhs` have different lengths.
+    ///
+    /// # Safety
+    /// The conditions of [`Plan::execute_unchecked`] must hold for each problem, and the `dst` of a
+    /// problem must not overlap with the operands of the other problems, unless it's the same
+    /// matrix.
+    #[track_caller]
+    #[inline]
+    pub unsafe fn execute_ptr_batched(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: &[*mut T],
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: &[*const T],
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: &[*const T],
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        equator::assert!(all(dst.len() == lhs.len(), dst.len() == rhs.len()));
+
+        for ((&dst, &lhs), &rhs) in dst.iter().zip(lhs).zip(rhs) {
+            self.execute_raw(
+                m,
+                n,
+                k,
+                dst,
+                dst_rs,
+                dst_cs,
+                lhs,
+                lhs_rs,
+                lhs_cs,
+                rhs,
+                rhs_rs,
+                rhs_cs,
+                alpha,
+                beta,
+                false,
+                conj_lhs,
+                conj_rhs,
+                core::ptr::null_mut(),
+                1,
+                0,
+                0,
+                0,
+                FusedPtrs::NONE,
+            );
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn execute_raw(
+        &self,
+        mut m: usize,
+        mut n: usize,
+        k: usize,
+        dst: *mut T,
+        mut dst_rs: isize,
+        mut dst_cs: isize,
+        mut lhs: *const T,
+        mut lhs_rs: isize,
+        mut lhs_cs: isize,
+        mut rhs: *const T,
+        mut rhs_rs: isize,
+        mut rhs_cs: isize,
+        mut alpha: T,
+        mut beta: T,
+        read_dst: bool,
+        mut conj_lhs: bool,
+        mut conj_rhs: bool,
+        workspace: *mut T,
+        batch: usize,
+        dst_bs: isize,
+        mut lhs_bs: isize,
+        mut rhs_bs: isize,
+        mut fused: FusedPtrs<T>,
+    ) {
+        debug_assert!(m == self.m);
+        debug_assert!(n == self.n);
+        debug_assert!(k == self.k);
+        if self.dst_cs != isize::MIN {
+            debug_assert!(dst_cs == self.dst_cs);
+        }
+        if self.dst_rs != isize::MIN {
+            debug_assert!(dst_rs == self.dst_rs);
+        }
+        if self.lhs_cs != isize::MIN {
+            debug_assert!(lhs_cs == self.lhs_cs);
+        }
+        if self.lhs_rs != isize::MIN {
+            debug_assert!(lhs_rs == self.lhs_rs);
+        }
+        if self.rhs_cs != isize::MIN {
+            debug_assert!(rhs_cs == self.rhs_cs);
+        }
+        if self.rhs_rs != isize::MIN {
+            debug_assert!(rhs_rs == self.rhs_rs);
+        }
+
+        if self.lhs_op != Op::NoTrans {
+            core::mem::swap(&mut lhs_rs, &mut lhs_cs);
+            conj_lhs ^= self.lhs_op == Op::ConjTrans;
+        }
+        if self.rhs_op != Op::NoTrans {
+            core::mem::swap(&mut rhs_rs, &mut rhs_cs);
+            conj_rhs ^= self.rhs_op == Op::ConjTrans;
+        }
+        if self.dst_op != Op::NoTrans {
+            core::mem::swap(&mut dst_rs, &mut dst_cs);
+            // `src` is stored like `dst`, unlike the vectors of the fused operations
+            core::mem::swap(&mut fused.src_rs, &mut fused.src_cs);
+            if self.dst_op == Op::ConjTrans {
+                // conj(dst) = conj(alpha) * conj(dst) + conj(beta) * conj(lhs) * conj(rhs)
+                alpha = alpha.conj();
+                beta = beta.conj();
+                conj_lhs = !conj_lhs;
+                conj_rhs = !conj_rhs;
+            }
+        }
+        if self.transpose {
+            // dst^T = rhs^T * lhs^T
+            core::mem::swap(&mut m, &mut n);
+            core::mem::swap(&mut dst_rs, &mut dst_cs);
+            core::mem::swap(&mut lhs, &mut rhs);
+            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
+            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
+            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
+            core::mem::swap(&mut lhs_bs, &mut rhs_bs);
+            fused = fused.transpose();
+        }
+
+        // the structure follows the operand through the transpositions
+        let lhs_structure = if self.lhs_op != Op::NoTrans {
+            self.lhs_structure.map(Structure::transpose)
+        } else {
+            self.lhs_structure
+        };
+        let rhs_structure = if self.rhs_op != Op::NoTrans {
+            self.rhs_structure.map(Structure::transpose)
+        } else {
+            self.rhs_structure
+        };
+        let structure = if self.transpose {
+            rhs_structure.map(Structure::transpose)
+        } else {
+            lhs_structure
+        };
+        if let Some(structure) = structure {
+            debug_assert!(fused.is_none());
+            for b in 0..batch as isize {
+                structured_millikernel(
+                    self,
+                    structure,
+                    m,
+                    n,
+                    k,
+                    dst.wrapping_offset(b * dst_bs),
+                    dst_rs,
+                    dst_cs,
+                    lhs.wrapping_offset(b * lhs_bs),
+                    lhs_rs,
+                    lhs_cs,
+                    rhs.wrapping_offset(b * rhs_bs),
+                    rhs_rs,
+                    rhs_cs,
+                    alpha,
+                    beta,
+                    read_dst,
+                    conj_lhs,
+                    conj_rhs,
+                    workspace,
+                );
+            }
+            return;
+        }
+
+        if let Some(mut triangle) = self.triangle {
+            debug_assert!(fused.is_none());
+            // the triangle is given for the stored `dst`
+            if (self.dst_op != Op::NoTrans) != self.transpose {
+                triangle = triangle.transpose();
+            }
+            for b in 0..batch as isize {
+                triangular_millikernel(
+                    self,
+                    triangle,
+                    m,
+                    n,
+                    k,
+                    dst.wrapping_offset(b * dst_bs),
+                    dst_rs,
+                    dst_cs,
+                    lhs.wrapping_offset(b * lhs_bs),
+                    lhs_rs,
+                    lhs_cs,
+                    rhs.wrapping_offset(b * rhs_bs),
+                    rhs_rs,
+                    rhs_cs,
+                    alpha,
+                    beta,
+                    read_dst,
+                    conj_lhs,
+                    conj_rhs,
+                    workspace,
+                );
+            }
+            return;
+        }
+
+        // the matrix-vector kernels don't apply the fused operations
+        if let Some(gemv) = self.gemv.as_ref().filter(|_| fused.is_none()) {
+            // the dot product form is preferred for short columns
+            let rowmajor = lhs_cs == 1 && rhs_rs == 1;
+            let colmajor = lhs_rs == 1 && dst_rs == 1;
+            let kernel = if rowmajor && (m < 4 || !colmajor) {
+                Some(gemv.rowmajor)
+            } else if colmajor {
+                Some(gemv.colmajor)
+            } else {
+                None
+            };
+
+            if let Some(kernel) = kernel {
+                let data = GemvData {
+                    alpha,
+                    beta,
+                    read_dst,
+                    m,
+                    k,
+                    dst_rs,
+                    lhs_rs,
+                    lhs_cs,
+                    rhs_rs,
+                };
+                for b in 0..batch as isize {
+                    kernel(
+                        &data,
+                        dst.wrapping_offset(b * dst_bs),
+                        lhs.wrapping_offset(b * lhs_bs),
+                        rhs.wrapping_offset(b * rhs_bs),
+                    );
+                }
+                return;
+            }
+        }
+
+        (self.millikernel)(
+            &self.microkernels,
+            self.mr,
+            self.nr,
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            read_dst,
+            conj_lhs,
+            conj_rhs,
+            self.full_mask,
+            self.last_mask,
+            workspace,
+            batch,
+            dst_bs,
+            lhs_bs,
+            rhs_bs,
+            &fused,
+        );
+    }
+
+    /// Checks that the dimensions and strides match the ones the plan was created with.
+    fn check_layout(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs_rs: isize,
+        rhs_cs: isize,
+    ) -> Result<(), PlanError> {
+        if (m, n, k) != (self.m, self.n, self.k) {
+            return Err(PlanError::ShapeMismatch {
+                expected: (self.m, self.n, self.k),
+                found: (m, n, k),
+            });
+        }
+        for (stride, expected, found) in [
+            ("dst_rs", self.dst_rs, dst_rs),
+            ("dst_cs", self.dst_cs, dst_cs),
+            ("lhs_rs", self.lhs_rs, lhs_rs),
+            ("lhs_cs", self.lhs_cs, lhs_cs),
+            ("rhs_rs", self.rhs_rs, rhs_rs),
+            ("rhs_cs", self.rhs_cs, rhs_cs),
+        ] {
+            if expected != isize::MIN && expected != found {
+                return Err(PlanError::StrideMismatch {
+                    stride,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as [`Plan::execute_unchecked`], but checks the arguments before running the kernels.
+    ///
+    /// # Errors
+    /// Returns an error and leaves `dst` untouched if the dimensions or strides don't match the
+    /// ones the plan was created with, if an element offset overflows an `isize`, or if the memory
+    /// spanned by `dst` overlaps with the memory spanned by `lhs` or `rhs`.
+    ///
+    /// # Safety
+    /// `dst` must be valid for reads and writes, and `lhs` and `rhs` must be valid for reads, at
+    /// the offsets of all the elements described by the dimensions and strides.
+    #[inline]
+    pub unsafe fn try_execute(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) -> Result<(), PlanError> {
+        self.check_layout(m, n, k, dst_rs, dst_cs, lhs_rs, lhs_cs, rhs_rs, rhs_cs)?;
+
+        let (dst_nrows, dst_ncols) = self.dst_op.dims(m, n);
+        let (lhs_nrows, lhs_ncols) = self.lhs_op.dims(m, k);
+        let (rhs_nrows, rhs_ncols) = self.rhs_op.dims(k, n);
+
+        let dst_range = error::byte_range(dst, dst_nrows, dst_ncols, dst_rs, dst_cs)
+            .ok_or(PlanError::OffsetOverflow { operand: "dst" })?;
+        let lhs_range = error::byte_range(lhs, lhs_nrows, lhs_ncols, lhs_rs, lhs_cs)
+            .ok_or(PlanError::OffsetOverflow { operand: "lhs" })?;
+        let rhs_range = error::byte_range(rhs, rhs_nrows, rhs_ncols, rhs_rs, rhs_cs)
+            .ok_or(PlanError::OffsetOverflow { operand: "rhs" })?;
+
+        for (operand, range) in [("lhs", lhs_range), ("rhs", rhs_range)] {
+            if !dst_range.is_empty()
+                && !range.is_empty()
+                && dst_range.start < range.end
+                && range.start < dst_range.end
+            {
+                return Err(PlanError::Aliasing { operand });
+            }
+        }
+
+        self.execute_unchecked(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        );
+        Ok(())
+    }
+
+    /// Same as [`Plan::execute_unchecked`], but combines the product with `dst` as described by
+    /// `accumulate`.
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_unchecked`]. `dst` isn't read if `accumulate` is
+    /// [`Accumulate::Overwrite`].
+    #[inline(always)]
+    pub unsafe fn execute_accumulate_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        accumulate: Accumulate<T>,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        let (alpha, beta, read_dst) = accumulate.to_alpha_beta();
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            read_dst,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            1,
+            0,
+            0,
+            0,
+            FusedPtrs::NONE,
+        )
+    }
+
+    /// Same as [`Plan::execute_unchecked`], but also applies the operations of `fused` while the
+    /// result is stored, computing
+    /// `dst[i, j] = f(alpha * dst[i, j] + beta * prod[i, j] + row_bias[i] + col_bias[j])`, where
+    /// `f` is the activation and `prod = D1 * lhs * D * rhs * D2`, with `D1`, `D` and `D2` the
+    /// diagonal matrices formed by the row, depth and column scales.
+    ///
+    /// # Panics
+    /// Panics if the plan was created with a triangular `dst` or a structured operand, if the
+    /// scale and bias vectors don't have `m`, `k` or `n` elements as needed, or if they're combined
+    /// with a `dst_op` of [`Op::ConjTrans`].
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_unchecked`].
+    #[track_caller]
+    #[inline]
+    pub unsafe fn execute_fused_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        fused: &FusedOps<'_, T>,
+    ) {
+        self.check_fused(m, n, k, fused);
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            false,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            1,
+            0,
+            0,
+            0,
+            fused.as_ptrs(),
+        )
+    }
+
+    /// Same as [`Plan::execute_unchecked`], but reads `src` in place of `dst`, computing
+    /// `dst = alpha * src + beta * lhs * rhs`. `src` has the same dimensions as `dst`, with its own
+    /// strides, and isn't read if `alpha` is zero.
+    ///
+    /// # Panics
+    /// Panics if the plan was created with a triangular `dst` or a structured operand.
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_unchecked`]. `src` must be valid for reads, and must either be
+    /// the same as `dst` with the same strides, or not overlap it.
+    #[track_caller]
+    #[inline]
+    pub unsafe fn execute_with_src_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        src: *const T,
+        src_rs: isize,
+        src_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        equator::assert!(all(
+            self.triangle.is_none(),
+            self.lhs_structure.is_none(),
+            self.rhs_structure.is_none(),
+        ));
+        self.execute_raw(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            alpha,
+            beta,
+            false,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            1,
+            0,
+            0,
+            0,
+            FusedPtrs {
+                src,
+                src_rs,
+                src_cs,
+                ..FusedPtrs::NONE
+            },
+        )
+    }
+
+    /// Same as [`Plan::execute_with_epilogue`], but takes raw pointers and strides like
+    /// [`Plan::execute_unchecked`].
+    ///
+    /// # Panics
+    /// Panics if the plan was created with a triangular `dst` or a structured operand.
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_unchecked`].
+    #[track_caller]
+    #[inline]
+    pub unsafe fn execute_with_epilogue_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const T,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const T,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        epilogue: impl FnMut(usize, usize, T, T) -> T,
+    ) {
+        self.execute_with_epilogue_unchecked_with_workspace(
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            conj_lhs,
+            conj_rhs,
+            core::ptr::null_mut(),
+            epilogue,
+        )
+    }
+
+    /// Same as [`Plan::execute_with_epilogue_unchecked`], but uses `workspace` for the temporary
+    /// buffers instead of allocating them on the stack.
+    ///
+    /// # Panics
+    /// Panics if the plan was created with a triangular `dst` or a structured operand.
+    ///
+    /// # Safety
+    /// Same as [`Plan::execute_with_epilogue_unchecked`]. Additionally, `workspace` must be valid
+    /// for reads and writes of [`Plan::epilogue_workspace_size`] elements, and must not overlap
+    /// with the operands.
+    #[track_caller]
+    #[inline]
+    pub unsafe fn execute_with_epilogue_unchecked_with_workspace(
+        &self,
+        mut m: usize,
+        mut n: usize,
+        k: usize,
+        dst: *mut T,
+        mut dst_rs: isize,
+        mut dst_cs: isize,
+        mut lhs: *const T,
+        mut lhs_rs: isize,
+        mut lhs_cs: isize,
+        mut rhs: *const T,
+        mut rhs_rs: isize,
+        mut rhs_cs: isize,
+        mut conj_lhs: bool,
+        mut conj_rhs: bool,
+        workspace: *mut MaybeUninit<T>,
+        mut epilogue: impl FnMut(usize, usize, T, T) -> T,
+    ) {
+        equator::assert!(all(
+            self.triangle.is_none(),
+            self.lhs_structure.is_none(),
+            self.rhs_structure.is_none(),
+        ));
+        debug_assert!(all(m == self.m, n == self.n, k == self.k));
+
+        if self.lhs_op != Op::NoTrans {
+            core::mem::swap(&mut lhs_rs, &mut lhs_cs);
+            conj_lhs ^= self.lhs_op == Op::ConjTrans;
+        }
+        if self.rhs_op != Op::NoTrans {
+            core::mem::swap(&mut rhs_rs, &mut rhs_cs);
+            conj_rhs ^= self.rhs_op == Op::ConjTrans;
+        }
+        // the product is computed as is, and only the elements of `dst` are conjugated
+        let conj_dst = self.dst_op == Op::ConjTrans;
+        if self.dst_op != Op::NoTrans {
+            core::mem::swap(&mut dst_rs, &mut dst_cs);
+        }
+        let transpose = self.transpose;
+        if transpose {
+            // dst^T = rhs^T * lhs^T
+            core::mem::swap(&mut m, &mut n);
+            core::mem::swap(&mut dst_rs, &mut dst_cs);
+            core::mem::swap(&mut lhs, &mut rhs);
+            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
+            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
+            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
+        }
+
+        epilogue_millikernel(
+            self,
+            m,
+            n,
+            k,
+            dst,
+            dst_rs,
+            dst_cs,
+            lhs,
+            lhs_rs,
+            lhs_cs,
+            rhs,
+            rhs_rs,
+            rhs_cs,
+            conj_lhs,
+            conj_rhs,
+            workspace as *mut T,
+            &mut |i, j, acc, old: T| {
+                let (i, j) = if transpose { (j, i) } else { (i, j) };
+                if conj_dst {
+                    epilogue(i, j, acc, old.conj()).conj()
+                } else {
+                    epilogue(i, j, acc, old)
+                }
+            },
+        );
+    }
+
+    // `map_or(true, ..)` rather than `is_none_or`, which would need rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    #[track_caller]
+    fn check_fused(&self, m: usize, n: usize, k: usize, fused: &FusedOps<'_, T>) {
+        if fused.is_empty() {
+            return;
+        }
+        equator::assert!(all(
+            self.triangle.is_none(),
+            self.lhs_structure.is_none(),
+            self.rhs_structure.is_none(),
+            self.dst_op != Op::ConjTrans
+                || (fused.row_scale.is_none()
+                    && fused.col_scale.is_none()
+                    && fused.k_scale.is_none()
+                    && fused.row_bias.is_none()
+                    && fused.col_bias.is_none()),
+            fused.row_scale.map_or(true, |scale| scale.len() == m),
+            fused.col_scale.map_or(true, |scale| scale.len() == n),
+            fused.k_scale.map_or(true, |scale| scale.len() == k),
+            fused.row_bias.map_or(true, |bias| bias.len() == m),
+            fused.col_bias.map_or(true, |bias| bias.len() == n),
+        ));
+    }
+
+    /// Checks that the operands, after applying the plan's operations, match each other and the
+    /// plan.
+    #[track_caller]
+    fn check_mats(&self, dst: &MatMut<'_, T>, lhs: &MatRef<'_, T>, rhs: &MatRef<'_, T>) {
+        let (dst_nrows, dst_ncols) = self.dst_op.dims(dst.nrows(), dst.ncols());
+        let (lhs_nrows, lhs_ncols) = self.lhs_op.dims(lhs.nrows(), lhs.ncols());
+        let (rhs_nrows, rhs_ncols) = self.rhs_op.dims(rhs.nrows(), rhs.ncols());
+        equator::assert!(all(
+            lhs_nrows == dst_nrows,
+            rhs_ncols == dst_ncols,
+            lhs_ncols == rhs_nrows,
+        ));
+        if let Err(err) = self.check_layout(
+            dst_nrows,
+            dst_ncols,
+            lhs_ncols,
+            dst.row_stride(),
+            dst.col_stride(),
+            lhs.row_stride(),
+            lhs.col_stride(),
+            rhs.row_stride(),
+            rhs.col_stride(),
+        ) {
+            panic!("{err}");
+        }
+    }
+
+    /// Computes `dst = alpha * dst + beta * lhs * rhs`, where `lhs` and `rhs` are optionally
+    /// conjugated, or `dst = beta * lhs * rhs` if `alpha` is zero.
+    ///
+    /// For plans created with [`Plan::new_with_ops`] or [`Plan::new_colmajor_with_ops`], the
+    /// operations are applied to the operands first.
+    ///
+    /// # Panics
+    /// Panics if the dimensions of the operands don't match the ones the plan was created with,
+    /// or if the plan was created with a layout that doesn't match the operand strides.
+    #[track_caller]
+    pub fn execute(
+        &self,
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+
+        unsafe {
+            self.execute_unchecked(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                alpha,
+                beta,
+                conj_lhs,
+                conj_rhs,
+            )
+        }
+    }
+
+    /// Same as [`Plan::execute`], but uses `workspace` for the temporary buffers instead of
+    /// allocating them on the stack.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Plan::execute`], or if `workspace` holds fewer than
+    /// [`Plan::workspace_size`] elements.
+    #[track_caller]
+    pub fn execute_with_workspace(
+        &self,
+        workspace: &mut [MaybeUninit<T>],
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+        equator::assert!(workspace.len() >= self.workspace_size);
+
+        unsafe {
+            self.execute_unchecked_with_workspace(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                alpha,
+                beta,
+                conj_lhs,
+                conj_rhs,
+                workspace.as_mut_ptr(),
+            )
+        }
+    }
+
+    /// Same as [`Plan::execute`], but combines the product with `dst` as described by
+    /// `accumulate`.
+    ///
+    /// # Panics
+    /// Panics if the dimensions of the operands don't match the ones the plan was created with,
+    /// or if the plan was created with a layout that doesn't match the operand strides.
+    #[track_caller]
+    pub fn execute_accumulate(
+        &self,
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        accumulate: Accumulate<T>,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+
+        unsafe {
+            self.execute_accumulate_unchecked(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                accumulate,
+                conj_lhs,
+                conj_rhs,
+            )
+        }
+    }
+
+    /// Same as [`Plan::execute`], but also applies the operations of `fused` while the result is
+    /// stored, as described in [`Plan::execute_fused_unchecked`].
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Plan::execute`] and
+    /// [`Plan::execute_fused_unchecked`].
+    #[track_caller]
+    pub fn execute_fused(
+        &self,
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        fused: &FusedOps<'_, T>,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+
+        unsafe {
+            self.execute_fused_unchecked(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                alpha,
+                beta,
+                conj_lhs,
+                conj_rhs,
+                fused,
+            )
+        }
+    }
+
+    /// Computes `dst = alpha * src + beta * lhs * rhs`, where `lhs` and `rhs` are optionally
+    /// conjugated, or `dst = beta * lhs * rhs` if `alpha` is zero. `src` is only read, and has the
+    /// same dimensions as `dst`.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Plan::execute`], if the dimensions of `src` don't
+    /// match the ones of `dst`, or if the plan was created with a triangular `dst` or a
+    /// structured operand.
+    #[track_caller]
+    pub fn execute_with_src(
+        &self,
+        dst: MatMut<'_, T>,
+        src: MatRef<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        alpha: T,
+        beta: T,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+        equator::assert!(all(src.nrows() == dst.nrows(), src.ncols() == dst.ncols()));
+
+        unsafe {
+            self.execute_with_src_unchecked(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                src.as_ptr(),
+                src.row_stride(),
+                src.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                alpha,
+                beta,
+                conj_lhs,
+                conj_rhs,
+            )
+        }
+    }
+
+    /// Computes `dst[i, j] = epilogue(i, j, (lhs * rhs)[i, j], dst[i, j])` for each element of
+    /// `dst`, where `lhs` and `rhs` are optionally conjugated.
+    ///
+    /// The product is computed by tiles in a local buffer, and its elements are passed to
+    /// `epilogue` before the tile is stored, so that it never goes through `dst` on its own. Each
+    /// element is visited once, in an unspecified order.
+    ///
+    /// For plans created with [`Plan::new_with_ops`] or [`Plan::new_colmajor_with_ops`], the
+    /// operations are applied to the operands first, and the indices and values given to
+    /// `epilogue` are the ones of `op(dst)`.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Plan::execute`], or if the plan was created with a
+    /// triangular `dst` or a structured operand.
+    #[track_caller]
+    pub fn execute_with_epilogue(
+        &self,
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        epilogue: impl FnMut(usize, usize, T, T) -> T,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+
+        unsafe {
+            self.execute_with_epilogue_unchecked(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                conj_lhs,
+                conj_rhs,
+                epilogue,
+            )
+        }
+    }
+
+    /// Same as [`Plan::execute_with_epilogue`], but uses `workspace` for the temporary buffers
+    /// instead of allocating them on the stack.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Plan::execute_with_epilogue`], or if `workspace`
+    /// holds fewer than [`Plan::epilogue_workspace_size`] elements.
+    #[track_caller]
+    pub fn execute_with_epilogue_with_workspace(
+        &self,
+        workspace: &mut [MaybeUninit<T>],
+        dst: MatMut<'_, T>,
+        lhs: MatRef<'_, T>,
+        rhs: MatRef<'_, T>,
+        conj_lhs: bool,
+        conj_rhs: bool,
+        epilogue: impl FnMut(usize, usize, T, T) -> T,
+    ) {
+        let mut dst = dst;
+        self.check_mats(&dst, &lhs, &rhs);
+        equator::assert!(workspace.len() >= self.epilogue_workspace_size());
+
+        unsafe {
+            self.execute_with_epilogue_unchecked_with_workspace(
+                self.m,
+                self.n,
+                self.k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                lhs.as_ptr(),
+                lhs.row_stride(),
+                lhs.col_stride(),
+                rhs.as_ptr(),
+                rhs.row_stride(),
+                rhs.col_stride(),
+                conj_lhs,
+                conj_rhs,
+                workspace.as_mut_ptr(),
+                epilogue,
+            )
+        }
+    }
+}
+
+impl Plan<f32> {
+    /// Creates a plan using the kernels of `isa`, or returns `None` if they're not available.
+    #[track_caller]
+    pub fn new_f32_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Self> {
+        if !isa.is_available() {
+            return None;
+        }
+
+        match isa {
+            Isa::Scalar => Some(Self::new_scalar(m, n, k, is_col_major)),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => {
+                let plan = if m == 1 {
+                    Self::new_f32x1(m, n, k, is_col_major)
+                } else if m == 2 {
+                    Self::new_f32x2(m, n, k, is_col_major)
+                } else if m <= 4 {
+                    Self::new_f32x4(m, n, k, is_col_major)
+                } else {
+                    Self::new_f32_avx(m, n, k, is_col_major)
+                };
+                Some(plan.with_avx_gemv())
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[cfg(feature = "nightly")]
+            Isa::Avx512 => Some(Self::new_f32_avx512(m, n, k, is_col_major).with_avx_gemv()),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => Some(Self::from_non_masked_impl(
+                &aarch64::f32::neon::MICROKERNELS,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Neon,
+            )),
+            _ => None,
+        }
+    }
+
+    // the AVX2 matrix-vector kernels are also used by the AVX-512 plans
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn with_avx_gemv(mut self) -> Self {
+        if self.n == 1 && self.m > 0 && self.k > 0 {
+            self.gemv = Some(GemvKernels {
+                colmajor: x86::f32::avx::gemv::gemv_colmajor,
+                rowmajor: x86::f32::avx::gemv::gemv_rowmajor,
+            });
+        }
+        self
+    }
+
+    #[track_caller]
+    pub fn new_f32_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+        let isa = match Isa::best() {
+            Isa::Avx512 if m <= 8 => Isa::Avx2,
+            isa => isa,
+        };
+        Self::new_f32_with_isa_impl(m, n, k, is_col_major, isa)
+            .unwrap_or_else(|| Self::new_scalar(m, n, k, is_col_major))
+    }
+
+    #[track_caller]
+    pub fn new_colmajor_lhs_and_dst_f32(m: usize, n: usize, k: usize) -> Self {
+        Self::new_f32_impl(m, n, k, true)
+    }
+
+    #[track_caller]
+    pub fn new_f32(m: usize, n: usize, k: usize) -> Self {
+        Self::new_f32_impl(m, n, k, false)
+    }
+}
+
+impl Plan<f64> {
+    /// Creates a plan using the kernels of `isa`, or returns `None` if they're not available.
+    #[track_caller]
+    pub fn new_f64_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Self> {
+        if !isa.is_available() {
+            return None;
+        }
+
+        match isa {
+            Isa::Scalar => Some(Self::new_scalar(m, n, k, is_col_major)),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => {
+                let plan = if m == 1 {
+                    Self::new_f64x1(m, n, k, is_col_major)
+                } else if m == 2 {
+                    Self::new_f64x2(m, n, k, is_col_major)
+                } else {
+                    Self::new_f64_avx(m, n, k, is_col_major)
+                };
+                Some(plan.with_avx_gemv())
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[cfg(feature = "nightly")]
+            Isa::Avx512 => Some(Self::new_f64_avx512(m, n, k, is_col_major).with_avx_gemv()),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => Some(Self::from_non_masked_impl(
+                &aarch64::f64::neon::MICROKERNELS,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Neon,
+            )),
+            _ => None,
+        }
+    }
+
+    // the AVX2 matrix-vector kernels are also used by the AVX-512 plans
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn with_avx_gemv(mut self) -> Self {
+        if self.n == 1 && self.m > 0 && self.k > 0 {
+            self.gemv = Some(GemvKernels {
+                colmajor: x86::f64::avx::gemv::gemv_colmajor,
+                rowmajor: x86::f64::avx::gemv::gemv_rowmajor,
+            });
+        }
+        self
+    }
+
+    #[track_caller]
+    pub fn new_f64_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+        let isa = match Isa::best() {
+            Isa::Avx512 if m <= 4 => Isa::Avx2,
+            isa => isa,
+        };
+        Self::new_f64_with_isa_impl(m, n, k, is_col_major, isa)
+            .unwrap_or_else(|| Self::new_scalar(m, n, k, is_col_major))
+    }
+
+    #[track_caller]
+    pub fn new_colmajor_lhs_and_dst_f64(m: usize, n: usize, k: usize) -> Self {
+        Self::new_f64_impl(m, n, k, true)
+    }
+
+    #[track_caller]
+    pub fn new_f64(m: usize, n: usize, k: usize) -> Self {
+        Self::new_f64_impl(m, n, k, false)
+    }
+}
+
+impl Plan<c32> {
+    /// Creates a plan using the kernels of `isa`, or returns `None` if they're not available.
+    #[track_caller]
+    pub fn new_c32_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Self> {
+        if !isa.is_available() {
+            return None;
+        }
+
+        match isa {
+            Isa::Scalar => Some(Self::new_scalar(m, n, k, is_col_major)),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => Some(if m == 1 {
+                Self::new_c32x1(m, n, k, is_col_major)
+            } else if m == 2 {
+                Self::new_c32x2(m, n, k, is_col_major)
+            } else {
+                Self::new_c32_avx(m, n, k, is_col_major)
+            }),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[cfg(feature = "nightly")]
+            Isa::Avx512 => Some(Self::new_c32_avx512(m, n, k, is_col_major)),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon if Isa::has_fcma() => Some(Self::from_non_masked_impl(
+                &aarch64::c32::neon::MICROKERNELS,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Neon,
+            )),
+            _ => None,
+        }
+    }
+
+    #[track_caller]
+    pub fn new_c32_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+        let isa = match Isa::best() {
+            Isa::Avx512 if m <= 4 => Isa::Avx2,
+            isa => isa,
+        };
+        Self::new_c32_with_isa_impl(m, n, k, is_col_major, isa)
+            .unwrap_or_else(|| Self::new_scalar(m, n, k, is_col_major))
+    }
+
+    #[track_caller]
+    pub fn new_colmajor_lhs_and_dst_c32(m: usize, n: usize, k: usize) -> Self {
+        Self::new_c32_impl(m, n, k, true)
+    }
+
+    #[track_caller]
+    pub fn new_c32(m: usize, n: usize, k: usize) -> Self {
+        Self::new_c32_impl(m, n, k, false)
+    }
+}
+
+impl Plan<c64> {
+    /// Creates a plan using the kernels of `isa`, or returns `None` if they're not available.
+    #[track_caller]
+    pub fn new_c64_with_isa_impl(
+        m: usize,
+        n: usize,
+        k: usize,
+        is_col_major: bool,
+        isa: Isa,
+    ) -> Option<Self> {
+        if !isa.is_available() {
+            return None;
+        }
+
+        match isa {
+            Isa::Scalar => Some(Self::new_scalar(m, n, k, is_col_major)),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => Some(if m == 1 {
+                Self::new_c64x1(m, n, k, is_col_major)
+            } else {
+                Self::new_c64_avx(m, n, k, is_col_major)
+            }),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[cfg(feature = "nightly")]
+            Isa::Avx512 => Some(Self::new_c64_avx512(m, n, k, is_col_major)),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon if Isa::has_fcma() => Some(Self::from_non_masked_impl(
+                &aarch64::c64::neon::MICROKERNELS,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Neon,
+            )),
+            _ => None,
+        }
+    }
+
+    #[track_caller]
+    pub fn new_c64_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+        let isa = match Isa::best() {
+            Isa::Avx512 if m <= 2 => Isa::Avx2,
+            isa => isa,
+        };
+        Self::new_c64_with_isa_impl(m, n, k, is_col_major, isa)
+            .unwrap_or_else(|| Self::new_scalar(m, n, k, is_col_major))
+    }
+
+    #[track_caller]
+    pub fn new_colmajor_lhs_and_dst_c64(m: usize, n: usize, k: usize) -> Self {
+        Self::new_c64_impl(m, n, k, true)
+    }
+
+    #[track_caller]
+    pub fn new_c64(m: usize, n: usize, k: usize) -> Self {
+        Self::new_c64_impl(m, n, k, false)
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86_api {
+    use super::*;
+
+    impl Plan<f32> {
+        pub fn new_f32x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f32::f32x1::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+        pub fn new_f32x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f32::f32x2::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+        pub fn new_f32x4(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f32::f32x4::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        pub fn new_f32_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f32::avx::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        #[cfg(feature = "nightly")]
+        pub fn new_f32_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f32::avx512::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx512,
+            )
+        }
+    }
+
+    impl Plan<f64> {
+        pub fn new_f64x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f64::f64x1::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+        pub fn new_f64x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f64::f64x2::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        pub fn new_f64_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f64::avx::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        #[cfg(feature = "nightly")]
+        pub fn new_f64_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::f64::avx512::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx512,
+            )
+        }
+    }
+    impl Plan<c32> {
+        pub fn new_c32x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c32::c32x1::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+        pub fn new_c32x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c32::c32x2::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        pub fn new_c32_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c32::avx::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        #[cfg(feature = "nightly")]
+        pub fn new_c32_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c32::avx512::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx512,
+            )
+        }
+    }
+    impl Plan<c64> {
+        pub fn new_c64x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c64::c64x1::*;
+            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
+                &MICROKERNELS,
+                None,
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        pub fn new_c64_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c64::avx::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx2,
+            )
+        }
+
+        #[cfg(feature = "nightly")]
+        pub fn new_c64_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
+            use x86::c64::avx512::*;
+            Self::from_masked_impl(
+                &MICROKERNELS,
+                Some(&MASKS),
+                m,
+                n,
+                k,
+                is_col_major,
+                Isa::Avx512,
+            )
+        }
+    }
+}
+
+pub mod planless {
+    use super::*;
+
+    /// Computes `dst = alpha * dst + beta * lhs * rhs`, where `lhs` and `rhs` are optionally
+    /// conjugated, or `dst = beta * lhs * rhs` if `alpha` is zero, with a plan created for the
+    /// given dimensions and strides.
+    ///
+    /// # Safety
+    /// `dst` must be valid for reads and writes, and `lhs` and `rhs` must be valid for reads, at
+    /// the offsets of all the elements described by the dimensions and strides. `dst` must not
+    /// overlap with `lhs` or `rhs`.
+    #[inline(always)]
+    pub unsafe fn execute<T: GemmScalar>(
+        mut m: usize,
+        mut n: usize,
+        k: usize,
+        mut dst: *mut T,
+        mut dst_rs: isize,
+        mut dst_cs: isize,
+        mut lhs: *const T,
+        mut lhs_rs: isize,
+        mut lhs_cs: isize,
+        mut rhs: *const T,
+        mut rhs_rs: isize,
+        mut rhs_cs: isize,
+        alpha: T,
+        beta: T,
+        mut conj_lhs: bool,
+        mut conj_rhs: bool,
+    ) {
+        if dst_cs.unsigned_abs() < dst_rs.unsigned_abs() {
+            core::mem::swap(&mut m, &mut n);
+            core::mem::swap(&mut dst_rs, &mut dst_cs);
+            core::mem::swap(&mut lhs, &mut rhs);
+            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
+            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
+            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
+        }
+        if dst_rs == -1 && m > 0 {
+            dst = dst.wrapping_offset((m - 1) as isize * dst_rs);
+            dst_rs = dst_rs.wrapping_neg();
+            lhs = lhs.wrapping_offset((m - 1) as isize * lhs_rs);
+            lhs_rs = lhs_rs.wrapping_neg();
+        }
+
+        let plan = if lhs_rs == 1 && dst_rs == 1 {
+            Plan::new_colmajor_lhs_and_dst(m, n, k)
+        } else {
+            Plan::new(m, n, k)
+        };
+        plan.execute_unchecked(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        )
+    }
+
+    /// Same as [`execute`], for `f32`.
+    ///
+    /// # Safety
+    /// Same as [`execute`].
+    #[inline(always)]
+    pub unsafe fn execute_f32(
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut f32,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const f32,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const f32,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: f32,
+        beta: f32,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        execute(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        )
+    }
+
+    /// Same as [`execute`], for `c32`.
+    ///
+    /// # Safety
+    /// Same as [`execute`].
+    #[inline(always)]
+    pub unsafe fn execute_c32(
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut c32,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const c32,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const c32,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: c32,
+        beta: c32,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        execute(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        )
+    }
+
+    /// Same as [`execute`], for `f64`.
+    ///
+    /// # Safety
+    /// Same as [`execute`].
+    #[inline(always)]
+    pub unsafe fn execute_f64(
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut f64,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const f64,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const f64,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: f64,
+        beta: f64,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        execute(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        )
+    }
+
+    /// Same as [`execute`], for `c64`.
+    ///
+    /// # Safety
+    /// Same as [`execute`].
+    #[inline(always)]
+    pub unsafe fn execute_c64(
+        m: usize,
+        n: usize,
+        k: usize,
+        dst: *mut c64,
+        dst_rs: isize,
+        dst_cs: isize,
+        lhs: *const c64,
+        lhs_rs: isize,
+        lhs_cs: isize,
+        rhs: *const c64,
+        rhs_rs: isize,
+        rhs_cs: isize,
+        alpha: c64,
+        beta: c64,
+        conj_lhs: bool,
+        conj_rhs: bool,
+    ) {
+        execute(
+            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
+            conj_lhs, conj_rhs,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use equator::assert;
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_kernel() {
+        let gen = |_| rand::random::<f32>();
+        let a: [[f32; 17]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let b: [[f32; 6]; 4] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let c: [[f32; 15]; 4] = core::array::from_fn(|_| core::array::from_fn(gen));
+        assert!(std::is_x86_feature_detected!("avx2"));
+        let mut dst = c;
+
+        let last_mask: std::arch::x86_64::__m256i = unsafe {
+            core::mem::transmute([
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                0,
+            ])
+        };
+
+        let beta = 2.5;
+        let alpha = 1.0;
+
+        unsafe {
+            x86::f32::avx::matmul_2_4_dyn(
+                &MicroKernelData {
+                    alpha,
+                    beta,
+                    read_dst: false,
+                    conj_lhs: false,
+                    conj_rhs: false,
+                    k: 3,
+                    dst_cs: dst[0].len() as isize,
+                    lhs_cs: a[0].len() as isize,
+                    rhs_rs: 2,
+                    rhs_cs: 6,
+                    last_mask: (&last_mask) as *const _ as *const (),
+                    src: core::ptr::null(),
+                    src_cs: 0,
+                    row_scale: core::ptr::null(),
+                    col_scale: core::ptr::null(),
+                    k_scale: core::ptr::null(),
+                    row_bias: core::ptr::null(),
+                    col_bias: core::ptr::null(),
+                    activation: Activation::None,
+                },
+                dst.as_mut_ptr() as *mut f32,
+                a.as_ptr() as *const f32,
+                b.as_ptr() as *const f32,
+            );
+        };
+
+        let mut expected_dst = c;
+        for i in 0..15 {
+            for j in 0..4 {
+                let mut acc = 0.0f32;
+                for depth in 0..3 {
+                    acc = f32::mul_add(a[depth][i], b[j][2 * depth], acc);
+                }
+                expected_dst[j][i] = f32::mul_add(beta, acc, expected_dst[j][i]);
+            }
+        }
+
+        assert!(dst == expected_dst);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_kernel_cplx() {
+        let gen = |_| rand::random::<c32>();
+        let a: [[c32; 9]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let b: [[c32; 6]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let c: [[c32; 7]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
+        assert!(std::is_x86_feature_detected!("avx2"));
+
+        let last_mask: std::arch::x86_64::__m256i = unsafe {
+            core::mem::transmute([
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                u32::MAX,
+                0,
+                0,
+            ])
+        };
+
+        let beta = c32::new(2.5, 3.5);
+        let alpha = c32::new(1.0, 0.0);
+
+        for (conj_lhs, conj_rhs) in [(false, false), (false, true), (true, false), (true, true)] {
+            let mut dst = c;
+            unsafe {
+                x86::c32::avx::matmul_2_2_dyn(
+                    &MicroKernelData {
+                        alpha,
+                        beta,
+                        read_dst: false,
+                        conj_lhs,
+                        conj_rhs,
+                        k: 3,
+                        dst_cs: dst[0].len() as isize,
+                        lhs_cs: a[0].len() as isize,
+                        rhs_rs: 2,
+                        rhs_cs: b[0].len() as isize,
+                        last_mask: (&last_mask) as *const _ as *const (),
+                        src: core::ptr::null(),
+                        src_cs: 0,
+                        row_scale: core::ptr::null(),
+                        col_scale: core::ptr::null(),
+                        k_scale: core::ptr::null(),
+                        row_bias: core::ptr::null(),
+                        col_bias: core::ptr::null(),
+                        activation: Activation::None,
+                    },
+                    dst.as_mut_ptr() as *mut c32,
+                    a.as_ptr() as *const c32,
+                    b.as_ptr() as *const c32,
+                );
+            };
+
+            let mut expected_dst = c;
+            for i in 0..7 {
+                for j in 0..2 {
+                    let mut acc = c32::new(0.0, 0.0);
+                    for depth in 0..3 {
+                        let mut a = a[depth][i];
+                        let mut b = b[j][2 * depth];
+                        if conj_lhs {
+                            a = a.conj();
+                        }
+                        if conj_rhs {
+                            b = b.conj();
+                        }
+                        acc += a * b;
+                    }
+                    expected_dst[j][i] += beta * acc;
+                }
+            }
+
+            for (&dst, &expected_dst) in
+                core::iter::zip(dst.iter().flatten(), expected_dst.iter().flatten())
+            {
+                assert!((dst.re - expected_dst.re).abs() < 1e-5);
+                assert!((dst.im - expected_dst.im).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_kernel_cplx64() {
+        let gen = |_| rand::random::<c64>();
+        let a: [[c64; 5]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let b: [[c64; 6]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
+        let c: [[c64; 3]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
+        assert!(std::is_x86_feature_detected!("avx2"));
+
+        let last_mask: std::arch::x86_64::__m256i =
+            unsafe { core::mem::transmute([u64::MAX, u64::MAX, 0, 0]) };
+
+        let beta = c64::new(2.5, 3.5);
+        let alpha = c64::new(1.0, 0.0);
+
+        for (conj_lhs, conj_rhs) in [(false, false), (false, true), (true, false), (true, true)] {
+            let mut dst = c;
+            unsafe {
+                x86::c64::avx::matmul_2_2_dyn(
+                    &MicroKernelData {
+                        alpha,
+                        beta,
+                        read_dst: false,
+                        conj_lhs,
+                        conj_rhs,
+                        k: 3,
+                        dst_cs: dst[0].len() as isize,
+                        lhs_cs: a[0].len() as isize,
+                        rhs_rs: 2,
+                        rhs_cs: b[0].len() as isize,
+                        last_mask: (&last_mask) as *const _ as *const (),
+                        src: core::ptr::null(),
+                        src_cs: 0,
+                        row_scale: core::ptr::null(),
+                        col_scale: core::ptr::null(),
+                        k_scale: core::ptr::null(),
+                        row_bias: core::ptr::null(),
+                        col_bias: core::ptr::null(),
+                        activation: Activation::None,
+                    },
+                    dst.as_mut_ptr() as *mut c64,
+                    a.as_ptr() as *const c64,
+                    b.as_ptr() as *const c64,
+                );
+            };
+
+            let mut expected_dst = c;
+            for i in 0..3 {
+                for j in 0..2 {
+                    let mut acc = c64::new(0.0, 0.0);
+                    for depth in 0..3 {
+                        let mut a = a[depth][i];
+                        let mut b = b[j][2 * depth];
+                        if conj_lhs {
+                            a = a.conj();
+                        }
+                        if conj_rhs {
+                            b = b.conj();
+                        }
+                        acc += a * b;
+                    }
+                    expected_dst[j][i] += beta * acc;
+                }
+            }
+
+            for (&dst, &expected_dst) in
+                core::iter::zip(dst.iter().flatten(), expected_dst.iter().flatten())
+            {
+                assert!((dst.re - expected_dst.re).abs() < 1e-5);
+                assert!((dst.im - expected_dst.im).abs() < 1e-5);
+            }
+        }
+    }
+    #[test]
+    fn test_plan() {
+        let gen = |_| rand::random::<f32>();
+        for ((m, n), k) in (64..=64).zip(64..=64).zip([1, 4, 64]) {
+            let a = (0..m * k).into_iter().map(gen).collect::<Vec<_>>();
+            let b = (0..k * n).into_iter().map(gen).collect::<Vec<_>>();
+            let c = (0..m * n).into_iter().map(|_| 0.0).collect::<Vec<_>>();
+            let mut dst = c.clone();
+
+            let plan = Plan::new_f32(m, n, k);
+            let beta = 2.5;
+
+            unsafe {
+                plan.execute_unchecked(
+                    m,
+                    n,
+                    k,
+                    dst.as_mut_ptr(),
+                    1,
+                    m as isize,
+                    a.as_ptr(),
+                    1,
+                    m as isize,
+                    b.as_ptr(),
+                    1,
+                    k as isize,
+                    1.0,
+                    beta,
+                    false,
+                    false,
+                );
+            };
+
+            let mut expected_dst = c;
+            for i in 0..m {
+                for j in 0..n {
+                    let mut acc = 0.0f32;
+                    for depth in 0..k {
+                        acc = f32::mul_add(a[depth * m + i], b[j * k + depth], acc);
+                    }
+                    expected_dst[j * m + i] = f32::mul_add(beta, acc, expected_dst[j * m + i]);
+                }
+            }
+
+            for (dst, expected_dst) in dst.iter().zip(&expected_dst) {
+                assert!((dst - expected_dst).abs() < 1e-4);
             }
+        }
+    }
 
-            if std::is_x86_feature_detected!("avx2") {
-                if m == 1 {
-                    return Self::new_f32x1(m, n, k, is_col_major);
+    #[test]
+    fn test_plan_safe() {
+        let gen = |_| rand::random::<f64>();
+        for ((m, n), k) in (0..40).zip(0..40).zip([1, 4, 17]) {
+            let a = (0..m * k).map(gen).collect::<Vec<_>>();
+            let b = (0..k * n).map(gen).collect::<Vec<_>>();
+            let c = (0..m * n).map(gen).collect::<Vec<_>>();
+
+            let alpha = 1.5;
+            let beta = 2.5;
+
+            let mut expected_dst = c.clone();
+            for i in 0..m {
+                for j in 0..n {
+                    let mut acc = 0.0;
+                    for depth in 0..k {
+                        acc += a[depth * m + i] * b[j * k + depth];
+                    }
+                    expected_dst[i * n + j] = alpha * expected_dst[i * n + j] + beta * acc;
                 }
-                if m == 2 {
-                    return Self::new_f32x2(m, n, k, is_col_major);
+            }
+
+            let mut dst = c.clone();
+            Plan::new_f64(m, n, k).execute(
+                MatMut::from_row_major_slice(&mut dst, m, n),
+                MatRef::from_col_major_slice(&a, m, k),
+                MatRef::from_col_major_slice(&b, k, n),
+                alpha,
+                beta,
+                false,
+                false,
+            );
+            for (dst, expected_dst) in dst.iter().zip(&expected_dst) {
+                assert!((dst - expected_dst).abs() < 1e-10);
+            }
+
+            let mut dst = vec![0.0; m * n];
+            for i in 0..m {
+                for j in 0..n {
+                    dst[j * m + i] = c[i * n + j];
                 }
-                if m <= 4 {
-                    return Self::new_f32x4(m, n, k, is_col_major);
+            }
+            Plan::new_colmajor_lhs_and_dst_f64(m, n, k).execute(
+                MatMut::from_col_major_slice(&mut dst, m, n),
+                MatRef::from_col_major_slice(&a, m, k),
+                MatRef::from_col_major_slice(&b, k, n),
+                alpha,
+                beta,
+                false,
+                false,
+            );
+            for i in 0..m {
+                for j in 0..n {
+                    assert!((dst[j * m + i] - expected_dst[i * n + j]).abs() < 1e-10);
                 }
-
-                return Self::new_f32_avx(m, n, k, is_col_major);
             }
         }
-        #[cfg(feature = "std")]
-        #[cfg(target_arch = "aarch64")]
-        {
-            if std::arch::is_aarch64_feature_detected!("neon") {
-                return Self::from_non_masked_impl(
-                    &aarch64::f32::neon::MICROKERNELS,
-                    m,
-                    n,
-                    k,
-                    is_col_major,
+    }
+
+    #[test]
+    fn test_plan_generic() {
+        fn check<T: GemmScalar + core::fmt::Debug>(
+            gen: impl Fn() -> T,
+            dist: impl Fn(T, T) -> f64,
+        ) {
+            for ((m, n), k) in (0..40).zip(0..40).zip([1, 4, 17]) {
+                let a = (0..m * k).map(|_| gen()).collect::<Vec<_>>();
+                let b = (0..k * n).map(|_| gen()).collect::<Vec<_>>();
+                let c = (0..m * n).map(|_| gen()).collect::<Vec<_>>();
+                let beta = gen();
+
+                let mut expected_dst = c.clone();
+                for i in 0..m {
+                    for j in 0..n {
+                        let mut acc = unsafe { core::mem::zeroed::<T>() };
+                        for depth in 0..k {
+                            acc = acc + a[depth * m + i] * b[j * k + depth];
+                        }
+                        expected_dst[j * m + i] = expected_dst[j * m + i] + beta * acc;
+                    }
+                }
+
+                let mut dst = c.clone();
+                Plan::<T>::new_colmajor_lhs_and_dst(m, n, k).execute(
+                    MatMut::from_col_major_slice(&mut dst, m, n),
+                    MatRef::from_col_major_slice(&a, m, k),
+                    MatRef::from_col_major_slice(&b, k, n),
+                    T::ONE,
+                    beta,
+                    false,
+                    false,
                 );
+                for (&dst, &expected_dst) in dst.iter().zip(&expected_dst) {
+                    assert!(dist(dst, expected_dst) < 1e-3);
+                }
+
+                let mut dst = c.clone();
+                unsafe {
+                    planless::execute::<T>(
+                        m,
+                        n,
+                        k,
+                        dst.as_mut_ptr(),
+                        1,
+                        m as isize,
+                        a.as_ptr(),
+                        1,
+                        m as isize,
+                        b.as_ptr(),
+                        1,
+                        k as isize,
+                        T::ONE,
+                        beta,
+                        false,
+                        false,
+                    )
+                };
+                for (&dst, &expected_dst) in dst.iter().zip(&expected_dst) {
+                    assert!(dist(dst, expected_dst) < 1e-3);
+                }
             }
         }
 
-        Self::new_f32_scalar(m, n, k, is_col_major)
+        check(rand::random::<f32>, |a, b| (a - b).abs() as f64);
+        check(rand::random::<f64>, |a, b| (a - b).abs());
+        check(rand::random::<c32>, |a, b| (a - b).norm() as f64);
+        check(rand::random::<c64>, |a, b| (a - b).norm());
     }
 
-    #[track_caller]
-    pub fn new_colmajor_lhs_and_dst_f32(m: usize, n: usize, k: usize) -> Self {
-        Self::new_f32_impl(m, n, k, true)
-    }
+    #[test]
+    fn test_plan_accumulate() {
+        let gen = |_| rand::random::<c64>();
+        for ((m, n), k) in (0..40).zip(0..40).zip([0, 1, 4, 17, 70]) {
+            let a = (0..m * k).map(gen).collect::<Vec<_>>();
+            let b = (0..k * n).map(gen).collect::<Vec<_>>();
+            let mut c = (0..2 * m * n).map(gen).collect::<Vec<_>>();
+            if m * n > 0 {
+                c[0] = c64::new(f64::NAN, 0.0);
+                c[2 * m * n - 1] = c64::new(0.0, f64::INFINITY);
+            }
 
-    #[track_caller]
-    pub fn new_f32(m: usize, n: usize, k: usize) -> Self {
-        Self::new_f32_impl(m, n, k, false)
+            for accumulate in [
+                Accumulate::Overwrite,
+                Accumulate::Add,
+                Accumulate::Scale {
+                    dst_scale: c64::new(0.0, 0.0),
+                    prod_scale: c64::new(2.5, -1.0),
+                },
+                Accumulate::Scale {
+                    dst_scale: c64::new(1.5, 0.5),
+                    prod_scale: c64::new(0.0, 1.0),
+                },
+            ] {
+                for colmajor in [false, true] {
+                    // column-major plans write to a contiguous dst, otherwise every other row is
+                    // skipped
+                    let dst_rs = if colmajor { 1 } else { 2 };
+                    let plan = if colmajor {
+                        Plan::new_colmajor_lhs_and_dst_c64(m, n, k)
+                    } else {
+                        Plan::new_c64(m, n, k)
+                    };
+
+                    let mut dst = c.clone();
+                    plan.execute_accumulate(
+                        MatMut::from_slice(&mut dst, m, n, dst_rs, 2 * m as isize),
+                        MatRef::from_col_major_slice(&a, m, k),
+                        MatRef::from_col_major_slice(&b, k, n),
+                        accumulate,
+                        false,
+                        true,
+                    );
+
+                    for i in 0..m {
+                        for j in 0..n {
+                            let mut acc = c64::ZERO;
+                            for depth in 0..k {
+                                acc += a[depth * m + i] * b[j * k + depth].conj();
+                            }
+                            let idx = i * dst_rs as usize + j * 2 * m;
+                            let old = c[idx];
+                            let expected = match accumulate {
+                                Accumulate::Overwrite => acc,
+                                Accumulate::Add => old + acc,
+                                Accumulate::Scale {
+                                    dst_scale,
+                                    prod_scale,
+                                } => dst_scale * old + prod_scale * acc,
+                            };
+                            let dst = dst[idx];
+
+                            if accumulate == Accumulate::Overwrite {
+                                assert!(dst.is_finite());
+                            }
+                            if expected.is_finite() {
+                                assert!((dst - expected).norm() < 1e-8);
+                            } else {
+                                assert!(!dst.is_finite());
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
-}
 
-impl Plan<f64> {
-    #[track_caller]
-    pub fn new_f64_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        #[cfg(feature = "std")]
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            #[cfg(feature = "nightly")]
-            if m > 4 && std::is_x86_feature_detected!("avx512f") {
-                return Self::new_f64_avx512(m, n, k, is_col_major);
+    #[test]
+    fn test_plan_ops() {
+        let gen = |_| rand::random::<c64>();
+        let ops = [Op::NoTrans, Op::Trans, Op::ConjTrans];
+        // returns `op(mat)[(i, j)]` for a column-major matrix with `nrows` rows
+        let get = |mat: &[c64], nrows: usize, op: Op, i: usize, j: usize| match op {
+            Op::NoTrans => mat[j * nrows + i],
+            Op::Trans => mat[i * nrows + j],
+            Op::ConjTrans => mat[i * nrows + j].conj(),
+        };
+
+        for (m, n, k) in [(3, 5, 2), (9, 1, 7), (17, 13, 4), (40, 33, 9)] {
+            for (lhs_op, rhs_op, dst_op) in ops
+                .into_iter()
+                .flat_map(|x| ops.into_iter().flat_map(move |y| ops.map(|z| (x, y, z))))
+            {
+                let (lhs_nrows, lhs_ncols) = lhs_op.dims(m, k);
+                let (rhs_nrows, rhs_ncols) = rhs_op.dims(k, n);
+                let (dst_nrows, dst_ncols) = dst_op.dims(m, n);
+
+                let a = (0..m * k).map(gen).collect::<Vec<_>>();
+                let b = (0..k * n).map(gen).collect::<Vec<_>>();
+                let c = (0..m * n).map(gen).collect::<Vec<_>>();
+                let alpha = c64::new(1.5, -0.5);
+                let beta = c64::new(0.5, 2.0);
+
+                for colmajor in [false, true] {
+                    let plan = if colmajor {
+                        Plan::new_colmajor_with_ops(m, n, k, lhs_op, rhs_op, dst_op)
+                    } else {
+                        Plan::new_with_ops(m, n, k, lhs_op, rhs_op, dst_op)
+                    };
+
+                    let mut dst = c.clone();
+                    plan.execute(
+                        MatMut::from_col_major_slice(&mut dst, dst_nrows, dst_ncols),
+                        MatRef::from_col_major_slice(&a, lhs_nrows, lhs_ncols),
+                        MatRef::from_col_major_slice(&b, rhs_nrows, rhs_ncols),
+                        alpha,
+                        beta,
+                        false,
+                        true,
+                    );
+
+                    for i in 0..m {
+                        for j in 0..n {
+                            let mut acc = c64::ZERO;
+                            for depth in 0..k {
+                                acc += get(&a, lhs_nrows, lhs_op, i, depth)
+                                    * get(&b, rhs_nrows, rhs_op, depth, j).conj();
+                            }
+                            let expected = alpha * get(&c, dst_nrows, dst_op, i, j) + beta * acc;
+                            let dst = get(&dst, dst_nrows, dst_op, i, j);
+                            assert!((dst - expected).norm() < 1e-8);
+                        }
+                    }
+                }
             }
+        }
+    }
+
+    #[test]
+    fn test_plan_builder() {
+        let gen = |_| rand::random::<f64>();
+        for (m, n, k) in [(1, 1, 1), (3, 5, 2), (9, 1, 7), (17, 13, 4), (40, 33, 9)] {
+            let a = (0..m * k).map(gen).collect::<Vec<_>>();
+            let b = (0..k * n).map(gen).collect::<Vec<_>>();
+
+            for (row_major_dst, row_major_lhs, row_major_rhs) in [
+                (false, false, false),
+                (true, true, true),
+                (true, false, true),
+                (false, true, false),
+            ] {
+                let layout = |row_major: bool, nrows: usize, ncols: usize| {
+                    if row_major {
+                        (ncols as isize, 1)
+                    } else {
+                        (1, nrows as isize)
+                    }
+                };
+                let (dst_rs, dst_cs) = layout(row_major_dst, m, n);
+                let (lhs_rs, lhs_cs) = layout(row_major_lhs, m, k);
+                let (rhs_rs, rhs_cs) = layout(row_major_rhs, k, n);
+
+                let plan: Plan<f64> = PlanBuilder::new(m, n, k)
+                    .dst_rs(dst_rs)
+                    .dst_cs(dst_cs)
+                    .lhs_rs(lhs_rs)
+                    .lhs_cs(lhs_cs)
+                    .rhs_rs(rhs_rs)
+                    .rhs_cs(rhs_cs)
+                    .build();
+
+                let mut dst = vec![0.0; m * n];
+                plan.execute(
+                    MatMut::from_slice(&mut dst, m, n, dst_rs, dst_cs),
+                    MatRef::from_slice(&a, m, k, lhs_rs, lhs_cs),
+                    MatRef::from_slice(&b, k, n, rhs_rs, rhs_cs),
+                    0.0,
+                    1.0,
+                    false,
+                    false,
+                );
 
-            if std::is_x86_feature_detected!("avx2") {
-                if m == 1 {
-                    return Self::new_f64x1(m, n, k, is_col_major);
+                for i in 0..m {
+                    for j in 0..n {
+                        let mut acc = 0.0;
+                        for depth in 0..k {
+                            acc += a[i * lhs_rs as usize + depth * lhs_cs as usize]
+                                * b[depth * rhs_rs as usize + j * rhs_cs as usize];
+                        }
+                        let dst = dst[i * dst_rs as usize + j * dst_cs as usize];
+                        assert!((dst - acc).abs() < 1e-10);
+                    }
                 }
-                if m == 2 {
-                    return Self::new_f64x2(m, n, k, is_col_major);
+
+                if m > 1 {
+                    let mut dst = vec![0.0; 2 * m * n];
+                    let err = unsafe {
+                        plan.try_execute(
+                            m,
+                            n,
+                            k,
+                            dst.as_mut_ptr(),
+                            2 * dst_rs,
+                            2 * dst_cs,
+                            a.as_ptr(),
+                            lhs_rs,
+                            lhs_cs,
+                            b.as_ptr(),
+                            rhs_rs,
+                            rhs_cs,
+                            0.0,
+                            1.0,
+                            false,
+                            false,
+                        )
+                    };
+                    assert!(matches!(
+                        err,
+                        Err(PlanError::StrideMismatch {
+                            stride: "dst_rs",
+                            ..
+                        })
+                    ));
                 }
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_cache() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Plan<f32>>();
+        assert_send_sync::<Plan<c64>>();
+        assert_send_sync::<PlanCache>();
+
+        let cache = PlanCache::new();
+        let (m, n, k) = (7, 5, 3);
+        let a = (0..m * k).map(|x| x as f64).collect::<Vec<_>>();
+        let b = (0..k * n).map(|x| x as f64).collect::<Vec<_>>();
+        let builder = PlanBuilder::new(m, n, k).dst_rs(1).lhs_rs(1);
+
+        let results = std::thread::scope(|s| {
+            let handles = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let plan = cache.get::<f64>(&builder);
+                        let mut dst = vec![0.0; m * n];
+                        plan.execute(
+                            MatMut::from_col_major_slice(&mut dst, m, n),
+                            MatRef::from_col_major_slice(&a, m, k),
+                            MatRef::from_col_major_slice(&b, k, n),
+                            0.0,
+                            1.0,
+                            false,
+                            false,
+                        );
+                        dst
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
 
-                return Self::new_f64_avx(m, n, k, is_col_major);
+        for i in 0..m {
+            for j in 0..n {
+                let mut acc = 0.0;
+                for depth in 0..k {
+                    acc += a[depth * m + i] * b[j * k + depth];
+                }
+                for dst in &results {
+                    assert!(dst[j * m + i] == acc);
+                }
             }
         }
+        assert!(cache.len() == 1);
 
-        #[cfg(feature = "std")]
-        #[cfg(target_arch = "aarch64")]
-        {
-            if std::arch::is_aarch64_feature_detected!("neon") {
-                return Self::from_non_masked_impl(
-                    &aarch64::f64::neon::MICROKERNELS,
-                    m,
-                    n,
-                    k,
-                    is_col_major,
+        cache.get::<f32>(&builder);
+        cache.get::<f64>(&builder.dst_op(Op::Trans));
+        assert!(cache.len() == 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        PlanCache::global().get::<c32>(&builder);
+        assert!(!PlanCache::global().is_empty());
+    }
+
+    #[test]
+    fn test_plan_isa() {
+        fn check<T: GemmScalar + core::fmt::Debug>(
+            gen: impl Fn(usize) -> T,
+            tol: impl Fn(T, T) -> bool,
+        ) {
+            for (m, n, k) in [(1, 1, 1), (2, 3, 5), (4, 7, 2), (13, 9, 17), (33, 20, 40)] {
+                let a = (0..m * k).map(&gen).collect::<Vec<_>>();
+                let b = (0..k * n).map(&gen).collect::<Vec<_>>();
+
+                let mut expected = (0..m * n).map(&gen).collect::<Vec<_>>();
+                let scalar = Plan::<T>::new_with_isa(m, n, k, Isa::Scalar).unwrap();
+                assert!(scalar.isa() == Isa::Scalar);
+                scalar.execute(
+                    MatMut::from_col_major_slice(&mut expected, m, n),
+                    MatRef::from_col_major_slice(&a, m, k),
+                    MatRef::from_col_major_slice(&b, k, n),
+                    T::ONE,
+                    T::ONE,
+                    false,
+                    false,
                 );
+
+                for isa in [Isa::Scalar, Isa::Avx2, Isa::Avx512, Isa::Neon] {
+                    let plan = match Plan::<T>::new_with_isa(m, n, k, isa) {
+                        Ok(plan) => plan,
+                        Err(err) => {
+                            assert!(err == PlanError::UnsupportedIsa { isa });
+                            continue;
+                        }
+                    };
+                    assert!(isa.is_available());
+                    assert!(plan.isa() == isa);
+
+                    let mut dst = (0..m * n).map(&gen).collect::<Vec<_>>();
+                    plan.execute(
+                        MatMut::from_col_major_slice(&mut dst, m, n),
+                        MatRef::from_col_major_slice(&a, m, k),
+                        MatRef::from_col_major_slice(&b, k, n),
+                        T::ONE,
+                        T::ONE,
+                        false,
+                        false,
+                    );
+                    for (&dst, &expected) in core::iter::zip(&dst, &expected) {
+                        assert!(tol(dst, expected));
+                    }
+                }
             }
         }
 
-        Self::new_f64_scalar(m, n, k, is_col_major)
+        // the same seed is used for every call, so that the initial `dst` matches
+        let seeded = |seed: usize| move |i: usize| ((i * 7 + seed) % 13) as f64 - 6.0;
+        let f = seeded(0);
+        check(|i| f(i) as f32, |x: f32, y| (x - y).abs() < 1e-3);
+        check(f, |x: f64, y| (x - y).abs() < 1e-10);
+        let g = seeded(5);
+        check(
+            |i| c32::new(f(i) as f32, g(i) as f32),
+            |x: c32, y| (x - y).norm() < 1e-3,
+        );
+        check(|i| c64::new(f(i), g(i)), |x: c64, y| (x - y).norm() < 1e-10);
+
+        assert!(Isa::best().is_available());
+        let isa = Plan::new_f64(64, 64, 64).isa();
+        assert!(isa == Isa::best() || isa == Isa::Scalar);
     }
 
-    #[track_caller]
-    pub fn new_colmajor_lhs_and_dst_f64(m: usize, n: usize, k: usize) -> Self {
-        Self::new_f64_impl(m, n, k, true)
+    #[test]
+    fn test_plan_workspace() {
+        let gen = |_| rand::random::<f64>();
+        for (m, n, k) in [(3, 4, 5), (17, 9, 70), (100, 70, 130)] {
+            // row-major operands, so that the copy path is used
+            let a = (0..m * k).map(gen).collect::<Vec<_>>();
+            let b = (0..k * n).map(gen).collect::<Vec<_>>();
+            let c = (0..m * n).map(gen).collect::<Vec<_>>();
+
+            assert!(Plan::<f64>::new_colmajor_lhs_and_dst(m, n, k).workspace_size() == 0);
+            let plan = Plan::<f64>::new(m, n, k);
+            let mut workspace = vec![MaybeUninit::uninit(); plan.workspace_size()];
+
+            let mut expected = c.clone();
+            for i in 0..m {
+                for j in 0..n {
+                    let mut acc = 0.0;
+                    for depth in 0..k {
+                        acc += a[i * k + depth] * b[depth * n + j];
+                    }
+                    expected[i * n + j] = 0.5 * c[i * n + j] + 2.0 * acc;
+                }
+            }
+
+            for use_workspace in [false, true] {
+                let mut dst = c.clone();
+                let dst_view = MatMut::from_row_major_slice(&mut dst, m, n);
+                let lhs = MatRef::from_row_major_slice(&a, m, k);
+                let rhs = MatRef::from_row_major_slice(&b, k, n);
+                if use_workspace {
+                    plan.execute_with_workspace(
+                        &mut workspace,
+                        dst_view,
+                        lhs,
+                        rhs,
+                        0.5,
+                        2.0,
+                        false,
+                        false,
+                    );
+                } else {
+                    plan.execute(dst_view, lhs, rhs, 0.5, 2.0, false, false);
+                }
+
+                for (&dst, &expected) in core::iter::zip(&dst, &expected) {
+                    assert!((dst - expected).abs() < 1e-10);
+                }
+            }
+        }
     }
 
-    #[track_caller]
-    pub fn new_f64(m: usize, n: usize, k: usize) -> Self {
-        Self::new_f64_impl(m, n, k, false)
+    #[test]
+    fn test_plan_batched() {
+        let gen = |_| rand::random::<c32>();
+        let batch = 9;
+        for (m, n, k) in [
+            (0, 3, 2),
+            (3, 4, 0),
+            (2, 3, 4),
+            (5, 7, 3),
+            (19, 17, 9),
+            (70, 3, 70),
+        ] {
+            for (colmajor, dst_op) in [(true, Op::NoTrans), (false, Op::NoTrans), (true, Op::Trans)]
+            {
+                // the rhs is shared by every problem
+                let (dst_bs, lhs_bs, rhs_bs) = (m * n + 1, m * k + 2, 0);
+                let a = (0..batch * lhs_bs).map(gen).collect::<Vec<_>>();
+                let b = (0..k * n).map(gen).collect::<Vec<_>>();
+                let c = (0..batch * dst_bs).map(gen).collect::<Vec<_>>();
+
+                let (dst_rs, dst_cs) = if dst_op == Op::NoTrans {
+                    (1, m as isize)
+                } else {
+                    (n as isize, 1)
+                };
+                let lhs_rs = if colmajor { 1 } else { k as isize };
+                let lhs_cs = if colmajor { m as isize } else { 1 };
+                let plan = PlanBuilder::new(m, n, k)
+                    .dst_op(dst_op)
+                    .lhs_rs(lhs_rs)
+                    .build::<c32>();
+
+                let alpha = c32::new(0.5, 1.0);
+                let beta = c32::new(-1.0, 2.0);
+                let mut dst = c.clone();
+                let dst_view_rs = if dst_op == Op::NoTrans {
+                    dst_rs
+                } else {
+                    dst_cs
+                };
+                let dst_view_cs = if dst_op == Op::NoTrans {
+                    dst_cs
+                } else {
+                    dst_rs
+                };
+                unsafe {
+                    plan.execute_batched(
+                        batch,
+                        m,
+                        n,
+                        k,
+                        dst.as_mut_ptr(),
+                        dst_bs as isize,
+                        dst_view_rs,
+                        dst_view_cs,
+                        a.as_ptr(),
+                        lhs_bs as isize,
+                        lhs_rs,
+                        lhs_cs,
+                        b.as_ptr(),
+                        rhs_bs as isize,
+                        1,
+                        k as isize,
+                        alpha,
+                        beta,
+                        true,
+                        false,
+                    );
+                }
+
+                for p in 0..batch {
+                    for i in 0..m {
+                        for j in 0..n {
+                            let mut acc = c32::ZERO;
+                            for depth in 0..k {
+                                let lhs = a[p * lhs_bs
+                                    + (i as isize * lhs_rs + depth as isize * lhs_cs) as usize];
+                                acc += lhs.conj() * b[j * k + depth];
+                            }
+                            let idx =
+                                p * dst_bs + (i as isize * dst_rs + j as isize * dst_cs) as usize;
+                            let expected = alpha * c[idx] + beta * acc;
+                            assert!((dst[idx] - expected).norm() < 1e-3);
+                        }
+                    }
+                    // the padding between problems is untouched
+                    assert!(dst[p * dst_bs + m * n] == c[p * dst_bs + m * n]);
+                }
+            }
+        }
     }
-}
 
-impl Plan<c32> {
-    #[track_caller]
-    pub fn new_c32_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        #[cfg(feature = "std")]
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            #[cfg(feature = "nightly")]
-            if m > 4 && std::is_x86_feature_detected!("avx512f") {
-                return Self::new_c32_avx512(m, n, k, is_col_major);
+    #[test]
+    fn test_plan_ptr_batched() {
+        let gen = |_| rand::random::<f32>();
+        for (m, n, k) in [(1, 2, 3), (6, 5, 4), (33, 17, 20)] {
+            // every matrix lives in its own allocation
+            let a = (0..5)
+                .map(|_| (0..m * k).map(gen).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let b = (0..5)
+                .map(|_| (0..k * n).map(gen).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let mut c = (0..5)
+                .map(|_| (0..m * n).map(gen).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let c_old = c.clone();
+
+            let plan = Plan::new_colmajor_lhs_and_dst_f32(m, n, k);
+            let dst = c.iter_mut().map(|c| c.as_mut_ptr()).collect::<Vec<_>>();
+            let lhs = a.iter().map(|a| a.as_ptr()).collect::<Vec<_>>();
+            let rhs = b.iter().map(|b| b.as_ptr()).collect::<Vec<_>>();
+            unsafe {
+                plan.execute_ptr_batched(
+                    m, n, k, &dst, 1, m as isize, &lhs, 1, m as isize, &rhs, 1, k as isize, 1.0,
+                    2.0, false, false,
+                );
+            }
+
+            for p in 0..5 {
+                for i in 0..m {
+                    for j in 0..n {
+                        let mut acc = 0.0;
+                        for depth in 0..k {
+                            acc += a[p][depth * m + i] * b[p][j * k + depth];
+                        }
+                        let expected = c_old[p][j * m + i] + 2.0 * acc;
+                        assert!((c[p][j * m + i] - expected).abs() < 1e-3);
+                    }
+                }
             }
+        }
+    }
+
+    #[test]
+    fn test_plan_ptr_batched_matches_strided() {
+        fn check<T: TestScalar>() {
+            let batch = 4;
+            for (m, n, k) in [(1, 1, 1), (3, 5, 0), (9, 1, 7), (17, 13, 16), (70, 9, 150)] {
+                let a = random_vec::<T>(batch * m * k);
+                let b = random_vec::<T>(k * n);
+                let c = random_vec::<T>(batch * m * n);
+                let (alpha, beta) = (T::random(), T::random());
+
+                // column-major, and row-major `dst` with a shared `rhs`
+                for colmajor in [true, false] {
+                    let plan = if colmajor {
+                        Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                    } else {
+                        Plan::<T>::new(m, n, k)
+                    };
+                    let (dst_rs, dst_cs) = if colmajor {
+                        (1, m as isize)
+                    } else {
+                        (n as isize, 1)
+                    };
+
+                    let mut strided = c.clone();
+                    let mut ptrs = c.clone();
+                    let dst = (0..batch)
+                        .map(|p| ptrs[p * m * n..].as_mut_ptr())
+                        .collect::<Vec<_>>();
+                    let lhs = (0..batch)
+                        .map(|p| a[p * m * k..].as_ptr())
+                        .collect::<Vec<_>>();
+                    let rhs = vec![b.as_ptr(); batch];
+                    unsafe {
+                        plan.execute_batched(
+                            batch,
+                            m,
+                            n,
+                            k,
+                            strided.as_mut_ptr(),
+                            (m * n) as isize,
+                            dst_rs,
+                            dst_cs,
+                            a.as_ptr(),
+                            (m * k) as isize,
+                            1,
+                            m as isize,
+                            b.as_ptr(),
+                            0,
+                            1,
+                            k as isize,
+                            alpha,
+                            beta,
+                            false,
+                            true,
+                        );
+                        plan.execute_ptr_batched(
+                            m, n, k, &dst, dst_rs, dst_cs, &lhs, 1, m as isize, &rhs, 1,
+                            k as isize, alpha, beta, false, true,
+                        );
+                    }
 
-            if std::is_x86_feature_detected!("avx2") {
-                if m == 1 {
-                    return Self::new_c32x1(m, n, k, is_col_major);
-                }
-                if m == 2 {
-                    return Self::new_c32x2(m, n, k, is_col_major);
+                    for (x, y) in strided.iter().zip(&ptrs) {
+                        assert!((*x - *y).abs2().sqrt() < 1e-3);
+                    }
                 }
+            }
+        }
+
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
+    }
+
+    #[test]
+    fn test_grouped_gemm() {
+        let gen = |_| rand::random::<f64>();
+        let shapes = [
+            (4, 3, 2),
+            (17, 5, 9),
+            (4, 3, 2),
+            (1, 1, 1),
+            (17, 5, 9),
+            (30, 2, 64),
+        ];
+
+        for sort_by_shape in [false, true] {
+            let mut group = GroupedGemm::<f64>::new().sort_by_shape(sort_by_shape);
+            let a = shapes.map(|(m, _, k)| (0..m * k).map(gen).collect::<Vec<_>>());
+            let b = shapes.map(|(_, n, k)| (0..k * n).map(gen).collect::<Vec<_>>());
+            let c = shapes.map(|(m, n, _)| (0..m * n).map(gen).collect::<Vec<_>>());
+
+            for _ in 0..2 {
+                let mut dst = c.clone();
+                let problems = core::array::from_fn::<_, 6, _>(|p| {
+                    let (m, n, k) = shapes[p];
+                    // odd problems use a row-major dst
+                    let (dst_rs, dst_cs) = if p % 2 == 0 {
+                        (1, m as isize)
+                    } else {
+                        (n as isize, 1)
+                    };
+                    GemmProblem {
+                        m,
+                        n,
+                        k,
+                        dst: dst[p].as_mut_ptr(),
+                        dst_rs,
+                        dst_cs,
+                        lhs: a[p].as_ptr(),
+                        lhs_rs: 1,
+                        lhs_cs: m as isize,
+                        rhs: b[p].as_ptr(),
+                        rhs_rs: 1,
+                        rhs_cs: k as isize,
+                        alpha: 2.0,
+                        beta: p as f64,
+                        conj_lhs: false,
+                        conj_rhs: false,
+                    }
+                });
+                unsafe { group.execute(&problems) };
 
-                return Self::new_c32_avx(m, n, k, is_col_major);
+                for (p, &(m, n, k)) in shapes.iter().enumerate() {
+                    let problem = &problems[p];
+                    for i in 0..m {
+                        for j in 0..n {
+                            let mut acc = 0.0;
+                            for depth in 0..k {
+                                acc += a[p][depth * m + i] * b[p][j * k + depth];
+                            }
+                            let idx = (i as isize * problem.dst_rs + j as isize * problem.dst_cs)
+                                as usize;
+                            let expected = 2.0 * c[p][idx] + p as f64 * acc;
+                            assert!((dst[p][idx] - expected).abs() < 1e-10);
+                        }
+                    }
+                }
             }
+            // one plan per distinct shape and layout
+            assert!(group.plan_count() == 5);
         }
+    }
 
-        #[cfg(feature = "std")]
-        #[cfg(target_arch = "aarch64")]
-        {
-            if std::arch::is_aarch64_feature_detected!("neon")
-                && std::arch::is_aarch64_feature_detected!("fcma")
-            {
-                return Self::from_non_masked_impl(
-                    &aarch64::c32::neon::MICROKERNELS,
-                    m,
-                    n,
-                    k,
-                    is_col_major,
-                );
+    #[test]
+    fn test_plan_gemv() {
+        fn check<T: GemmScalar + Into<f64> + From<f32>>(gen: impl Fn(usize) -> T) {
+            for m in [1, 3, 4, 17, 70] {
+                for k in [1, 5, 33] {
+                    // column major and row major, with and without padding, and strided `lhs` that
+                    // falls back to the millikernel
+                    for (lhs_rs, lhs_cs) in [(1, m), (1, 2 * m), (k, 1), (2 * k, 1), (2, 2 * m)] {
+                        for (dst_rs, rhs_rs) in [(1, 1), (2, 3)] {
+                            let a = (0..2 * m * k).map(&gen).collect::<Vec<_>>();
+                            let b = (0..3 * k).map(&gen).collect::<Vec<_>>();
+                            let c = (0..2 * m).map(&gen).collect::<Vec<_>>();
+
+                            for isa in [Isa::Scalar, Isa::Avx2, Isa::Avx512, Isa::Neon] {
+                                let Ok(plan) = Plan::<T>::new_with_isa(m, 1, k, isa) else {
+                                    continue;
+                                };
+                                for alpha in [0.0f32, 1.0, 2.5] {
+                                    let alpha = T::from(alpha);
+                                    let beta = T::from(-1.5);
+                                    let mut dst = c.clone();
+                                    if alpha == T::from(0.0) {
+                                        // `dst` must not be read
+                                        for i in 0..m {
+                                            dst[i * dst_rs] = T::from(f32::NAN);
+                                        }
+                                    }
+                                    unsafe {
+                                        plan.execute_unchecked(
+                                            m,
+                                            1,
+                                            k,
+                                            dst.as_mut_ptr(),
+                                            dst_rs as isize,
+                                            m as isize,
+                                            a.as_ptr(),
+                                            lhs_rs as isize,
+                                            lhs_cs as isize,
+                                            b.as_ptr(),
+                                            rhs_rs as isize,
+                                            k as isize,
+                                            alpha,
+                                            beta,
+                                            false,
+                                            false,
+                                        );
+                                    }
+
+                                    for i in 0..m {
+                                        let mut acc = 0.0f64;
+                                        for depth in 0..k {
+                                            acc += a[i * lhs_rs + depth * lhs_cs].into()
+                                                * b[depth * rhs_rs].into();
+                                        }
+                                        let expected = if alpha == T::from(0.0) {
+                                            0.0
+                                        } else {
+                                            alpha.into() * c[i * dst_rs].into()
+                                        } + beta.into() * acc;
+                                        let found: f64 = dst[i * dst_rs].into();
+                                        assert!((found - expected).abs() < 1e-3);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
 
-        Self::new_c32_scalar(m, n, k, is_col_major)
+        check(|_| rand::random::<f32>());
+        check(|_| rand::random::<f64>());
     }
 
-    #[track_caller]
-    pub fn new_colmajor_lhs_and_dst_c32(m: usize, n: usize, k: usize) -> Self {
-        Self::new_c32_impl(m, n, k, true)
-    }
+    #[test]
+    fn test_plan_gemmt() {
+        let gen = |_| rand::random::<f32>();
+        for n in [1, 5, 16, 33, 70] {
+            for k in [1, 4, 17] {
+                for triangle in [Triangle::Lower, Triangle::Upper] {
+                    for (rowmajor_dst, dst_op) in [
+                        (false, Op::NoTrans),
+                        (true, Op::NoTrans),
+                        (false, Op::Trans),
+                    ] {
+                        let a = (0..n * k).map(gen).collect::<Vec<_>>();
+                        let b = (0..k * n).map(gen).collect::<Vec<_>>();
+                        let c = (0..n * n).map(gen).collect::<Vec<_>>();
+                        let (dst_rs, dst_cs) = if rowmajor_dst {
+                            (n as isize, 1)
+                        } else {
+                            (1, n as isize)
+                        };
 
-    #[track_caller]
-    pub fn new_c32(m: usize, n: usize, k: usize) -> Self {
-        Self::new_c32_impl(m, n, k, false)
-    }
-}
+                        let plan = if (rowmajor_dst, dst_op) == (false, Op::NoTrans) {
+                            Plan::<f32>::new_with_triangle(n, k, triangle)
+                        } else {
+                            PlanBuilder::new(n, n, k)
+                                .dst_rs(dst_rs)
+                                .dst_cs(dst_cs)
+                                .dst_op(dst_op)
+                                .dst_triangle(triangle)
+                                .build()
+                        };
+
+                        // the row-major dst goes through a workspace
+                        let mut workspace = vec![MaybeUninit::new(f32::NAN); plan.workspace_size()];
+                        let mut dst = c.clone();
+                        unsafe {
+                            plan.execute_unchecked_with_workspace(
+                                n,
+                                n,
+                                k,
+                                dst.as_mut_ptr(),
+                                dst_rs,
+                                dst_cs,
+                                a.as_ptr(),
+                                1,
+                                n as isize,
+                                b.as_ptr(),
+                                1,
+                                k as isize,
+                                2.0,
+                                -1.0,
+                                false,
+                                false,
+                                if rowmajor_dst {
+                                    workspace.as_mut_ptr()
+                                } else {
+                                    core::ptr::null_mut()
+                                },
+                            );
+                        }
 
-impl Plan<c64> {
-    #[track_caller]
-    pub fn new_c64_impl(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-        #[cfg(feature = "std")]
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            #[cfg(feature = "nightly")]
-            if m > 2 && std::is_x86_feature_detected!("avx512f") {
-                return Self::new_c64_avx512(m, n, k, is_col_major);
+                        for i in 0..n {
+                            for j in 0..n {
+                                // position in the stored `dst`
+                                let (si, sj) = if dst_op == Op::NoTrans {
+                                    (i, j)
+                                } else {
+                                    (j, i)
+                                };
+                                let idx = (si as isize * dst_rs + sj as isize * dst_cs) as usize;
+                                if !triangle.contains(si, sj) {
+                                    assert!(dst[idx] == c[idx]);
+                                    continue;
+                                }
+                                let mut acc = 0.0;
+                                for depth in 0..k {
+                                    acc += a[depth * n + i] * b[j * k + depth];
+                                }
+                                let expected = 2.0 * c[idx] - acc;
+                                assert!((dst[idx] - expected).abs() < 1e-3);
+                            }
+                        }
+                    }
+                }
             }
+        }
+    }
+
+    #[test]
+    fn test_plan_structured() {
+        let gen = |_| c64::new(rand::random(), rand::random());
+        let nan = c64::new(f64::NAN, f64::NAN);
+        for (dim, other) in [(1, 3), (7, 1), (9, 4), (17, 2), (33, 5), (70, 20), (96, 9)] {
+            for triangle in [Triangle::Lower, Triangle::Upper] {
+                for hermitian in [false, true] {
+                    for structured_lhs in [true, false] {
+                        let structure = if hermitian {
+                            Structure::Hermitian(triangle)
+                        } else {
+                            Structure::Symmetric(triangle)
+                        };
+                        let (m, n, k) = if structured_lhs {
+                            (dim, other, dim)
+                        } else {
+                            (other, dim, dim)
+                        };
+
+                        // the other triangle of the square operand is never read
+                        let mut s = (0..dim * dim).map(gen).collect::<Vec<_>>();
+                        for i in 0..dim {
+                            for j in 0..dim {
+                                if !triangle.contains(i, j) {
+                                    s[i + j * dim] = nan;
+                                }
+                            }
+                        }
+                        let full = |i: usize, j: usize| {
+                            if hermitian && i == j {
+                                c64::new(s[i + i * dim].re, 0.0)
+                            } else if triangle.contains(i, j) {
+                                s[i + j * dim]
+                            } else if hermitian {
+                                s[j + i * dim].conj()
+                            } else {
+                                s[j + i * dim]
+                            }
+                        };
+                        let g = (0..dim * other).map(gen).collect::<Vec<_>>();
+                        let (a, b) = if structured_lhs { (&s, &g) } else { (&g, &s) };
+                        let c = (0..m * n).map(gen).collect::<Vec<_>>();
+
+                        for fixed_strides in [false, true] {
+                            let mut builder = PlanBuilder::new(m, n, k);
+                            if fixed_strides {
+                                builder = builder.dst_rs(1).lhs_rs(1).rhs_rs(1);
+                            }
+                            let plan = if structured_lhs {
+                                builder.lhs_structure(structure)
+                            } else {
+                                builder.rhs_structure(structure)
+                            }
+                            .build::<c64>();
+
+                            let alpha = c64::new(0.5, -1.0);
+                            let beta = c64::new(2.0, 1.0);
+                            // row-major dst, or column-major through a workspace
+                            let (dst_rs, dst_cs) = if fixed_strides {
+                                (1, m as isize)
+                            } else {
+                                (n as isize, 1)
+                            };
+                            let mut dst = vec![c64::ZERO; m * n];
+                            for i in 0..m {
+                                for j in 0..n {
+                                    dst[i * dst_rs as usize + j * dst_cs as usize] = c[i * n + j];
+                                }
+                            }
+                            let mut workspace = vec![MaybeUninit::new(nan); plan.workspace_size()];
+                            unsafe {
+                                plan.execute_unchecked_with_workspace(
+                                    m,
+                                    n,
+                                    k,
+                                    dst.as_mut_ptr(),
+                                    dst_rs,
+                                    dst_cs,
+                                    a.as_ptr(),
+                                    1,
+                                    m as isize,
+                                    b.as_ptr(),
+                                    1,
+                                    k as isize,
+                                    alpha,
+                                    beta,
+                                    false,
+                                    true,
+                                    if fixed_strides {
+                                        workspace.as_mut_ptr()
+                                    } else {
+                                        core::ptr::null_mut()
+                                    },
+                                );
+                            }
 
-            if std::is_x86_feature_detected!("avx2") {
-                if m == 1 {
-                    return Self::new_c64x1(m, n, k, is_col_major);
+                            for i in 0..m {
+                                for j in 0..n {
+                                    let mut acc = c64::ZERO;
+                                    for depth in 0..k {
+                                        let (lhs, rhs) = if structured_lhs {
+                                            (full(i, depth), b[depth + j * k])
+                                        } else {
+                                            (a[i + depth * m], full(depth, j))
+                                        };
+                                        acc += lhs * rhs.conj();
+                                    }
+                                    let expected = alpha * c[i * n + j] + beta * acc;
+                                    let got = dst[i * dst_rs as usize + j * dst_cs as usize];
+                                    assert!((got - expected).norm() < 1e-10);
+                                }
+                            }
+                        }
+                    }
                 }
-                return Self::new_c64_avx(m, n, k, is_col_major);
             }
         }
 
-        #[cfg(feature = "std")]
-        #[cfg(target_arch = "aarch64")]
-        {
-            if std::arch::is_aarch64_feature_detected!("neon")
-                && std::arch::is_aarch64_feature_detected!("fcma")
-            {
-                return Self::from_non_masked_impl(
-                    &aarch64::c64::neon::MICROKERNELS,
-                    m,
-                    n,
-                    k,
-                    is_col_major,
-                );
+        // fixed unit strides, on both sides of the 64 row strips of symmetric operands
+        for dim in [64, 65, 96] {
+            for triangle in [Triangle::Lower, Triangle::Upper] {
+                let (m, n, k) = (dim, 9, dim);
+                let mut s = (0..dim * dim)
+                    .map(|_| rand::random::<f32>())
+                    .collect::<Vec<_>>();
+                for i in 0..dim {
+                    for j in 0..dim {
+                        if !triangle.contains(i, j) {
+                            s[i + j * dim] = f32::NAN;
+                        }
+                    }
+                }
+                let full = |i: usize, j: usize| {
+                    if triangle.contains(i, j) {
+                        s[i + j * dim]
+                    } else {
+                        s[j + i * dim]
+                    }
+                };
+                let b = (0..k * n)
+                    .map(|_| rand::random::<f32>())
+                    .collect::<Vec<_>>();
+                let c = (0..m * n)
+                    .map(|_| rand::random::<f32>())
+                    .collect::<Vec<_>>();
+
+                let plan = PlanBuilder::new(m, n, k)
+                    .dst_rs(1)
+                    .lhs_rs(1)
+                    .rhs_rs(1)
+                    .lhs_structure(Structure::Symmetric(triangle))
+                    .build::<f32>();
+                let mut dst = c.clone();
+                let mut workspace = vec![MaybeUninit::new(f32::NAN); plan.workspace_size()];
+                unsafe {
+                    plan.execute_unchecked_with_workspace(
+                        m,
+                        n,
+                        k,
+                        dst.as_mut_ptr(),
+                        1,
+                        m as isize,
+                        s.as_ptr(),
+                        1,
+                        k as isize,
+                        b.as_ptr(),
+                        1,
+                        k as isize,
+                        0.5,
+                        2.0,
+                        false,
+                        false,
+                        workspace.as_mut_ptr(),
+                    );
+                }
+
+                for i in 0..m {
+                    for j in 0..n {
+                        let mut acc = 0.0;
+                        for depth in 0..k {
+                            acc += full(i, depth) * b[depth + j * k];
+                        }
+                        let expected = 0.5 * c[i + j * m] + 2.0 * acc;
+                        assert!((dst[i + j * m] - expected).abs() < 1e-3 * expected.abs());
+                    }
+                }
             }
         }
-        Self::new_c64_scalar(m, n, k, is_col_major)
     }
 
-    #[track_caller]
-    pub fn new_colmajor_lhs_and_dst_c64(m: usize, n: usize, k: usize) -> Self {
-        Self::new_c64_impl(m, n, k, true)
-    }
+    #[test]
+    fn test_plan_trmm() {
+        let gen = |_| rand::random::<f64>();
+        for (dim, other) in [
+            (1, 2),
+            (5, 3),
+            (9, 9),
+            (16, 1),
+            (17, 5),
+            (33, 33),
+            (37, 9),
+            (70, 4),
+        ] {
+            for triangle in [Triangle::Lower, Triangle::Upper] {
+                for unit in [false, true] {
+                    for (structured_lhs, op) in [
+                        (true, Op::NoTrans),
+                        (true, Op::Trans),
+                        (false, Op::NoTrans),
+                        (false, Op::Trans),
+                    ] {
+                        let structure = if unit {
+                            Structure::UnitTriangular(triangle)
+                        } else {
+                            Structure::Triangular(triangle)
+                        };
+                        let (m, n, k) = if structured_lhs {
+                            (dim, other, dim)
+                        } else {
+                            (other, dim, dim)
+                        };
+
+                        // the zero triangle and the unit diagonal are never read
+                        let mut t = (0..dim * dim).map(gen).collect::<Vec<_>>();
+                        for i in 0..dim {
+                            for j in 0..dim {
+                                if !triangle.contains(i, j) || (unit && i == j) {
+                                    t[i + j * dim] = f64::NAN;
+                                }
+                            }
+                        }
+                        // element of `op(t)`
+                        let full = |i: usize, j: usize| {
+                            let (i, j) = if op == Op::NoTrans { (i, j) } else { (j, i) };
+                            if unit && i == j {
+                                1.0
+                            } else if triangle.contains(i, j) {
+                                t[i + j * dim]
+                            } else {
+                                0.0
+                            }
+                        };
+                        let g = (0..dim * other).map(gen).collect::<Vec<_>>();
+                        let c = (0..m * n).map(gen).collect::<Vec<_>>();
+
+                        // with the unit row strides fixed, the kernels can work in place
+                        for fixed_strides in [false, true] {
+                            let mut builder = PlanBuilder::new(m, n, k);
+                            if fixed_strides {
+                                builder = builder.dst_rs(1).lhs_rs(1).rhs_rs(1);
+                            }
+                            let plan = if structured_lhs {
+                                builder.lhs_structure(structure).lhs_op(op)
+                            } else {
+                                builder.rhs_structure(structure).rhs_op(op)
+                            }
+                            .build::<f64>();
+
+                            let mut dst = c.clone();
+                            let (a, b) = if structured_lhs { (&t, &g) } else { (&g, &t) };
+                            unsafe {
+                                plan.execute_unchecked(
+                                    m,
+                                    n,
+                                    k,
+                                    dst.as_mut_ptr(),
+                                    1,
+                                    m as isize,
+                                    a.as_ptr(),
+                                    1,
+                                    if structured_lhs { dim } else { m } as isize,
+                                    b.as_ptr(),
+                                    1,
+                                    k as isize,
+                                    -1.0,
+                                    3.0,
+                                    false,
+                                    false,
+                                );
+                            }
 
-    #[track_caller]
-    pub fn new_c64(m: usize, n: usize, k: usize) -> Self {
-        Self::new_c64_impl(m, n, k, false)
+                            for i in 0..m {
+                                for j in 0..n {
+                                    let mut acc = 0.0;
+                                    for depth in 0..k {
+                                        acc += if structured_lhs {
+                                            full(i, depth) * b[depth + j * k]
+                                        } else {
+                                            a[i + depth * m] * full(depth, j)
+                                        };
+                                    }
+                                    let expected = -c[i + j * m] + 3.0 * acc;
+                                    assert!((dst[i + j * m] - expected).abs() < 1e-10);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
-}
-
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-mod x86_api {
-    use super::*;
 
-    impl Plan<f32> {
-        pub fn new_f32x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f32::f32x1::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
-        pub fn new_f32x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f32::f32x2::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
-        pub fn new_f32x4(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f32::f32x4::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
+    #[test]
+    fn test_syrk() {
+        let gen = |_| c64::new(rand::random(), rand::random());
+        for n in [1, 3, 8, 17, 70] {
+            for k in [0, 1, 5, 20] {
+                for triangle in [Triangle::Lower, Triangle::Upper] {
+                    for colmajor in [true, false] {
+                        let a = (0..n * k).map(gen).collect::<Vec<_>>();
+                        let c = (0..n * n).map(gen).collect::<Vec<_>>();
+                        let plan = if colmajor {
+                            SyrkPlan::<c64>::new_colmajor(n, k, triangle)
+                        } else {
+                            SyrkPlan::<c64>::new(n, k, triangle)
+                        };
+                        assert!(plan.triangle() == triangle);
+
+                        for (alpha, conj) in [(0.0, false), (2.0, false), (0.5, true)] {
+                            let alpha = c64::new(alpha, 0.0);
+                            let beta = c64::new(-1.5, 0.0);
+                            let mut dst = c.clone();
+                            let (a_view, dst_view) = if colmajor {
+                                (
+                                    MatRef::from_col_major_slice(&a, n, k),
+                                    MatMut::from_col_major_slice(&mut dst, n, n),
+                                )
+                            } else {
+                                (
+                                    MatRef::from_row_major_slice(&a, n, k),
+                                    MatMut::from_row_major_slice(&mut dst, n, n),
+                                )
+                            };
+                            plan.execute(dst_view, a_view, alpha, beta, conj);
+
+                            let idx = |i: usize, j: usize| {
+                                if colmajor {
+                                    i + j * n
+                                } else {
+                                    i * n + j
+                                }
+                            };
+                            let a_idx = |i: usize, j: usize| {
+                                if colmajor {
+                                    i + j * n
+                                } else {
+                                    i * k + j
+                                }
+                            };
+                            for i in 0..n {
+                                for j in 0..n {
+                                    let idx = idx(i, j);
+                                    if !triangle.contains(i, j) {
+                                        // the other triangle is left untouched
+                                        assert!(dst[idx] == c[idx]);
+                                        continue;
+                                    }
+                                    let mut acc = c64::ZERO;
+                                    for depth in 0..k {
+                                        let rhs = a[a_idx(j, depth)];
+                                        acc += a[a_idx(i, depth)]
+                                            * if conj { rhs.conj() } else { rhs };
+                                    }
+                                    let mut expected = alpha * c[idx] + beta * acc;
+                                    if conj && i == j {
+                                        assert!(dst[idx].im == 0.0);
+                                        expected.im = 0.0;
+                                    }
+                                    assert!((dst[idx] - expected).norm() < 1e-10);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         }
+    }
 
-        pub fn new_f32_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f32::avx::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
-        }
+    #[test]
+    fn test_trsm() {
+        let gen = |_| c64::new(rand::random(), rand::random());
+        for (m, n) in [(1, 1), (3, 5), (16, 2), (17, 33), (40, 7)] {
+            for side in [Side::Left, Side::Right] {
+                for triangle in [Triangle::Lower, Triangle::Upper] {
+                    for unit in [false, true] {
+                        for op in [Op::NoTrans, Op::Trans, Op::ConjTrans] {
+                            let structure = if unit {
+                                Structure::UnitTriangular(triangle)
+                            } else {
+                                Structure::Triangular(triangle)
+                            };
+                            let dim = if side == Side::Left { m } else { n };
+
+                            // diagonally dominant, with the unread elements set to NaN
+                            let mut t = (0..dim * dim).map(gen).collect::<Vec<_>>();
+                            for i in 0..dim {
+                                for j in 0..dim {
+                                    if !triangle.contains(i, j) || (unit && i == j) {
+                                        t[i + j * dim] = c64::new(f64::NAN, f64::NAN);
+                                    } else if i == j {
+                                        t[i + j * dim] += c64::new(dim as f64, 0.0);
+                                    }
+                                }
+                            }
+                            // element of `op(conj(t))`
+                            let op_t = |i: usize, j: usize| {
+                                let (i, j) = if op == Op::NoTrans { (i, j) } else { (j, i) };
+                                let x = if unit && i == j {
+                                    c64::ONE
+                                } else if triangle.contains(i, j) {
+                                    t[i + j * dim].conj()
+                                } else {
+                                    c64::ZERO
+                                };
+                                if op == Op::ConjTrans {
+                                    x.conj()
+                                } else {
+                                    x
+                                }
+                            };
+
+                            let x = (0..m * n).map(gen).collect::<Vec<_>>();
+                            // row-major `b`
+                            let mut b = vec![c64::ZERO; m * n];
+                            for i in 0..m {
+                                for j in 0..n {
+                                    for p in 0..dim {
+                                        b[i * n + j] += if side == Side::Left {
+                                            op_t(i, p) * x[p + j * m]
+                                        } else {
+                                            x[i + p * m] * op_t(p, j)
+                                        };
+                                    }
+                                }
+                            }
 
-        #[cfg(feature = "nightly")]
-        pub fn new_f32_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f32::avx512::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
+                            let plan = TrsmPlan::<c64>::new(m, n, side, structure, op);
+                            plan.execute(
+                                MatRef::from_col_major_slice(&t, dim, dim),
+                                MatMut::from_row_major_slice(&mut b, m, n),
+                                true,
+                            );
+                            for i in 0..m {
+                                for j in 0..n {
+                                    assert!((b[i * n + j] - x[i + j * m]).norm() < 1e-8);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         }
     }
 
-    impl Plan<f64> {
-        pub fn new_f64x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f64::f64x1::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
-        pub fn new_f64x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f64::f64x2::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
+    #[test]
+    fn test_cholesky() {
+        use linalg::CholeskyPlan;
 
-        pub fn new_f64_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f64::avx::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
-        }
+        let gen = |_| c64::new(rand::random(), rand::random());
+        for (n, nrhs) in [(1, 1), (5, 3), (16, 1), (17, 4), (40, 2), (64, 5)] {
+            let batch = 3;
+            // hermitian positive definite, with the unread upper triangle set to NaN
+            let mut a = vec![c64::ZERO; batch * n * n];
+            for a in a.chunks_exact_mut(n * n) {
+                let g = (0..n * n).map(gen).collect::<Vec<_>>();
+                for i in 0..n {
+                    for j in 0..n {
+                        a[i + j * n] = if i < j {
+                            c64::new(f64::NAN, f64::NAN)
+                        } else {
+                            let mut x = (0..n).map(|p| g[i + p * n] * g[j + p * n].conj()).sum();
+                            if i == j {
+                                x += c64::new(n as f64, 0.0);
+                            }
+                            x
+                        };
+                    }
+                }
+            }
+            let a_at = |a: &[c64], i: usize, j: usize| {
+                if i >= j {
+                    a[i + j * n]
+                } else {
+                    a[j + i * n].conj()
+                }
+            };
+            let x = (0..batch * n * nrhs).map(gen).collect::<Vec<_>>();
+            let mut b = vec![c64::ZERO; batch * n * nrhs];
+            for idx in 0..batch {
+                let a = &a[idx * n * n..][..n * n];
+                for i in 0..n {
+                    for j in 0..nrhs {
+                        b[idx * n * nrhs + i + j * n] = (0..n)
+                            .map(|p| a_at(a, i, p) * x[idx * n * nrhs + p + j * n])
+                            .sum();
+                    }
+                }
+            }
 
-        #[cfg(feature = "nightly")]
-        pub fn new_f64_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::f64::avx512::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
-        }
-    }
-    impl Plan<c32> {
-        pub fn new_c32x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c32::c32x1::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
-        pub fn new_c32x2(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c32::c32x2::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
+            let plan = CholeskyPlan::<c64>::new(n, nrhs);
+            let mut l = a[..n * n].to_vec();
+            let mut b0 = b[..n * nrhs].to_vec();
+            plan.factor(MatMut::from_col_major_slice(&mut l, n, n))
+                .unwrap();
+            plan.solve(
+                MatRef::from_col_major_slice(&l, n, n),
+                MatMut::from_col_major_slice(&mut b0, n, nrhs),
+            );
+            for i in 0..n * nrhs {
+                assert!((b0[i] - x[i]).norm() < 1e-8);
+            }
 
-        pub fn new_c32_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c32::avx::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
-        }
+            let mut l = a.clone();
+            plan.factor_batched(&mut l).unwrap();
+            plan.solve_batched(&l, &mut b);
+            for i in 0..batch * n * nrhs {
+                assert!((b[i] - x[i]).norm() < 1e-8);
+            }
 
-        #[cfg(feature = "nightly")]
-        pub fn new_c32_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c32::avx512::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
+            // the last diagonal element of the second matrix is made negative
+            a[2 * n * n - 1] = c64::new(-(n as f64) * n as f64 * 4.0, 0.0);
+            assert!(
+                plan.factor_batched(&mut a)
+                    == Err((1, FactorError::NotPositiveDefinite { col: n - 1 }))
+            );
         }
     }
-    impl Plan<c64> {
-        pub fn new_c64x1(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c64::c64x1::*;
-            Self::from_masked_impl::<MR_DIV_N, NR, N, ()>(
-                &MICROKERNELS,
-                None,
-                m,
-                n,
-                k,
-                is_col_major,
-            )
-        }
 
-        pub fn new_c64_avx(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c64::avx::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
-        }
+    #[test]
+    fn test_lu() {
+        use linalg::LuPlan;
 
-        #[cfg(feature = "nightly")]
-        pub fn new_c64_avx512(m: usize, n: usize, k: usize, is_col_major: bool) -> Self {
-            use x86::c64::avx512::*;
-            Self::from_masked_impl(&MICROKERNELS, Some(&MASKS), m, n, k, is_col_major)
+        let gen = |_| c64::new(rand::random(), rand::random());
+        for (n, nrhs) in [(1, 1), (5, 3), (16, 1), (17, 4), (40, 2), (64, 5)] {
+            let batch = 3;
+            let a = (0..batch * n * n).map(gen).collect::<Vec<_>>();
+            let x = (0..batch * n * nrhs).map(gen).collect::<Vec<_>>();
+            let mut b = vec![c64::ZERO; batch * n * nrhs];
+            for idx in 0..batch {
+                for i in 0..n {
+                    for j in 0..nrhs {
+                        b[idx * n * nrhs + i + j * n] = (0..n)
+                            .map(|p| a[idx * n * n + i + p * n] * x[idx * n * nrhs + p + j * n])
+                            .sum();
+                    }
+                }
+            }
+
+            let plan = LuPlan::<c64>::new(n, nrhs);
+            // row-major storage
+            let mut lu = (0..n * n).map(|i| a[i / n + i % n * n]).collect::<Vec<_>>();
+            let mut perm = vec![0; n];
+            let mut b0 = b[..n * nrhs].to_vec();
+            plan.factor(MatMut::from_row_major_slice(&mut lu, n, n), &mut perm)
+                .unwrap();
+            plan.solve(
+                MatRef::from_row_major_slice(&lu, n, n),
+                &perm,
+                MatMut::from_col_major_slice(&mut b0, n, nrhs),
+            );
+            for i in 0..n * nrhs {
+                assert!((b0[i] - x[i]).norm() < 1e-6);
+            }
+
+            let mut lu = a.clone();
+            let mut perm = vec![0; batch * n];
+            plan.factor_batched(&mut lu, &mut perm).unwrap();
+            plan.solve_batched(&lu, &perm, &mut b);
+            for i in 0..batch * n * nrhs {
+                assert!((b[i] - x[i]).norm() < 1e-6);
+            }
+
+            let mut singular = vec![c64::ZERO; n * n];
+            assert!(
+                plan.factor(
+                    MatMut::from_col_major_slice(&mut singular, n, n),
+                    &mut perm[..n]
+                ) == Err(FactorError::Singular { col: 0 })
+            );
         }
     }
-}
 
-pub mod planless {
-    use super::*;
+    #[test]
+    fn test_qr() {
+        use linalg::QrPlan;
 
-    #[inline(always)]
-    pub unsafe fn execute_f32(
-        mut m: usize,
-        mut n: usize,
-        k: usize,
-        mut dst: *mut f32,
-        mut dst_rs: isize,
-        mut dst_cs: isize,
-        mut lhs: *const f32,
-        mut lhs_rs: isize,
-        mut lhs_cs: isize,
-        mut rhs: *const f32,
-        mut rhs_rs: isize,
-        mut rhs_cs: isize,
-        alpha: f32,
-        beta: f32,
-        mut conj_lhs: bool,
-        mut conj_rhs: bool,
-    ) {
-        if dst_cs.unsigned_abs() < dst_rs.unsigned_abs() {
-            core::mem::swap(&mut m, &mut n);
-            core::mem::swap(&mut dst_rs, &mut dst_cs);
-            core::mem::swap(&mut lhs, &mut rhs);
-            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
-            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
-            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
-        }
-        if dst_rs == -1 && m > 0 {
-            dst = dst.wrapping_offset((m - 1) as isize * dst_rs);
-            dst_rs = dst_rs.wrapping_neg();
-            lhs = lhs.wrapping_offset((m - 1) as isize * lhs_rs);
-            lhs_rs = lhs_rs.wrapping_neg();
-        }
+        let gen = |_| c64::new(rand::random(), rand::random());
+        // the workspace is on the stack for `n <= 64`, and on the heap otherwise
+        for (m, n, nrhs) in [
+            (1, 1, 1),
+            (7, 5, 3),
+            (16, 16, 1),
+            (33, 17, 4),
+            (64, 40, 2),
+            (80, 70, 1),
+        ] {
+            let batch = 3;
+            let a = (0..batch * m * n).map(gen).collect::<Vec<_>>();
+            // consistent systems, whose least squares solution is `x`
+            let x = (0..batch * n * nrhs).map(gen).collect::<Vec<_>>();
+            let mut b = vec![c64::ZERO; batch * m * nrhs];
+            for idx in 0..batch {
+                for i in 0..m {
+                    for j in 0..nrhs {
+                        b[idx * m * nrhs + i + j * m] = (0..n)
+                            .map(|p| a[idx * m * n + i + p * m] * x[idx * n * nrhs + p + j * n])
+                            .sum();
+                    }
+                }
+            }
 
-        let plan = if lhs_rs == 1 && dst_rs == 1 {
-            Plan::new_colmajor_lhs_and_dst_f32(m, n, k)
-        } else {
-            Plan::new_f32(m, n, k)
-        };
-        plan.execute_unchecked(
-            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
-            conj_lhs, conj_rhs,
-        )
-    }
+            let plan = QrPlan::<c64>::new(m, n, nrhs);
+            let mut qr = a[..m * n].to_vec();
+            let mut tau = vec![c64::ZERO; n];
+            let mut b0 = b[..m * nrhs].to_vec();
+            plan.factor(MatMut::from_col_major_slice(&mut qr, m, n), &mut tau);
+            plan.solve(
+                MatRef::from_col_major_slice(&qr, m, n),
+                &tau,
+                MatMut::from_col_major_slice(&mut b0, m, nrhs),
+            );
+            for i in 0..n {
+                for j in 0..nrhs {
+                    assert!((b0[i + j * m] - x[i + j * n]).norm() < 1e-6);
+                }
+            }
 
-    #[inline(always)]
-    pub unsafe fn execute_c32(
-        mut m: usize,
-        mut n: usize,
-        k: usize,
-        mut dst: *mut c32,
-        mut dst_rs: isize,
-        mut dst_cs: isize,
-        mut lhs: *const c32,
-        mut lhs_rs: isize,
-        mut lhs_cs: isize,
-        mut rhs: *const c32,
-        mut rhs_rs: isize,
-        mut rhs_cs: isize,
-        alpha: c32,
-        beta: c32,
-        mut conj_lhs: bool,
-        mut conj_rhs: bool,
-    ) {
-        if dst_cs.unsigned_abs() < dst_rs.unsigned_abs() {
-            core::mem::swap(&mut m, &mut n);
-            core::mem::swap(&mut dst_rs, &mut dst_cs);
-            core::mem::swap(&mut lhs, &mut rhs);
-            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
-            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
-            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
-        }
-        if dst_rs == -1 && m > 0 {
-            dst = dst.wrapping_offset((m - 1) as isize * dst_rs);
-            dst_rs = dst_rs.wrapping_neg();
-            lhs = lhs.wrapping_offset((m - 1) as isize * lhs_rs);
-            lhs_rs = lhs_rs.wrapping_neg();
+            // the workspace is never read before it's written
+            let mut workspace =
+                vec![MaybeUninit::new(c64::new(f64::NAN, f64::NAN)); plan.workspace_size()];
+            let mut qr_ws = a[..m * n].to_vec();
+            let mut tau_ws = vec![c64::ZERO; n];
+            plan.factor_with_workspace(
+                &mut workspace,
+                MatMut::from_col_major_slice(&mut qr_ws, m, n),
+                &mut tau_ws,
+            );
+            assert!(all(qr_ws == qr, tau_ws == tau));
+
+            let mut qr = a.clone();
+            let mut tau = vec![c64::ZERO; batch * n];
+            plan.factor_batched(&mut qr, &mut tau);
+            plan.solve_batched(&qr, &tau, &mut b);
+            for idx in 0..batch {
+                for i in 0..n {
+                    for j in 0..nrhs {
+                        assert!(
+                            (b[idx * m * nrhs + i + j * m] - x[idx * n * nrhs + i + j * n]).norm()
+                                < 1e-6
+                        );
+                    }
+                }
+            }
         }
+    }
 
-        let plan = if lhs_rs == 1 && dst_rs == 1 {
-            Plan::new_colmajor_lhs_and_dst_c32(m, n, k)
-        } else {
-            Plan::new_c32(m, n, k)
-        };
-        plan.execute_unchecked(
-            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
-            conj_lhs, conj_rhs,
-        )
+    /// Scalar types of the tests that run for all of them.
+    trait TestScalar: GemmScalar + core::fmt::Debug {
+        fn random() -> Self;
     }
 
-    #[inline(always)]
-    pub unsafe fn execute_f64(
-        mut m: usize,
-        mut n: usize,
-        k: usize,
-        mut dst: *mut f64,
-        mut dst_rs: isize,
-        mut dst_cs: isize,
-        mut lhs: *const f64,
-        mut lhs_rs: isize,
-        mut lhs_cs: isize,
-        mut rhs: *const f64,
-        mut rhs_rs: isize,
-        mut rhs_cs: isize,
-        alpha: f64,
-        beta: f64,
-        mut conj_lhs: bool,
-        mut conj_rhs: bool,
-    ) {
-        if dst_cs.unsigned_abs() < dst_rs.unsigned_abs() {
-            core::mem::swap(&mut m, &mut n);
-            core::mem::swap(&mut dst_rs, &mut dst_cs);
-            core::mem::swap(&mut lhs, &mut rhs);
-            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
-            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
-            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
-        }
-        if dst_rs == -1 && m > 0 {
-            dst = dst.wrapping_offset((m - 1) as isize * dst_rs);
-            dst_rs = dst_rs.wrapping_neg();
-            lhs = lhs.wrapping_offset((m - 1) as isize * lhs_rs);
-            lhs_rs = lhs_rs.wrapping_neg();
+    impl<T: GemmScalar + core::fmt::Debug> TestScalar for T
+    where
+        rand::distributions::Standard: rand::distributions::Distribution<T>,
+    {
+        fn random() -> Self {
+            rand::random()
         }
+    }
 
-        let plan = if lhs_rs == 1 && dst_rs == 1 {
-            Plan::new_colmajor_lhs_and_dst_f64(m, n, k)
-        } else {
-            Plan::new_f64(m, n, k)
-        };
-        plan.execute_unchecked(
-            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
-            conj_lhs, conj_rhs,
-        )
+    fn random_vec<T: TestScalar>(len: usize) -> Vec<T> {
+        (0..len).map(|_| T::random()).collect()
     }
 
-    #[inline(always)]
-    pub unsafe fn execute_c64(
-        mut m: usize,
-        mut n: usize,
+    /// Returns the column-major `m × n` product `conj?(a) * diag(k_scale) * conj?(b)`, where `a`
+    /// and `b` are column-major `m × k` and `k × n` matrices.
+    fn naive_product<T: TestScalar>(
+        m: usize,
+        n: usize,
         k: usize,
-        mut dst: *mut c64,
-        mut dst_rs: isize,
-        mut dst_cs: isize,
-        mut lhs: *const c64,
-        mut lhs_rs: isize,
-        mut lhs_cs: isize,
-        mut rhs: *const c64,
-        mut rhs_rs: isize,
-        mut rhs_cs: isize,
-        alpha: c64,
-        beta: c64,
-        mut conj_lhs: bool,
-        mut conj_rhs: bool,
-    ) {
-        if dst_cs.unsigned_abs() < dst_rs.unsigned_abs() {
-            core::mem::swap(&mut m, &mut n);
-            core::mem::swap(&mut dst_rs, &mut dst_cs);
-            core::mem::swap(&mut lhs, &mut rhs);
-            core::mem::swap(&mut lhs_rs, &mut rhs_cs);
-            core::mem::swap(&mut lhs_cs, &mut rhs_rs);
-            core::mem::swap(&mut conj_lhs, &mut conj_rhs);
-        }
-        if dst_rs == -1 && m > 0 {
-            dst = dst.wrapping_offset((m - 1) as isize * dst_rs);
-            dst_rs = dst_rs.wrapping_neg();
-            lhs = lhs.wrapping_offset((m - 1) as isize * lhs_rs);
-            lhs_rs = lhs_rs.wrapping_neg();
+        a: &[T],
+        b: &[T],
+        conj_a: bool,
+        conj_b: bool,
+        k_scale: Option<&[T]>,
+    ) -> Vec<T> {
+        let mut prod = vec![unsafe { core::mem::zeroed::<T>() }; m * n];
+        for j in 0..n {
+            for i in 0..m {
+                for depth in 0..k {
+                    let lhs = a[i + depth * m];
+                    let rhs = b[depth + j * k];
+                    let mut x = if conj_a { lhs.conj() } else { lhs }
+                        * if conj_b { rhs.conj() } else { rhs };
+                    if let Some(k_scale) = k_scale {
+                        x = x * k_scale[depth];
+                    }
+                    prod[i + j * m] = prod[i + j * m] + x;
+                }
+            }
         }
-
-        let plan = if lhs_rs == 1 && dst_rs == 1 {
-            Plan::new_colmajor_lhs_and_dst_c64(m, n, k)
-        } else {
-            Plan::new_c64(m, n, k)
-        };
-        plan.execute_unchecked(
-            m, n, k, dst, dst_rs, dst_cs, lhs, lhs_rs, lhs_cs, rhs, rhs_rs, rhs_cs, alpha, beta,
-            conj_lhs, conj_rhs,
-        )
+        prod
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use equator::assert;
 
-    #[cfg(target_arch = "x86_64")]
     #[test]
-    fn test_kernel() {
-        let gen = |_| rand::random::<f32>();
-        let a: [[f32; 17]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let b: [[f32; 6]; 4] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let c: [[f32; 15]; 4] = core::array::from_fn(|_| core::array::from_fn(gen));
-        assert!(std::is_x86_feature_detected!("avx2"));
-        let mut dst = c;
-
-        let last_mask: std::arch::x86_64::__m256i = unsafe {
-            core::mem::transmute([
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                0,
-            ])
-        };
-
-        let beta = 2.5;
-        let alpha = 1.0;
+    fn test_plan_batched_layouts() {
+        fn check<T: TestScalar>() {
+            let batch = 3;
+            for (m, n, k) in [(1, 1, 1), (3, 5, 0), (9, 1, 7), (17, 13, 16), (70, 9, 150)] {
+                // the lhs is different for each problem, and the rhs is shared
+                let a = random_vec::<T>(batch * m * k);
+                let b = random_vec::<T>(k * n);
+                let (alpha, beta) = (T::random(), T::random());
+
+                for (colmajor, dst_op) in [
+                    (true, Op::NoTrans),
+                    (false, Op::NoTrans),
+                    (false, Op::Trans),
+                ] {
+                    let plan = if colmajor {
+                        Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                    } else {
+                        Plan::<T>::new_with_ops(m, n, k, Op::NoTrans, Op::NoTrans, dst_op)
+                    };
+                    // the stored `dst` has a row stride of 2 if the plan isn't column-major
+                    let (nrows, ncols) = dst_op.dims(m, n);
+                    let (dst_rs, dst_cs) = (if colmajor { 1 } else { 2 }, 2 * nrows);
+                    let dst_bs = dst_cs * ncols;
+                    let c = random_vec::<T>(batch * dst_bs);
 
-        unsafe {
-            x86::f32::avx::matmul_2_4_dyn(
-                &MicroKernelData {
-                    alpha,
-                    beta,
-                    conj_lhs: false,
-                    conj_rhs: false,
-                    k: 3,
-                    dst_cs: dst[0].len() as isize,
-                    lhs_cs: a[0].len() as isize,
-                    rhs_rs: 2,
-                    rhs_cs: 6,
-                    last_mask: (&last_mask) as *const _ as *const (),
-                },
-                dst.as_mut_ptr() as *mut f32,
-                a.as_ptr() as *const f32,
-                b.as_ptr() as *const f32,
-            );
-        };
+                    let mut dst = c.clone();
+                    unsafe {
+                        plan.execute_batched(
+                            batch,
+                            m,
+                            n,
+                            k,
+                            dst.as_mut_ptr(),
+                            dst_bs as isize,
+                            dst_rs as isize,
+                            dst_cs as isize,
+                            a.as_ptr(),
+                            (m * k) as isize,
+                            1,
+                            m as isize,
+                            b.as_ptr(),
+                            0,
+                            1,
+                            k as isize,
+                            alpha,
+                            beta,
+                            false,
+                            false,
+                        );
+                    }
 
-        let mut expected_dst = c;
-        for i in 0..15 {
-            for j in 0..4 {
-                let mut acc = 0.0f32;
-                for depth in 0..3 {
-                    acc = f32::mul_add(a[depth][i], b[j][2 * depth], acc);
+                    for p in 0..batch {
+                        let prod = naive_product(m, n, k, &a[p * m * k..], &b, false, false, None);
+                        for i in 0..m {
+                            for j in 0..n {
+                                let (row, col) = if dst_op == Op::NoTrans {
+                                    (i, j)
+                                } else {
+                                    (j, i)
+                                };
+                                let idx = p * dst_bs + row * dst_rs + col * dst_cs;
+                                let expected = alpha * c[idx] + beta * prod[i + j * m];
+                                assert!((dst[idx] - expected).abs2().sqrt() < 1e-3);
+                            }
+                        }
+                    }
                 }
-                expected_dst[j][i] = f32::mul_add(beta, acc, expected_dst[j][i]);
             }
         }
 
-        assert!(dst == expected_dst);
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
     }
 
-    #[cfg(target_arch = "x86_64")]
     #[test]
-    fn test_kernel_cplx() {
-        let gen = |_| rand::random::<c32>();
-        let a: [[c32; 9]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let b: [[c32; 6]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let c: [[c32; 7]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
-        assert!(std::is_x86_feature_detected!("avx2"));
-
-        let last_mask: std::arch::x86_64::__m256i = unsafe {
-            core::mem::transmute([
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                u32::MAX,
-                0,
-                0,
-            ])
-        };
-
-        let beta = c32::new(2.5, 3.5);
-        let alpha = c32::new(1.0, 0.0);
+    fn test_plan_fused_bias() {
+        fn check<T: TestScalar>() {
+            for (m, n, k) in [(1, 1, 1), (3, 5, 0), (9, 1, 7), (17, 13, 16), (70, 9, 150)] {
+                let a = random_vec::<T>(m * k);
+                let b = random_vec::<T>(k * n);
+                let c = random_vec::<T>(2 * m * n);
+                let prod = naive_product(m, n, k, &a, &b, false, false, None);
+                let row_bias = random_vec::<T>(m);
+                let col_bias = random_vec::<T>(n);
+                let (alpha, beta) = (T::random(), T::random());
+
+                for (use_row, use_col) in [(true, false), (false, true), (true, true)] {
+                    let mut fused = FusedOps::new();
+                    if use_row {
+                        fused = fused.row_bias(&row_bias);
+                    }
+                    if use_col {
+                        fused = fused.col_bias(&col_bias);
+                    }
 
-        for (conj_lhs, conj_rhs) in [(false, false), (false, true), (true, false), (true, true)] {
-            let mut dst = c;
-            unsafe {
-                x86::c32::avx::matmul_2_2_dyn(
-                    &MicroKernelData {
-                        alpha,
-                        beta,
-                        conj_lhs,
-                        conj_rhs,
-                        k: 3,
-                        dst_cs: dst[0].len() as isize,
-                        lhs_cs: a[0].len() as isize,
-                        rhs_rs: 2,
-                        rhs_cs: b[0].len() as isize,
-                        last_mask: (&last_mask) as *const _ as *const (),
-                    },
-                    dst.as_mut_ptr() as *mut c32,
-                    a.as_ptr() as *const c32,
-                    b.as_ptr() as *const c32,
-                );
-            };
+                    for (colmajor, dst_op) in [
+                        (true, Op::NoTrans),
+                        (false, Op::NoTrans),
+                        (false, Op::Trans),
+                    ] {
+                        let plan = if colmajor {
+                            Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                        } else {
+                            Plan::<T>::new_with_ops(m, n, k, Op::NoTrans, Op::NoTrans, dst_op)
+                        };
+                        // the stored `dst` has a row stride of 2 if the plan isn't column-major
+                        let (nrows, ncols) = dst_op.dims(m, n);
+                        let (dst_rs, dst_cs) = (if colmajor { 1 } else { 2 }, 2 * nrows);
+
+                        let mut dst = c.clone();
+                        plan.execute_fused(
+                            MatMut::from_slice(
+                                &mut dst,
+                                nrows,
+                                ncols,
+                                dst_rs as isize,
+                                dst_cs as isize,
+                            ),
+                            MatRef::from_col_major_slice(&a, m, k),
+                            MatRef::from_col_major_slice(&b, k, n),
+                            alpha,
+                            beta,
+                            false,
+                            false,
+                            &fused,
+                        );
 
-            let mut expected_dst = c;
-            for i in 0..7 {
-                for j in 0..2 {
-                    let mut acc = c32::new(0.0, 0.0);
-                    for depth in 0..3 {
-                        let mut a = a[depth][i];
-                        let mut b = b[j][2 * depth];
-                        if conj_lhs {
-                            a = a.conj();
-                        }
-                        if conj_rhs {
-                            b = b.conj();
+                        for i in 0..m {
+                            for j in 0..n {
+                                let (row, col) = if dst_op == Op::NoTrans {
+                                    (i, j)
+                                } else {
+                                    (j, i)
+                                };
+                                let idx = row * dst_rs + col * dst_cs;
+                                let mut expected = alpha * c[idx] + beta * prod[i + j * m];
+                                if use_row {
+                                    expected = expected + row_bias[i];
+                                }
+                                if use_col {
+                                    expected = expected + col_bias[j];
+                                }
+                                assert!((dst[idx] - expected).abs2().sqrt() < 1e-3);
+                            }
                         }
-                        acc += a * b;
                     }
-                    expected_dst[j][i] += beta * acc;
                 }
             }
-
-            for (&dst, &expected_dst) in
-                core::iter::zip(dst.iter().flatten(), expected_dst.iter().flatten())
-            {
-                assert!((dst.re - expected_dst.re).abs() < 1e-5);
-                assert!((dst.im - expected_dst.im).abs() < 1e-5);
-            }
         }
+
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
     }
 
-    #[cfg(target_arch = "x86_64")]
     #[test]
-    fn test_kernel_cplx64() {
-        let gen = |_| rand::random::<c64>();
-        let a: [[c64; 5]; 3] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let b: [[c64; 6]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
-        let c: [[c64; 3]; 2] = core::array::from_fn(|_| core::array::from_fn(gen));
-        assert!(std::is_x86_feature_detected!("avx2"));
+    fn test_plan_fused_scale() {
+        fn check<T: TestScalar>() {
+            for (m, n, k) in [
+                (1, 1, 1),
+                (3, 5, 0),
+                (9, 1, 7),
+                (17, 13, 16),
+                // several row and depth blocks in the copying kernels, with a partial last one
+                (70, 9, 150),
+            ] {
+                let a = random_vec::<T>(m * k);
+                let b = random_vec::<T>(k * n);
+                let c = random_vec::<T>(2 * m * n);
+                let row_scale = random_vec::<T>(m);
+                let col_scale = random_vec::<T>(n);
+                let k_scale = random_vec::<T>(k);
+                let row_bias = random_vec::<T>(m);
+                let (alpha, beta) = (T::random(), T::random());
+
+                for (use_row, use_k, use_col) in [
+                    (true, false, false),
+                    (false, true, false),
+                    (false, false, true),
+                    (true, true, true),
+                ] {
+                    let mut fused = FusedOps::new().row_bias(&row_bias);
+                    if use_row {
+                        fused = fused.row_scale(&row_scale);
+                    }
+                    if use_k {
+                        fused = fused.k_scale(&k_scale);
+                    }
+                    if use_col {
+                        fused = fused.col_scale(&col_scale);
+                    }
 
-        let last_mask: std::arch::x86_64::__m256i =
-            unsafe { core::mem::transmute([u64::MAX, u64::MAX, 0, 0]) };
+                    for (conj_lhs, conj_rhs) in [(false, true), (true, false), (true, true)] {
+                        let k_scale = if use_k { Some(&*k_scale) } else { None };
+                        let prod = naive_product(m, n, k, &a, &b, conj_lhs, conj_rhs, k_scale);
+
+                        for (colmajor, dst_op) in [
+                            (true, Op::NoTrans),
+                            (false, Op::NoTrans),
+                            (false, Op::Trans),
+                        ] {
+                            let plan = if colmajor {
+                                Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                            } else {
+                                Plan::<T>::new_with_ops(m, n, k, Op::NoTrans, Op::NoTrans, dst_op)
+                            };
+                            // the stored `dst` has a row stride of 2 if the plan isn't column-major
+                            let (nrows, ncols) = dst_op.dims(m, n);
+                            let (dst_rs, dst_cs) = (if colmajor { 1 } else { 2 }, 2 * nrows);
+
+                            let mut dst = c.clone();
+                            plan.execute_fused(
+                                MatMut::from_slice(
+                                    &mut dst,
+                                    nrows,
+                                    ncols,
+                                    dst_rs as isize,
+                                    dst_cs as isize,
+                                ),
+                                MatRef::from_col_major_slice(&a, m, k),
+                                MatRef::from_col_major_slice(&b, k, n),
+                                alpha,
+                                beta,
+                                conj_lhs,
+                                conj_rhs,
+                                &fused,
+                            );
+
+                            for i in 0..m {
+                                for j in 0..n {
+                                    let mut acc = prod[i + j * m];
+                                    if use_row {
+                                        acc = row_scale[i] * acc;
+                                    }
+                                    if use_col {
+                                        acc = acc * col_scale[j];
+                                    }
+                                    let (row, col) = if dst_op == Op::NoTrans {
+                                        (i, j)
+                                    } else {
+                                        (j, i)
+                                    };
+                                    let idx = row * dst_rs + col * dst_cs;
+                                    let expected = alpha * c[idx] + beta * acc + row_bias[i];
+                                    assert!((dst[idx] - expected).abs2().sqrt() < 1e-3);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
 
-        let beta = c64::new(2.5, 3.5);
-        let alpha = c64::new(1.0, 0.0);
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
+    }
 
-        for (conj_lhs, conj_rhs) in [(false, false), (false, true), (true, false), (true, true)] {
-            let mut dst = c;
-            unsafe {
-                x86::c64::avx::matmul_2_2_dyn(
-                    &MicroKernelData {
-                        alpha,
-                        beta,
-                        conj_lhs,
-                        conj_rhs,
-                        k: 3,
-                        dst_cs: dst[0].len() as isize,
-                        lhs_cs: a[0].len() as isize,
-                        rhs_rs: 2,
-                        rhs_cs: b[0].len() as isize,
-                        last_mask: (&last_mask) as *const _ as *const (),
-                    },
-                    dst.as_mut_ptr() as *mut c64,
-                    a.as_ptr() as *const c64,
-                    b.as_ptr() as *const c64,
-                );
+    #[test]
+    fn test_plan_fused_activation() {
+        fn check<T: GemmScalar + core::fmt::Debug>(
+            with_activation: fn(FusedOps<'_, T>, Activation<T>) -> FusedOps<'_, T>,
+            tol: f64,
+        ) {
+            let gen = |_| T::from_real(rand::random::<f64>() - 0.5);
+            let eval = |activation: Activation<T>, x: f64| match activation {
+                Activation::None => x,
+                Activation::Relu => x.max(0.0),
+                Activation::LeakyRelu(slope) => {
+                    if x > 0.0 {
+                        x
+                    } else {
+                        slope.re() * x
+                    }
+                }
+                Activation::Gelu => {
+                    let u = (2.0 / core::f64::consts::PI).sqrt() * (x + 0.044715 * x * x * x);
+                    0.5 * x * (1.0 + u.tanh())
+                }
+                Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+                Activation::Tanh => x.tanh(),
             };
 
-            let mut expected_dst = c;
-            for i in 0..3 {
-                for j in 0..2 {
-                    let mut acc = c64::new(0.0, 0.0);
-                    for depth in 0..3 {
-                        let mut a = a[depth][i];
-                        let mut b = b[j][2 * depth];
-                        if conj_lhs {
-                            a = a.conj();
-                        }
-                        if conj_rhs {
-                            b = b.conj();
+            for (m, n, k) in [
+                (4, 3, 0),
+                (1, 1, 1),
+                (3, 5, 2),
+                (9, 1, 7),
+                (17, 13, 16),
+                (40, 33, 70),
+            ] {
+                let a = (0..m * k).map(gen).collect::<Vec<_>>();
+                let b = (0..k * n).map(gen).collect::<Vec<_>>();
+                let c = (0..2 * m * n).map(gen).collect::<Vec<_>>();
+                let row_bias = (0..m).map(gen).collect::<Vec<_>>();
+                // large enough for the smooth functions to saturate
+                let beta = T::from_real(4.0);
+
+                for activation in [
+                    Activation::Relu,
+                    Activation::LeakyRelu(T::from_real(0.1)),
+                    Activation::Gelu,
+                    Activation::Sigmoid,
+                    Activation::Tanh,
+                ] {
+                    let fused = with_activation(FusedOps::new().row_bias(&row_bias), activation);
+
+                    for (colmajor, alpha) in [(true, 0.0), (true, 1.5), (false, 0.0), (false, 1.5)]
+                    {
+                        let alpha = T::from_real(alpha);
+                        let dst_rs = if colmajor { 1 } else { 2 };
+                        let plan = if colmajor {
+                            Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                        } else {
+                            Plan::<T>::new(m, n, k)
+                        };
+
+                        let mut dst = c.clone();
+                        plan.execute_fused(
+                            MatMut::from_slice(&mut dst, m, n, dst_rs, 2 * m as isize),
+                            MatRef::from_col_major_slice(&a, m, k),
+                            MatRef::from_col_major_slice(&b, k, n),
+                            alpha,
+                            beta,
+                            false,
+                            false,
+                            &fused,
+                        );
+
+                        for i in 0..m {
+                            for j in 0..n {
+                                let mut acc = 0.0;
+                                for depth in 0..k {
+                                    acc += a[depth * m + i].re() * b[j * k + depth].re();
+                                }
+                                let idx = i * dst_rs as usize + j * 2 * m;
+                                let expected = eval(
+                                    activation,
+                                    alpha.re() * c[idx].re() + beta.re() * acc + row_bias[i].re(),
+                                );
+                                assert!(
+                                    (dst[idx].re() - expected).abs() < tol * (1.0 + expected.abs())
+                                );
+                            }
                         }
-                        acc += a * b;
                     }
-                    expected_dst[j][i] += beta * acc;
                 }
             }
+        }
 
-            for (&dst, &expected_dst) in
-                core::iter::zip(dst.iter().flatten(), expected_dst.iter().flatten())
-            {
-                assert!((dst.re - expected_dst.re).abs() < 1e-5);
-                assert!((dst.im - expected_dst.im).abs() < 1e-5);
+        check::<f32>(|fused, activation| fused.activation(activation), 1e-5);
+        check::<f64>(|fused, activation| fused.activation(activation), 1e-12);
+    }
+
+    #[test]
+    fn test_plan_src() {
+        fn check<T: TestScalar>() {
+            let zero = unsafe { core::mem::zeroed::<T>() };
+            let nan = T::from_real(f64::NAN);
+            for (m, n, k) in [(1, 1, 1), (3, 5, 0), (9, 1, 7), (17, 13, 16), (70, 9, 150)] {
+                let a = random_vec::<T>(m * k);
+                let b = random_vec::<T>(k * n);
+                let prod = naive_product(m, n, k, &a, &b, false, false, None);
+                let (alpha, beta) = (T::random(), T::random());
+
+                for (colmajor, dst_op) in [
+                    (true, Op::NoTrans),
+                    (false, Op::NoTrans),
+                    (false, Op::Trans),
+                    (false, Op::ConjTrans),
+                ] {
+                    let plan = if colmajor {
+                        Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                    } else {
+                        Plan::<T>::new_with_ops(m, n, k, Op::NoTrans, Op::NoTrans, dst_op)
+                    };
+                    // the stored `dst` has a row stride of 2 if the plan isn't column-major
+                    let (nrows, ncols) = dst_op.dims(m, n);
+                    let (dst_rs, dst_cs) = (if colmajor { 1 } else { 2 }, 2 * nrows);
+
+                    for src_rowmajor in [false, true] {
+                        let d = random_vec::<T>(m * n);
+                        let nans = vec![nan; m * n];
+                        // `src` isn't read if `alpha` is zero
+                        for (d, alpha) in [(&d, alpha), (&nans, zero)] {
+                            let src = if src_rowmajor {
+                                MatRef::from_row_major_slice(d, nrows, ncols)
+                            } else {
+                                MatRef::from_col_major_slice(d, nrows, ncols)
+                            };
+
+                            // `dst` is never read
+                            let mut dst = vec![nan; dst_cs * ncols];
+                            plan.execute_with_src(
+                                MatMut::from_slice(
+                                    &mut dst,
+                                    nrows,
+                                    ncols,
+                                    dst_rs as isize,
+                                    dst_cs as isize,
+                                ),
+                                src,
+                                MatRef::from_col_major_slice(&a, m, k),
+                                MatRef::from_col_major_slice(&b, k, n),
+                                alpha,
+                                beta,
+                                false,
+                                false,
+                            );
+
+                            for i in 0..m {
+                                for j in 0..n {
+                                    let (row, col) = if dst_op == Op::NoTrans {
+                                        (i, j)
+                                    } else {
+                                        (j, i)
+                                    };
+                                    let acc = beta * prod[i + j * m];
+                                    let expected = if alpha == zero {
+                                        acc
+                                    } else {
+                                        let src = if src_rowmajor {
+                                            d[row * ncols + col]
+                                        } else {
+                                            d[row + col * nrows]
+                                        };
+                                        if dst_op == Op::ConjTrans {
+                                            alpha * src.conj() + acc
+                                        } else {
+                                            alpha * src + acc
+                                        }
+                                    };
+                                    let expected = if dst_op == Op::ConjTrans {
+                                        expected.conj()
+                                    } else {
+                                        expected
+                                    };
+                                    let found = dst[row * dst_rs + col * dst_cs];
+                                    assert!((found - expected).abs2().sqrt() < 1e-3);
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
+
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
     }
-    #[test]
-    fn test_plan() {
-        let gen = |_| rand::random::<f32>();
-        for ((m, n), k) in (64..=64).zip(64..=64).zip([1, 4, 64]) {
-            let a = (0..m * k).into_iter().map(gen).collect::<Vec<_>>();
-            let b = (0..k * n).into_iter().map(gen).collect::<Vec<_>>();
-            let c = (0..m * n).into_iter().map(|_| 0.0).collect::<Vec<_>>();
-            let mut dst = c.clone();
 
-            let plan = Plan::new_f32(m, n, k);
-            let beta = 2.5;
+    #[test]
+    fn test_plan_epilogue() {
+        fn check<T: TestScalar>() {
+            for (m, n, k) in [(1, 1, 1), (3, 5, 0), (9, 1, 7), (17, 13, 16), (70, 9, 150)] {
+                let a = random_vec::<T>(m * k);
+                let b = random_vec::<T>(k * n);
+                let prod = naive_product(m, n, k, &a, &b, false, false, None);
+                let scale = |i: usize, j: usize| T::from_real((i + 2 * j) as f64);
+
+                for (colmajor, dst_op, use_workspace) in [
+                    (true, Op::NoTrans, false),
+                    (true, Op::NoTrans, true),
+                    (false, Op::NoTrans, false),
+                    (false, Op::Trans, true),
+                    (false, Op::ConjTrans, false),
+                ] {
+                    let plan = if colmajor {
+                        Plan::<T>::new_colmajor_lhs_and_dst(m, n, k)
+                    } else {
+                        Plan::<T>::new_with_ops(m, n, k, Op::NoTrans, Op::NoTrans, dst_op)
+                    };
+                    // the stored `dst` has a row stride of 2 if the plan isn't column-major
+                    let (nrows, ncols) = dst_op.dims(m, n);
+                    let (dst_rs, dst_cs) = (if colmajor { 1 } else { 2 }, 2 * nrows);
+                    let c = random_vec::<T>(dst_cs * ncols);
 
-            unsafe {
-                plan.execute_unchecked(
-                    m,
-                    n,
-                    k,
-                    dst.as_mut_ptr(),
-                    1,
-                    m as isize,
-                    a.as_ptr(),
-                    1,
-                    m as isize,
-                    b.as_ptr(),
-                    1,
-                    k as isize,
-                    1.0,
-                    beta,
-                    false,
-                    false,
-                );
-            };
+                    let mut dst = c.clone();
+                    let mut visits = vec![0usize; m * n];
+                    let epilogue = |i: usize, j: usize, acc: T, old: T| {
+                        visits[i + j * m] += 1;
+                        old + scale(i, j) * acc
+                    };
+                    let dst_mat = MatMut::from_slice(
+                        &mut dst,
+                        nrows,
+                        ncols,
+                        dst_rs as isize,
+                        dst_cs as isize,
+                    );
+                    let lhs = MatRef::from_col_major_slice(&a, m, k);
+                    let rhs = MatRef::from_col_major_slice(&b, k, n);
+                    if use_workspace {
+                        let nan = MaybeUninit::new(T::from_real(f64::NAN));
+                        let mut workspace = vec![nan; plan.epilogue_workspace_size()];
+                        plan.execute_with_epilogue_with_workspace(
+                            &mut workspace,
+                            dst_mat,
+                            lhs,
+                            rhs,
+                            false,
+                            false,
+                            epilogue,
+                        );
+                    } else {
+                        plan.execute_with_epilogue(dst_mat, lhs, rhs, false, false, epilogue);
+                    }
+                    assert!(visits.iter().all(|&v| v == 1));
 
-            let mut expected_dst = c;
-            for i in 0..m {
-                for j in 0..n {
-                    let mut acc = 0.0f32;
-                    for depth in 0..k {
-                        acc = f32::mul_add(a[depth * m + i], b[j * k + depth], acc);
+                    for i in 0..m {
+                        for j in 0..n {
+                            let (row, col) = if dst_op == Op::NoTrans {
+                                (i, j)
+                            } else {
+                                (j, i)
+                            };
+                            let idx = row * dst_rs + col * dst_cs;
+                            // the epilogue sees the elements of `op(dst)`
+                            let expected = if dst_op == Op::ConjTrans {
+                                (c[idx].conj() + scale(i, j) * prod[i + j * m]).conj()
+                            } else {
+                                c[idx] + scale(i, j) * prod[i + j * m]
+                            };
+                            let tol = 1e-3 * (1 + i + 2 * j) as f64;
+                            assert!((dst[idx] - expected).abs2().sqrt() < tol);
+                        }
                     }
-                    expected_dst[j * m + i] = f32::mul_add(beta, acc, expected_dst[j * m + i]);
                 }
             }
-
-            for (dst, expected_dst) in dst.iter().zip(&expected_dst) {
-                assert!((dst - expected_dst).abs() < 1e-4);
-            }
         }
+
+        check::<f32>();
+        check::<f64>();
+        check::<c32>();
+        check::<c64>();
+    }
+
+    #[test]
+    fn test_plan_try_execute() {
+        let (m, n, k) = (5, 3, 4);
+        let a = vec![1.0f32; 2 * m * k];
+        let b = vec![1.0f32; k * n];
+        let mut c = vec![1.0f32; m * n + m * k];
+        let plan = Plan::new_colmajor_lhs_and_dst_f32(m, n, k);
+
+        let c = c.as_mut_ptr();
+        let run = |n: usize, dst_cs: isize, lhs: *const f32, lhs_rs: isize| unsafe {
+            plan.try_execute(
+                m,
+                n,
+                k,
+                c,
+                1,
+                dst_cs,
+                lhs,
+                lhs_rs,
+                m as isize * lhs_rs,
+                b.as_ptr(),
+                1,
+                k as isize,
+                1.0,
+                2.0,
+                false,
+                false,
+            )
+        };
+
+        assert!(run(n, m as isize, a.as_ptr(), 1) == Ok(()));
+        assert!((0..m * n).all(|i| unsafe { *c.add(i) } == 9.0));
+
+        assert!(
+            run(n + 1, m as isize, a.as_ptr(), 1)
+                == Err(PlanError::ShapeMismatch {
+                    expected: (m, n, k),
+                    found: (m, n + 1, k),
+                })
+        );
+        assert!(
+            run(n, m as isize, a.as_ptr(), 2)
+                == Err(PlanError::StrideMismatch {
+                    stride: "lhs_rs",
+                    expected: 1,
+                    found: 2,
+                })
+        );
+        assert!(
+            run(n, isize::MAX / 2, a.as_ptr(), 1)
+                == Err(PlanError::OffsetOverflow { operand: "dst" })
+        );
+        assert!(
+            run(n, m as isize, unsafe { c.add(m * n - 1) }, 1)
+                == Err(PlanError::Aliasing { operand: "lhs" })
+        );
+        assert!(run(n, m as isize, unsafe { c.add(m * n) }, 1) == Ok(()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mat_out_of_bounds() {
+        let a = [0.0f32; 15];
+        MatRef::from_col_major_slice(&a, 4, 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_plan_safe_layout_mismatch() {
+        let a = [0.0f32; 16];
+        let b = [0.0f32; 16];
+        let mut c = [0.0f32; 16];
+        Plan::new_colmajor_lhs_and_dst_f32(4, 4, 4).execute(
+            MatMut::from_row_major_slice(&mut c, 4, 4),
+            MatRef::from_col_major_slice(&a, 4, 4),
+            MatRef::from_col_major_slice(&b, 4, 4),
+            1.0,
+            1.0,
+            false,
+            false,
+        );
     }
 
     #[test]
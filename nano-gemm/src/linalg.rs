@@ -0,0 +1,890 @@
+//! Factorizations of small dense matrices, and the linear system solves built on them.
+//!
+//! The factorizations are blocked: a few columns are factored at a time with scalar code, then the
+//! rest of the matrix is updated with the kernels of [`Plan`], [`SyrkPlan`] and [`TrsmPlan`].
+//!
+//! # Batched layout
+//!
+//! The `factor_batched` and `solve_batched` functions only accept packed column-major buffers:
+//! each matrix of the batch is stored contiguously in column-major order with no padding between
+//! columns, and the matrices follow each other with no gap. Strided or row-major batches must be
+//! copied into this layout first, or handled one matrix at a time with the `factor` and `solve`
+//! methods, which accept any strides.
+
+use crate::{
+    FactorError, GemmScalar, MatMut, MatRef, Op, Plan, PlanBuilder, Side, Structure, SyrkPlan,
+    Triangle, TrsmPlan,
+};
+use core::mem::MaybeUninit;
+use equator::assert;
+
+// number of columns factored at once, before the rest of the matrix is updated
+const BS: usize = 16;
+
+// largest number of columns for which the workspace of `QrPlan` is allocated on the stack
+const QR_STACK_N: usize = 64;
+const QR_STACK_SIZE: usize = BS * BS + 2 * BS * QR_STACK_N;
+
+/// Returns the offset and the size of the blocks of columns of a matrix with `n` columns.
+fn blocks(n: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..n.div_ceil(BS)).map(move |j| (j * BS, Ord::min(BS, n - j * BS)))
+}
+
+/// Returns the number of matrices of `size` elements stored in a buffer of `len` elements.
+#[track_caller]
+fn batch_len(len: usize, size: usize) -> usize {
+    if size == 0 {
+        0
+    } else {
+        assert!(len % size == 0);
+        len / size
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Raw<T> {
+    ptr: *mut T,
+    rs: isize,
+    cs: isize,
+}
+
+impl<T> Raw<T> {
+    #[inline]
+    fn col_major(ptr: *mut T, nrows: usize) -> Self {
+        Self {
+            ptr,
+            rs: 1,
+            cs: nrows as isize,
+        }
+    }
+
+    #[inline]
+    fn from_mut(mat: &mut MatMut<'_, T>) -> Self {
+        Self {
+            ptr: mat.as_mut_ptr(),
+            rs: mat.row_stride(),
+            cs: mat.col_stride(),
+        }
+    }
+
+    // only used for reading
+    #[inline]
+    fn from_ref(mat: &MatRef<'_, T>) -> Self {
+        Self {
+            ptr: mat.as_ptr() as *mut T,
+            rs: mat.row_stride(),
+            cs: mat.col_stride(),
+        }
+    }
+
+    #[inline(always)]
+    fn at(self, i: usize, j: usize) -> *mut T {
+        self.ptr
+            .wrapping_offset(i as isize * self.rs + j as isize * self.cs)
+    }
+}
+
+/// Plan for the Cholesky factorization `a = l * l^H` of an `n × n` Hermitian positive definite
+/// matrix, and for solving `a * x = b` with `nrhs` right-hand sides.
+///
+/// Only the lower triangle of `a` is read, and it's overwritten by `l`.
+#[derive(Clone)]
+pub struct CholeskyPlan<T> {
+    n: usize,
+    nrhs: usize,
+    // for each block of columns, the solve of the panel below the diagonal block, and the update
+    // of the trailing matrix
+    panels: Vec<(TrsmPlan<T>, SyrkPlan<T>)>,
+    solve_l: TrsmPlan<T>,
+    solve_lh: TrsmPlan<T>,
+}
+
+impl<T: GemmScalar> CholeskyPlan<T> {
+    /// Creates a plan for factoring an `n × n` matrix, and solving systems with `nrhs`
+    /// right-hand sides.
+    #[track_caller]
+    pub fn new(n: usize, nrhs: usize) -> Self {
+        let lower = Structure::Triangular(Triangle::Lower);
+        Self {
+            n,
+            nrhs,
+            panels: blocks(n)
+                .map(|(j, bs)| {
+                    let rest = n - j - bs;
+                    (
+                        TrsmPlan::new(rest, bs, Side::Right, lower, Op::ConjTrans),
+                        SyrkPlan::new(rest, bs, Triangle::Lower),
+                    )
+                })
+                .collect(),
+            solve_l: TrsmPlan::new(n, nrhs, Side::Left, lower, Op::NoTrans),
+            solve_lh: TrsmPlan::new(n, nrhs, Side::Left, lower, Op::ConjTrans),
+        }
+    }
+
+    /// Factors `a` in place.
+    ///
+    /// # Errors
+    /// Returns an error if `a` isn't positive definite, in which case it holds a partial
+    /// factorization.
+    ///
+    /// # Panics
+    /// Panics if `a` isn't an `n × n` matrix.
+    #[track_caller]
+    pub fn factor(&self, a: MatMut<'_, T>) -> Result<(), FactorError> {
+        let mut a = a;
+        assert!(all(a.nrows() == self.n, a.ncols() == self.n));
+        unsafe { self.factor_impl(Raw::from_mut(&mut a)) }
+    }
+
+    /// Factors each of the matrices in `a` in place.
+    ///
+    /// # Errors
+    /// Returns the index of the first matrix that isn't positive definite along with the error.
+    /// The matrices after it are left untouched.
+    ///
+    /// # Panics
+    /// Panics if the length of `a` isn't a multiple of `n * n`.
+    #[track_caller]
+    pub fn factor_batched(&self, a: &mut [T]) -> Result<(), (usize, FactorError)> {
+        let size = self.n * self.n;
+        for idx in 0..batch_len(a.len(), size) {
+            let a = Raw::col_major(a[idx * size..].as_mut_ptr(), self.n);
+            unsafe { self.factor_impl(a) }.map_err(|err| (idx, err))?;
+        }
+        Ok(())
+    }
+
+    unsafe fn factor_impl(&self, a: Raw<T>) -> Result<(), FactorError> {
+        for ((j, bs), (trsm, syrk)) in blocks(self.n).zip(&self.panels) {
+            for c in j..j + bs {
+                let mut d = (*a.at(c, c)).re();
+                for p in j..c {
+                    d -= (*a.at(c, p)).abs2();
+                }
+                if d.is_nan() || d <= 0.0 {
+                    return Err(FactorError::NotPositiveDefinite { col: c });
+                }
+                let d = d.sqrt();
+                *a.at(c, c) = T::from_real(d);
+
+                let inv = T::from_real(1.0 / d);
+                for r in c + 1..j + bs {
+                    let mut x = *a.at(r, c);
+                    for p in j..c {
+                        x = x - *a.at(r, p) * (*a.at(c, p)).conj();
+                    }
+                    *a.at(r, c) = x * inv;
+                }
+            }
+
+            let rest = self.n - j - bs;
+            if rest != 0 {
+                // l21 = a21 * l11^-H
+                trsm.execute_unchecked(
+                    rest,
+                    bs,
+                    a.at(j, j),
+                    a.rs,
+                    a.cs,
+                    a.at(j + bs, j),
+                    a.rs,
+                    a.cs,
+                    false,
+                );
+                // a22 -= l21 * l21^H
+                syrk.execute_unchecked(
+                    rest,
+                    bs,
+                    a.at(j + bs, j + bs),
+                    a.rs,
+                    a.cs,
+                    a.at(j + bs, j),
+                    a.rs,
+                    a.cs,
+                    T::ONE,
+                    -T::ONE,
+                    true,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Solves `a * x = b` in place of `b`, where `l` is the factor computed by
+    /// [`CholeskyPlan::factor`].
+    ///
+    /// # Panics
+    /// Panics if `l` isn't an `n × n` matrix, or `b` isn't an `n × nrhs` matrix.
+    #[track_caller]
+    pub fn solve(&self, l: MatRef<'_, T>, b: MatMut<'_, T>) {
+        let mut b = b;
+        assert!(all(
+            l.nrows() == self.n,
+            l.ncols() == self.n,
+            b.nrows() == self.n,
+            b.ncols() == self.nrhs,
+        ));
+        unsafe { self.solve_impl(Raw::from_ref(&l), Raw::from_mut(&mut b)) }
+    }
+
+    /// Solves each of the systems in place of `b`, where `l` holds the factors computed by
+    /// [`CholeskyPlan::factor_batched`].
+    ///
+    /// # Panics
+    /// Panics if the length of `l` isn't a multiple of `n * n`, or if `b` doesn't hold as many
+    /// `n × nrhs` matrices.
+    #[track_caller]
+    pub fn solve_batched(&self, l: &[T], b: &mut [T]) {
+        let (l_size, b_size) = (self.n * self.n, self.n * self.nrhs);
+        let batch = batch_len(l.len(), l_size);
+        assert!(b.len() == batch * b_size);
+        for idx in 0..batch {
+            let l = Raw::col_major(l[idx * l_size..].as_ptr() as *mut T, self.n);
+            let b = Raw::col_major(b[idx * b_size..].as_mut_ptr(), self.n);
+            unsafe { self.solve_impl(l, b) };
+        }
+    }
+
+    unsafe fn solve_impl(&self, l: Raw<T>, b: Raw<T>) {
+        let (n, nrhs) = (self.n, self.nrhs);
+        self.solve_l
+            .execute_unchecked(n, nrhs, l.ptr, l.rs, l.cs, b.ptr, b.rs, b.cs, false);
+        self.solve_lh
+            .execute_unchecked(n, nrhs, l.ptr, l.rs, l.cs, b.ptr, b.rs, b.cs, false);
+    }
+}
+
+/// Plan for the LU factorization with partial pivoting `p * a = l * u` of an `n × n` matrix, and
+/// for solving `a * x = b` with `nrhs` right-hand sides.
+///
+/// `a` is overwritten by `u` and the strictly lower part of `l`, whose diagonal is implicitly one.
+/// The permutation is stored as the row swaps applied to `a` in order, where row `i` is swapped
+/// with row `perm[i]`.
+#[derive(Clone)]
+pub struct LuPlan<T> {
+    n: usize,
+    nrhs: usize,
+    // for each block of columns, the solve of the block row right of the diagonal block, and the
+    // update of the trailing matrix
+    panels: Vec<(TrsmPlan<T>, Plan<T>)>,
+    solve_l: TrsmPlan<T>,
+    solve_u: TrsmPlan<T>,
+}
+
+impl<T: GemmScalar> LuPlan<T> {
+    /// Creates a plan for factoring an `n × n` matrix, and solving systems with `nrhs`
+    /// right-hand sides.
+    #[track_caller]
+    pub fn new(n: usize, nrhs: usize) -> Self {
+        let unit_lower = Structure::UnitTriangular(Triangle::Lower);
+        Self {
+            n,
+            nrhs,
+            panels: blocks(n)
+                .map(|(j, bs)| {
+                    let rest = n - j - bs;
+                    (
+                        TrsmPlan::new(bs, rest, Side::Left, unit_lower, Op::NoTrans),
+                        Plan::new(rest, rest, bs),
+                    )
+                })
+                .collect(),
+            solve_l: TrsmPlan::new(n, nrhs, Side::Left, unit_lower, Op::NoTrans),
+            solve_u: TrsmPlan::new(
+                n,
+                nrhs,
+                Side::Left,
+                Structure::Triangular(Triangle::Upper),
+                Op::NoTrans,
+            ),
+        }
+    }
+
+    /// Factors `a` in place, storing the row swaps to `perm`.
+    ///
+    /// # Errors
+    /// Returns an error if `a` is singular, in which case it holds a partial factorization.
+    ///
+    /// # Panics
+    /// Panics if `a` isn't an `n × n` matrix, or `perm` doesn't have `n` elements.
+    #[track_caller]
+    pub fn factor(&self, a: MatMut<'_, T>, perm: &mut [usize]) -> Result<(), FactorError> {
+        let mut a = a;
+        assert!(all(
+            a.nrows() == self.n,
+            a.ncols() == self.n,
+            perm.len() == self.n,
+        ));
+        unsafe { self.factor_impl(Raw::from_mut(&mut a), perm) }
+    }
+
+    /// Factors each of the matrices in `a` in place, storing the row swaps of each one to the
+    /// consecutive chunks of `n` elements of `perm`.
+    ///
+    /// # Errors
+    /// Returns the index of the first matrix that is singular along with the error. The matrices
+    /// after it are left untouched.
+    ///
+    /// # Panics
+    /// Panics if the length of `a` isn't a multiple of `n * n`, or if `perm` doesn't have `n`
+    /// elements per matrix.
+    #[track_caller]
+    pub fn factor_batched(
+        &self,
+        a: &mut [T],
+        perm: &mut [usize],
+    ) -> Result<(), (usize, FactorError)> {
+        let n = self.n;
+        let batch = batch_len(a.len(), n * n);
+        assert!(perm.len() == batch * n);
+        for idx in 0..batch {
+            let a = Raw::col_major(a[idx * n * n..].as_mut_ptr(), n);
+            let perm = &mut perm[idx * n..][..n];
+            unsafe { self.factor_impl(a, perm) }.map_err(|err| (idx, err))?;
+        }
+        Ok(())
+    }
+
+    unsafe fn factor_impl(&self, a: Raw<T>, perm: &mut [usize]) -> Result<(), FactorError> {
+        let n = self.n;
+        for ((j, bs), (trsm, gemm)) in blocks(n).zip(&self.panels) {
+            for (c, perm) in (j..j + bs).zip(&mut perm[j..j + bs]) {
+                let mut pivot = c;
+                let mut max = (*a.at(c, c)).abs2();
+                for r in c + 1..n {
+                    let x = (*a.at(r, c)).abs2();
+                    if x > max {
+                        pivot = r;
+                        max = x;
+                    }
+                }
+                *perm = pivot;
+                if max == 0.0 {
+                    return Err(FactorError::Singular { col: c });
+                }
+                if pivot != c {
+                    for q in 0..n {
+                        core::ptr::swap(a.at(c, q), a.at(pivot, q));
+                    }
+                }
+
+                let inv = T::ONE / *a.at(c, c);
+                for r in c + 1..n {
+                    *a.at(r, c) = *a.at(r, c) * inv;
+                }
+                // the rest of the panel is updated right away
+                for q in c + 1..j + bs {
+                    let u = *a.at(c, q);
+                    for r in c + 1..n {
+                        *a.at(r, q) = *a.at(r, q) - *a.at(r, c) * u;
+                    }
+                }
+            }
+
+            let rest = n - j - bs;
+            if rest != 0 {
+                // u12 = l11^-1 * a12
+                trsm.execute_unchecked(
+                    bs,
+                    rest,
+                    a.at(j, j),
+                    a.rs,
+                    a.cs,
+                    a.at(j, j + bs),
+                    a.rs,
+                    a.cs,
+                    false,
+                );
+                // a22 -= l21 * u12
+                gemm.execute_unchecked(
+                    rest,
+                    rest,
+                    bs,
+                    a.at(j + bs, j + bs),
+                    a.rs,
+                    a.cs,
+                    a.at(j + bs, j),
+                    a.rs,
+                    a.cs,
+                    a.at(j, j + bs),
+                    a.rs,
+                    a.cs,
+                    T::ONE,
+                    -T::ONE,
+                    false,
+                    false,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Solves `a * x = b` in place of `b`, where `lu` and `perm` are the factors computed by
+    /// [`LuPlan::factor`].
+    ///
+    /// # Panics
+    /// Panics if `lu` isn't an `n × n` matrix, `b` isn't an `n × nrhs` matrix, or `perm` doesn't
+    /// hold `n` row indices.
+    #[track_caller]
+    pub fn solve(&self, lu: MatRef<'_, T>, perm: &[usize], b: MatMut<'_, T>) {
+        let mut b = b;
+        assert!(all(
+            lu.nrows() == self.n,
+            lu.ncols() == self.n,
+            b.nrows() == self.n,
+            b.ncols() == self.nrhs,
+            perm.len() == self.n,
+        ));
+        assert!(perm.iter().all(|&p| p < self.n));
+        unsafe { self.solve_impl(Raw::from_ref(&lu), perm, Raw::from_mut(&mut b)) }
+    }
+
+    /// Solves each of the systems in place of `b`, where `lu` and `perm` hold the factors computed
+    /// by [`LuPlan::factor_batched`].
+    ///
+    /// # Panics
+    /// Panics if the length of `lu` isn't a multiple of `n * n`, or if `perm` and `b` don't hold
+    /// as many permutations and `n × nrhs` matrices.
+    #[track_caller]
+    pub fn solve_batched(&self, lu: &[T], perm: &[usize], b: &mut [T]) {
+        let (n, b_size) = (self.n, self.n * self.nrhs);
+        let batch = batch_len(lu.len(), n * n);
+        assert!(all(perm.len() == batch * n, b.len() == batch * b_size));
+        assert!(perm.iter().all(|&p| p < n));
+        for idx in 0..batch {
+            let lu = Raw::col_major(lu[idx * n * n..].as_ptr() as *mut T, n);
+            let b = Raw::col_major(b[idx * b_size..].as_mut_ptr(), n);
+            unsafe { self.solve_impl(lu, &perm[idx * n..][..n], b) };
+        }
+    }
+
+    unsafe fn solve_impl(&self, lu: Raw<T>, perm: &[usize], b: Raw<T>) {
+        let (n, nrhs) = (self.n, self.nrhs);
+        for (i, &p) in perm.iter().enumerate() {
+            if p != i {
+                for q in 0..nrhs {
+                    core::ptr::swap(b.at(i, q), b.at(p, q));
+                }
+            }
+        }
+        self.solve_l
+            .execute_unchecked(n, nrhs, lu.ptr, lu.rs, lu.cs, b.ptr, b.rs, b.cs, false);
+        self.solve_u
+            .execute_unchecked(n, nrhs, lu.ptr, lu.rs, lu.cs, b.ptr, b.rs, b.cs, false);
+    }
+}
+
+// updates of the columns right of a block of householder reflectors `v`, which is split into its
+// unit lower triangular top and the rest
+#[derive(Copy, Clone)]
+struct QrPanel<T> {
+    // `w = v^H * a2`
+    w_top: Plan<T>,
+    w_bot: Plan<T>,
+    // `w = t^H * w`
+    tw: Plan<T>,
+    // `a2 -= v * w`
+    a_top: Plan<T>,
+    a_bot: Plan<T>,
+}
+
+/// Plan for the QR factorization `a = q * r` of an `m × n` matrix with `m >= n`, using
+/// Householder reflectors, and for solving the least squares problems `min |a * x - b|` with
+/// `nrhs` right-hand sides.
+///
+/// `a` is overwritten by `r` and the reflectors below the diagonal. `q` is the product of the
+/// reflectors `I - tau[i] * v_i * v_i^H`, where the first nonzero element of `v_i` is an implicit
+/// one.
+#[derive(Clone)]
+pub struct QrPlan<T> {
+    m: usize,
+    n: usize,
+    nrhs: usize,
+    panels: Vec<QrPanel<T>>,
+    solve_r: TrsmPlan<T>,
+}
+
+impl<T: GemmScalar> QrPlan<T> {
+    /// Creates a plan for factoring an `m × n` matrix, and solving least squares problems with
+    /// `nrhs` right-hand sides.
+    ///
+    /// # Panics
+    /// Panics if `m < n`.
+    #[track_caller]
+    pub fn new(m: usize, n: usize, nrhs: usize) -> Self {
+        assert!(m >= n);
+        let unit_lower = Structure::UnitTriangular(Triangle::Lower);
+        Self {
+            m,
+            n,
+            nrhs,
+            panels: blocks(n)
+                .map(|(j, bs)| {
+                    let (m2, n2) = (m - j - bs, n - j - bs);
+                    QrPanel {
+                        w_top: PlanBuilder::new(bs, n2, bs)
+                            .lhs_structure(unit_lower)
+                            .lhs_op(Op::ConjTrans)
+                            .build(),
+                        w_bot: PlanBuilder::new(bs, n2, m2).lhs_op(Op::ConjTrans).build(),
+                        tw: PlanBuilder::new(bs, n2, bs)
+                            .lhs_structure(Structure::Triangular(Triangle::Upper))
+                            .lhs_op(Op::ConjTrans)
+                            .build(),
+                        a_top: PlanBuilder::new(bs, n2, bs)
+                            .lhs_structure(unit_lower)
+                            .build(),
+                        a_bot: Plan::new(m2, n2, bs),
+                    }
+                })
+                .collect(),
+            solve_r: TrsmPlan::new(
+                n,
+                nrhs,
+                Side::Left,
+                Structure::Triangular(Triangle::Upper),
+                Op::NoTrans,
+            ),
+        }
+    }
+
+    /// Returns the number of elements of the workspace needed by
+    /// [`QrPlan::factor_with_workspace`] and [`QrPlan::factor_batched_with_workspace`].
+    #[inline]
+    pub fn workspace_size(&self) -> usize {
+        BS * BS + 2 * BS * self.n
+    }
+
+    /// Factors `a` in place, storing the scaling factors of the reflectors to `tau`.
+    ///
+    /// The temporary buffers are allocated on the stack if `n <= 64`, and on the heap otherwise.
+    ///
+    /// # Panics
+    /// Panics if `a` isn't an `m × n` matrix, or `tau` doesn't have `n` elements.
+    #[track_caller]
+    pub fn factor(&self, a: MatMut<'_, T>, tau: &mut [T]) {
+        self.with_workspace(|workspace| self.factor_with_workspace(workspace, a, tau))
+    }
+
+    /// Same as [`QrPlan::factor`], but uses `workspace` for the temporary buffers instead of
+    /// allocating them.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`QrPlan::factor`], or if `workspace` holds fewer than
+    /// [`QrPlan::workspace_size`] elements.
+    #[track_caller]
+    pub fn factor_with_workspace(
+        &self,
+        workspace: &mut [MaybeUninit<T>],
+        a: MatMut<'_, T>,
+        tau: &mut [T],
+    ) {
+        let mut a = a;
+        assert!(all(
+            a.nrows() == self.m,
+            a.ncols() == self.n,
+            tau.len() == self.n,
+            workspace.len() >= self.workspace_size(),
+        ));
+        unsafe { self.factor_impl(Raw::from_mut(&mut a), tau, workspace.as_mut_ptr() as *mut T) }
+    }
+
+    /// Factors each of the matrices in `a` in place, storing the scaling factors of the reflectors
+    /// of each one to the consecutive chunks of `n` elements of `tau`.
+    ///
+    /// The temporary buffers are allocated once for the whole batch, on the stack if `n <= 64`,
+    /// and on the heap otherwise.
+    ///
+    /// # Panics
+    /// Panics if the length of `a` isn't a multiple of `m * n`, or if `tau` doesn't have `n`
+    /// elements per matrix.
+    #[track_caller]
+    pub fn factor_batched(&self, a: &mut [T], tau: &mut [T]) {
+        self.with_workspace(|workspace| self.factor_batched_with_workspace(workspace, a, tau))
+    }
+
+    /// Same as [`QrPlan::factor_batched`], but uses `workspace` for the temporary buffers instead
+    /// of allocating them.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`QrPlan::factor_batched`], or if `workspace` holds
+    /// fewer than [`QrPlan::workspace_size`] elements.
+    #[track_caller]
+    pub fn factor_batched_with_workspace(
+        &self,
+        workspace: &mut [MaybeUninit<T>],
+        a: &mut [T],
+        tau: &mut [T],
+    ) {
+        let (m, n) = (self.m, self.n);
+        let batch = batch_len(a.len(), m * n);
+        assert!(all(
+            tau.len() == batch * n,
+            workspace.len() >= self.workspace_size(),
+        ));
+        let work = workspace.as_mut_ptr() as *mut T;
+        for idx in 0..batch {
+            let a = Raw::col_major(a[idx * m * n..].as_mut_ptr(), m);
+            unsafe { self.factor_impl(a, &mut tau[idx * n..][..n], work) };
+        }
+    }
+
+    /// Calls `f` with a workspace of [`QrPlan::workspace_size`] elements, that lives on the stack
+    /// if `n <= QR_STACK_N`.
+    #[track_caller]
+    fn with_workspace<R>(&self, f: impl FnOnce(&mut [MaybeUninit<T>]) -> R) -> R {
+        if self.n <= QR_STACK_N {
+            let mut workspace: MaybeUninit<[T; QR_STACK_SIZE]> = MaybeUninit::uninit();
+            // the elements are `MaybeUninit`, so the uninitialized array is a valid slice of them
+            let workspace = unsafe {
+                core::slice::from_raw_parts_mut(
+                    workspace.as_mut_ptr() as *mut MaybeUninit<T>,
+                    QR_STACK_SIZE,
+                )
+            };
+            f(workspace)
+        } else {
+            f(&mut vec![MaybeUninit::uninit(); self.workspace_size()])
+        }
+    }
+
+    // `work` must be valid for writes of `workspace_size` elements. the buffers are always written
+    // before they're read
+    unsafe fn factor_impl(&self, a: Raw<T>, tau: &mut [T], work: *mut T) {
+        let (m, n) = (self.m, self.n);
+        let t = work;
+        let w = t.add(BS * BS);
+        let tw = w.add(BS * n);
+
+        for ((j, bs), panel) in blocks(n).zip(&self.panels) {
+            for (c, tau) in (j..j + bs).zip(&mut tau[j..j + bs]) {
+                *tau = householder(a, c, m);
+                apply_reflector(a, c, m, tau.conj(), a, c + 1..j + bs);
+            }
+
+            let (m2, n2) = (m - j - bs, n - j - bs);
+            if n2 == 0 {
+                continue;
+            }
+
+            // `t` is the upper triangular factor such that the product of the reflectors of the
+            // block is `I - v * t * v^H`
+            let t_at = |i: usize, j: usize| t.add(i + j * bs);
+            for i in 0..bs {
+                for p in 0..i {
+                    let mut z = (*a.at(j + i, j + p)).conj();
+                    for r in j + i + 1..m {
+                        z = z + (*a.at(r, j + p)).conj() * *a.at(r, j + i);
+                    }
+                    *t_at(p, i) = z;
+                }
+                for p in 0..i {
+                    let mut x = T::from_real(0.0);
+                    for q in p..i {
+                        x = x + *t_at(p, q) * *t_at(q, i);
+                    }
+                    *t_at(p, i) = -tau[j + i] * x;
+                }
+                *t_at(i, i) = tau[j + i];
+            }
+
+            let (v_top, v_bot) = (a.at(j, j), a.at(j + bs, j));
+            let (a_top, a_bot) = (a.at(j, j + bs), a.at(j + bs, j + bs));
+            let zero = T::from_real(0.0);
+            let bs_ = bs as isize;
+
+            panel.w_bot.execute_unchecked(
+                bs,
+                n2,
+                m2,
+                w,
+                1,
+                bs_,
+                v_bot,
+                a.rs,
+                a.cs,
+                a_bot,
+                a.rs,
+                a.cs,
+                zero,
+                T::ONE,
+                false,
+                false,
+            );
+            panel.w_top.execute_unchecked(
+                bs,
+                n2,
+                bs,
+                w,
+                1,
+                bs_,
+                v_top,
+                a.rs,
+                a.cs,
+                a_top,
+                a.rs,
+                a.cs,
+                T::ONE,
+                T::ONE,
+                false,
+                false,
+            );
+            panel.tw.execute_unchecked(
+                bs,
+                n2,
+                bs,
+                tw,
+                1,
+                bs_,
+                t,
+                1,
+                bs_,
+                w,
+                1,
+                bs_,
+                zero,
+                T::ONE,
+                false,
+                false,
+            );
+            panel.a_top.execute_unchecked(
+                bs,
+                n2,
+                bs,
+                a_top,
+                a.rs,
+                a.cs,
+                v_top,
+                a.rs,
+                a.cs,
+                tw,
+                1,
+                bs_,
+                T::ONE,
+                -T::ONE,
+                false,
+                false,
+            );
+            panel.a_bot.execute_unchecked(
+                m2,
+                n2,
+                bs,
+                a_bot,
+                a.rs,
+                a.cs,
+                v_bot,
+                a.rs,
+                a.cs,
+                tw,
+                1,
+                bs_,
+                T::ONE,
+                -T::ONE,
+                false,
+                false,
+            );
+        }
+    }
+
+    /// Solves the least squares problem `min |a * x - b|` in place of `b`, where `qr` and `tau`
+    /// are the factors computed by [`QrPlan::factor`]. The solution is stored to the first `n`
+    /// rows of `b`.
+    ///
+    /// # Panics
+    /// Panics if `qr` isn't an `m × n` matrix, `b` isn't an `m × nrhs` matrix, or `tau` doesn't
+    /// have `n` elements.
+    #[track_caller]
+    pub fn solve(&self, qr: MatRef<'_, T>, tau: &[T], b: MatMut<'_, T>) {
+        let mut b = b;
+        assert!(all(
+            qr.nrows() == self.m,
+            qr.ncols() == self.n,
+            b.nrows() == self.m,
+            b.ncols() == self.nrhs,
+            tau.len() == self.n,
+        ));
+        unsafe { self.solve_impl(Raw::from_ref(&qr), tau, Raw::from_mut(&mut b)) }
+    }
+
+    /// Solves each of the least squares problems in place of `b`, where `qr` and `tau` hold the
+    /// factors computed by [`QrPlan::factor_batched`].
+    ///
+    /// # Panics
+    /// Panics if the length of `qr` isn't a multiple of `m * n`, or if `tau` and `b` don't hold
+    /// as many scaling factors and `m × nrhs` matrices.
+    #[track_caller]
+    pub fn solve_batched(&self, qr: &[T], tau: &[T], b: &mut [T]) {
+        let (m, n, b_size) = (self.m, self.n, self.m * self.nrhs);
+        let batch = batch_len(qr.len(), m * n);
+        assert!(all(tau.len() == batch * n, b.len() == batch * b_size));
+        for idx in 0..batch {
+            let qr = Raw::col_major(qr[idx * m * n..].as_ptr() as *mut T, m);
+            let b = Raw::col_major(b[idx * b_size..].as_mut_ptr(), m);
+            unsafe { self.solve_impl(qr, &tau[idx * n..][..n], b) };
+        }
+    }
+
+    unsafe fn solve_impl(&self, qr: Raw<T>, tau: &[T], b: Raw<T>) {
+        let (m, n, nrhs) = (self.m, self.n, self.nrhs);
+        // b = q^H * b
+        for (c, tau) in tau.iter().enumerate() {
+            apply_reflector(qr, c, m, tau.conj(), b, 0..nrhs);
+        }
+        self.solve_r
+            .execute_unchecked(n, nrhs, qr.ptr, qr.rs, qr.cs, b.ptr, b.rs, b.cs, false);
+    }
+}
+
+/// Computes the reflector `I - tau * v * v^H` mapping the column `c` of `a` below the diagonal to
+/// a multiple of its first element, and returns `tau`.
+///
+/// `a` is overwritten by the result of the reflection on the diagonal, and by `v` below it.
+unsafe fn householder<T: GemmScalar>(a: Raw<T>, c: usize, m: usize) -> T {
+    let alpha = *a.at(c, c);
+    let mut norm2 = 0.0;
+    for r in c + 1..m {
+        norm2 += (*a.at(r, c)).abs2();
+    }
+    if norm2 == 0.0 && alpha == alpha.real() {
+        return T::from_real(0.0);
+    }
+
+    let mut beta = (alpha.abs2() + norm2).sqrt();
+    if alpha.re() >= 0.0 {
+        beta = -beta;
+    }
+    let beta = T::from_real(beta);
+    let scale = T::ONE / (alpha - beta);
+    for r in c + 1..m {
+        *a.at(r, c) = *a.at(r, c) * scale;
+    }
+    *a.at(c, c) = beta;
+    (beta - alpha) / beta
+}
+
+/// Applies the reflector `I - tau * v * v^H` to the given columns of `dst`, where `v` is stored
+/// in the column `c` of `qr` below the diagonal.
+unsafe fn apply_reflector<T: GemmScalar>(
+    qr: Raw<T>,
+    c: usize,
+    m: usize,
+    tau: T,
+    dst: Raw<T>,
+    cols: core::ops::Range<usize>,
+) {
+    if tau == T::from_real(0.0) {
+        return;
+    }
+    for q in cols {
+        let mut w = *dst.at(c, q);
+        for r in c + 1..m {
+            w = w + (*qr.at(r, c)).conj() * *dst.at(r, q);
+        }
+        let w = tau * w;
+        *dst.at(c, q) = *dst.at(c, q) - w;
+        for r in c + 1..m {
+            *dst.at(r, q) = *dst.at(r, q) - *qr.at(r, c) * w;
+        }
+    }
+}
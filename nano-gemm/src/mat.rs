@@ -0,0 +1,233 @@
+use core::marker::PhantomData;
+use equator::assert;
+
+/// Returns the offset one past the furthest element of an `nrows × ncols` matrix with the given
+/// strides, or `None` if an element would lie before the start of the storage or the offset
+/// overflows.
+#[inline]
+pub(crate) fn storage_len(
+    nrows: usize,
+    ncols: usize,
+    row_stride: isize,
+    col_stride: isize,
+) -> Option<usize> {
+    if nrows == 0 || ncols == 0 {
+        return Some(0);
+    }
+    let last_row = isize::try_from(nrows - 1).ok()?;
+    let last_col = isize::try_from(ncols - 1).ok()?;
+    let row = last_row.checked_mul(row_stride)?;
+    let col = last_col.checked_mul(col_stride)?;
+
+    if row < 0 || col < 0 {
+        return None;
+    }
+    usize::try_from(row.checked_add(col)?.checked_add(1)?).ok()
+}
+
+/// Returns the stride between consecutive rows or columns of a contiguous matrix, where each one
+/// holds `len` elements.
+#[inline]
+#[track_caller]
+fn contiguous_stride(len: usize) -> isize {
+    isize::try_from(len).expect("the dimensions of the matrix must fit in an `isize`")
+}
+
+/// Immutable matrix view over a slice.
+///
+/// The element at row `i` and column `j` is stored at index `i * row_stride + j * col_stride`.
+#[derive(Copy, Clone, Debug)]
+pub struct MatRef<'a, T> {
+    ptr: *const T,
+    nrows: usize,
+    ncols: usize,
+    row_stride: isize,
+    col_stride: isize,
+    __marker: PhantomData<&'a [T]>,
+}
+
+/// Mutable matrix view over a slice.
+///
+/// The element at row `i` and column `j` is stored at index `i * row_stride + j * col_stride`.
+#[derive(Debug)]
+pub struct MatMut<'a, T> {
+    ptr: *mut T,
+    nrows: usize,
+    ncols: usize,
+    row_stride: isize,
+    col_stride: isize,
+    __marker: PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<T: Sync> Sync for MatRef<'_, T> {}
+unsafe impl<T: Sync> Send for MatRef<'_, T> {}
+unsafe impl<T: Sync> Sync for MatMut<'_, T> {}
+unsafe impl<T: Send> Send for MatMut<'_, T> {}
+
+impl<'a, T> MatRef<'a, T> {
+    /// Creates a view over `data` with the given dimensions and strides.
+    ///
+    /// # Panics
+    /// Panics if any element of the matrix lies outside of `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_slice(
+        data: &'a [T],
+        nrows: usize,
+        ncols: usize,
+        row_stride: isize,
+        col_stride: isize,
+    ) -> Self {
+        let len = storage_len(nrows, ncols, row_stride, col_stride);
+        assert!(len.is_some_and(|len| len <= data.len()));
+        Self {
+            ptr: data.as_ptr(),
+            nrows,
+            ncols,
+            row_stride,
+            col_stride,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Creates a view over a column-major matrix stored contiguously in `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_col_major_slice(data: &'a [T], nrows: usize, ncols: usize) -> Self {
+        Self::from_slice(data, nrows, ncols, 1, contiguous_stride(nrows))
+    }
+
+    /// Creates a view over a row-major matrix stored contiguously in `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_row_major_slice(data: &'a [T], nrows: usize, ncols: usize) -> Self {
+        Self::from_slice(data, nrows, ncols, contiguous_stride(ncols), 1)
+    }
+
+    #[inline]
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+    #[inline]
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+    #[inline]
+    pub fn row_stride(&self) -> isize {
+        self.row_stride
+    }
+    #[inline]
+    pub fn col_stride(&self) -> isize {
+        self.col_stride
+    }
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+}
+
+impl<'a, T> MatMut<'a, T> {
+    /// Creates a view over `data` with the given dimensions and strides.
+    ///
+    /// # Panics
+    /// Panics if any element of the matrix lies outside of `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_slice(
+        data: &'a mut [T],
+        nrows: usize,
+        ncols: usize,
+        row_stride: isize,
+        col_stride: isize,
+    ) -> Self {
+        let len = storage_len(nrows, ncols, row_stride, col_stride);
+        assert!(len.is_some_and(|len| len <= data.len()));
+        Self {
+            ptr: data.as_mut_ptr(),
+            nrows,
+            ncols,
+            row_stride,
+            col_stride,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Creates a view over a column-major matrix stored contiguously in `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_col_major_slice(data: &'a mut [T], nrows: usize, ncols: usize) -> Self {
+        Self::from_slice(data, nrows, ncols, 1, contiguous_stride(nrows))
+    }
+
+    /// Creates a view over a row-major matrix stored contiguously in `data`.
+    #[inline]
+    #[track_caller]
+    pub fn from_row_major_slice(data: &'a mut [T], nrows: usize, ncols: usize) -> Self {
+        Self::from_slice(data, nrows, ncols, contiguous_stride(ncols), 1)
+    }
+
+    #[inline]
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+    #[inline]
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+    #[inline]
+    pub fn row_stride(&self) -> isize {
+        self.row_stride
+    }
+    #[inline]
+    pub fn col_stride(&self) -> isize {
+        self.col_stride
+    }
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr
+    }
+
+    /// Returns an immutable view over the same matrix.
+    #[inline]
+    pub fn rb(&self) -> MatRef<'_, T> {
+        MatRef {
+            ptr: self.ptr,
+            nrows: self.nrows,
+            ncols: self.ncols,
+            row_stride: self.row_stride,
+            col_stride: self.col_stride,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Returns a mutable view over the same matrix, with a shorter lifetime.
+    #[inline]
+    pub fn rb_mut(&mut self) -> MatMut<'_, T> {
+        MatMut {
+            ptr: self.ptr,
+            nrows: self.nrows,
+            ncols: self.ncols,
+            row_stride: self.row_stride,
+            col_stride: self.col_stride,
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> From<MatMut<'a, T>> for MatRef<'a, T> {
+    #[inline]
+    fn from(value: MatMut<'a, T>) -> Self {
+        Self {
+            ptr: value.ptr,
+            nrows: value.nrows,
+            ncols: value.ncols,
+            row_stride: value.row_stride,
+            col_stride: value.col_stride,
+            __marker: PhantomData,
+        }
+    }
+}
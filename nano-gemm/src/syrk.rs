@@ -0,0 +1,124 @@
+use crate::{GemmScalar, MatMut, MatRef, Plan, Triangle};
+
+/// Plan for a symmetric rank-k update `dst = alpha * dst + beta * a * a^T`, or a Hermitian rank-k
+/// update `dst = alpha * dst + beta * a * a^H`, where `a` is an `n × k` matrix.
+///
+/// Only the elements of `dst` in the triangle the plan was created with are computed, read and
+/// written. The tiles of `dst` outside of the triangle are skipped entirely.
+#[derive(Copy, Clone)]
+pub struct SyrkPlan<T> {
+    plan: Plan<T>,
+    triangle: Triangle,
+}
+
+impl<T: GemmScalar> SyrkPlan<T> {
+    /// Creates a plan for updating the `triangle` of an `n × n` matrix with the product of an
+    /// `n × k` matrix by its transpose, with arbitrary strides.
+    #[track_caller]
+    pub fn new(n: usize, k: usize, triangle: Triangle) -> Self {
+        Self::new_impl(n, k, triangle, false)
+    }
+
+    /// Same as [`SyrkPlan::new`], but `a` and `dst` must have a unit row stride.
+    #[track_caller]
+    pub fn new_colmajor(n: usize, k: usize, triangle: Triangle) -> Self {
+        Self::new_impl(n, k, triangle, true)
+    }
+
+    #[track_caller]
+    fn new_impl(n: usize, k: usize, triangle: Triangle, is_col_major: bool) -> Self {
+        let mut plan = T::new_plan_impl(n, n, k, is_col_major);
+        plan.triangle = Some(triangle);
+        Self { plan, triangle }
+    }
+
+    /// Returns the triangle of `dst` updated by the plan.
+    #[inline]
+    pub fn triangle(&self) -> Triangle {
+        self.triangle
+    }
+
+    /// Computes `dst = alpha * dst + beta * a * a^T`, or `dst = alpha * dst + beta * a * a^H` if
+    /// `conj` is `true`, or the same without the `alpha * dst` term if `alpha` is zero.
+    ///
+    /// With `conj`, the imaginary parts of the diagonal of `dst` are set to zero, as expected of
+    /// a Hermitian matrix. This matches the result when `alpha` and `beta` are real.
+    ///
+    /// # Safety
+    /// The dimensions must match the ones the plan was created with, and `dst` and `a` must be
+    /// valid for the given strides. `dst` must not overlap with `a`.
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn execute_unchecked(
+        &self,
+        n: usize,
+        k: usize,
+        dst: *mut T,
+        dst_rs: isize,
+        dst_cs: isize,
+        a: *const T,
+        a_rs: isize,
+        a_cs: isize,
+        alpha: T,
+        beta: T,
+        conj: bool,
+    ) {
+        // the transpose of `a` is read through the same storage
+        self.plan.execute_unchecked(
+            n, n, k, dst, dst_rs, dst_cs, a, a_rs, a_cs, a, a_cs, a_rs, alpha, beta, false, conj,
+        );
+
+        if conj {
+            for i in 0..n as isize {
+                let dst = dst.offset(i * dst_rs + i * dst_cs);
+                *dst = (*dst).real();
+            }
+        }
+    }
+
+    /// Same as [`SyrkPlan::execute_unchecked`], taking the operands as matrix views.
+    ///
+    /// # Panics
+    /// Panics if the dimensions of the operands don't match the ones the plan was created with,
+    /// or if the plan was created with a layout that doesn't match the operand strides.
+    #[track_caller]
+    pub fn execute(&self, dst: MatMut<'_, T>, a: MatRef<'_, T>, alpha: T, beta: T, conj: bool) {
+        let mut dst = dst;
+        let (n, k) = (self.plan.m, self.plan.k);
+        equator::assert!(all(
+            dst.nrows() == n,
+            dst.ncols() == n,
+            a.nrows() == n,
+            a.ncols() == k,
+        ));
+        if let Err(err) = self.plan.check_layout(
+            n,
+            n,
+            k,
+            dst.row_stride(),
+            dst.col_stride(),
+            a.row_stride(),
+            a.col_stride(),
+            a.col_stride(),
+            a.row_stride(),
+        ) {
+            panic!("{err}");
+        }
+
+        unsafe {
+            self.execute_unchecked(
+                n,
+                k,
+                dst.as_mut_ptr(),
+                dst.row_stride(),
+                dst.col_stride(),
+                a.as_ptr(),
+                a.row_stride(),
+                a.col_stride(),
+                alpha,
+                beta,
+                conj,
+            )
+        }
+    }
+}
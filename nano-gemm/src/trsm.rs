@@ -0,0 +1,248 @@
+use crate::{GemmScalar, MatMut, MatRef, Op, Plan, Structure, Triangle};
+use equator::debug_assert;
+
+/// Side of the triangular matrix in a [`TrsmPlan`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// Solves `op(t) * x = b`.
+    Left,
+    /// Solves `x * op(t) = b`.
+    Right,
+}
+
+// size of the diagonal blocks, which are solved by substitution
+const BS: usize = 16;
+
+/// Plan for solving `op(t) * x = b` or `x * op(t) = b` in place of `b`, where `t` is a triangular
+/// matrix, and `x` and `b` are `m × n` matrices.
+///
+/// The diagonal blocks of `t` are solved by substitution, and the updates by the off-diagonal
+/// blocks are applied with the kernels of [`Plan`].
+#[derive(Copy, Clone)]
+pub struct TrsmPlan<T> {
+    m: usize,
+    n: usize,
+    side: Side,
+    structure: Structure,
+    op: Op,
+    // updates of a block of rows of `b` by a block of `t`, indexed by whether the blocks of rows
+    // and columns of `t` are the last ones
+    updates: [[Plan<T>; 2]; 2],
+}
+
+impl<T: GemmScalar> TrsmPlan<T> {
+    /// Creates a plan for solving a triangular system with an `m × n` right-hand side, where
+    /// `structure` gives the stored triangle of `t`, and whether its diagonal is implicitly one.
+    ///
+    /// # Panics
+    /// Panics if `structure` isn't [`Structure::Triangular`] or [`Structure::UnitTriangular`].
+    #[track_caller]
+    pub fn new(m: usize, n: usize, side: Side, structure: Structure, op: Op) -> Self {
+        equator::assert!(structure.nonzero_triangle().is_some());
+
+        // the right side solve is done as the left side solve of the transposed system
+        let (dim, nrhs) = match side {
+            Side::Left => (m, n),
+            Side::Right => (n, m),
+        };
+        let full = Ord::min(dim, BS);
+        let last = dim - dim.saturating_sub(1) / BS * BS;
+        let sizes = [full, last];
+
+        Self {
+            m,
+            n,
+            side,
+            structure,
+            op,
+            updates: core::array::from_fn(|i| {
+                core::array::from_fn(|j| Plan::new(sizes[i], nrhs, sizes[j]))
+            }),
+        }
+    }
+
+    /// Solves the system in place of `b`, with `t` optionally conjugated before `op` is applied.
+    ///
+    /// # Safety
+    /// The dimensions must match the ones the plan was created with, and `t` and `b` must be valid
+    /// for the given strides. `b` must not overlap with `t`.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn execute_unchecked(
+        &self,
+        m: usize,
+        n: usize,
+        t: *const T,
+        t_rs: isize,
+        t_cs: isize,
+        b: *mut T,
+        b_rs: isize,
+        b_cs: isize,
+        conj_t: bool,
+    ) {
+        debug_assert!(all(m == self.m, n == self.n));
+
+        let mut triangle = self.structure.nonzero_triangle().unwrap();
+        let unit = matches!(self.structure, Structure::UnitTriangular(_));
+        let (mut t_rs, mut t_cs) = (t_rs, t_cs);
+        let conj = conj_t != (self.op == Op::ConjTrans);
+
+        // `x * op(t) = b` is solved as `op(t)^T * x^T = b^T`
+        let (dim, nrhs, b_rs, b_cs) = match self.side {
+            Side::Left => (m, n, b_rs, b_cs),
+            Side::Right => (n, m, b_cs, b_rs),
+        };
+        if (self.op != Op::NoTrans) != (self.side == Side::Right) {
+            core::mem::swap(&mut t_rs, &mut t_cs);
+            triangle = triangle.transpose();
+        }
+
+        let nblocks = dim.div_ceil(BS);
+        let block = |j: usize| (j * BS, Ord::min(BS, dim - j * BS));
+
+        let solve = |j: usize| {
+            let (j0, j_bs) = block(j);
+            solve_diagonal_block(
+                triangle,
+                unit,
+                conj,
+                j_bs,
+                nrhs,
+                t.offset(j0 as isize * (t_rs + t_cs)),
+                t_rs,
+                t_cs,
+                b.offset(j0 as isize * b_rs),
+                b_rs,
+                b_cs,
+            );
+        };
+        // `b[i] -= t[i, j] * x[j]`
+        let update = |i: usize, j: usize| {
+            let (i0, i_bs) = block(i);
+            let (j0, j_bs) = block(j);
+            let plan = &self.updates[(i + 1 == nblocks) as usize][(j + 1 == nblocks) as usize];
+            plan.execute_unchecked(
+                i_bs,
+                nrhs,
+                j_bs,
+                b.offset(i0 as isize * b_rs),
+                b_rs,
+                b_cs,
+                t.offset(i0 as isize * t_rs + j0 as isize * t_cs),
+                t_rs,
+                t_cs,
+                b.offset(j0 as isize * b_rs),
+                b_rs,
+                b_cs,
+                T::ONE,
+                -T::ONE,
+                conj,
+                false,
+            );
+        };
+
+        match triangle {
+            Triangle::Lower => {
+                for j in 0..nblocks {
+                    solve(j);
+                    for i in j + 1..nblocks {
+                        update(i, j);
+                    }
+                }
+            }
+            Triangle::Upper => {
+                for j in (0..nblocks).rev() {
+                    solve(j);
+                    for i in 0..j {
+                        update(i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Same as [`TrsmPlan::execute_unchecked`], taking the operands as matrix views.
+    ///
+    /// # Panics
+    /// Panics if the dimensions of the operands don't match the ones the plan was created with.
+    #[track_caller]
+    pub fn execute(&self, t: MatRef<'_, T>, b: MatMut<'_, T>, conj_t: bool) {
+        let mut b = b;
+        let dim = match self.side {
+            Side::Left => self.m,
+            Side::Right => self.n,
+        };
+        equator::assert!(all(
+            t.nrows() == dim,
+            t.ncols() == dim,
+            b.nrows() == self.m,
+            b.ncols() == self.n,
+        ));
+
+        unsafe {
+            self.execute_unchecked(
+                self.m,
+                self.n,
+                t.as_ptr(),
+                t.row_stride(),
+                t.col_stride(),
+                b.as_mut_ptr(),
+                b.row_stride(),
+                b.col_stride(),
+                conj_t,
+            )
+        }
+    }
+}
+
+/// Solves `t * x = b` in place of `b` by substitution, where `t` is a small triangular matrix.
+#[allow(clippy::too_many_arguments)]
+unsafe fn solve_diagonal_block<T: GemmScalar>(
+    triangle: Triangle,
+    unit: bool,
+    conj: bool,
+    dim: usize,
+    nrhs: usize,
+    t: *const T,
+    t_rs: isize,
+    t_cs: isize,
+    b: *mut T,
+    b_rs: isize,
+    b_cs: isize,
+) {
+    let t_at = |i: usize, j: usize| {
+        let t = *t.offset(i as isize * t_rs + j as isize * t_cs);
+        if conj {
+            t.conj()
+        } else {
+            t
+        }
+    };
+
+    for col in 0..nrhs {
+        let b = b.offset(col as isize * b_cs);
+        let b_at = |i: usize| b.offset(i as isize * b_rs);
+
+        let solve_row = |i: usize, known: core::ops::Range<usize>| {
+            let mut x = *b_at(i);
+            for p in known {
+                x = x - t_at(i, p) * *b_at(p);
+            }
+            if !unit {
+                x = x / t_at(i, i);
+            }
+            *b_at(i) = x;
+        };
+        match triangle {
+            Triangle::Lower => {
+                for i in 0..dim {
+                    solve_row(i, 0..i);
+                }
+            }
+            Triangle::Upper => {
+                for i in (0..dim).rev() {
+                    solve_row(i, i + 1..dim);
+                }
+            }
+        }
+    }
+}